@@ -0,0 +1,43 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+//! Measures the cost of reading each clock source. Run it with cargo bench.
+
+extern crate rustler;
+
+use std::hint;
+use rustler::ticks::ticks;
+use rustler::clock::clock;
+
+/// ITERATIONS is the number of calls to now timed for each clock source.
+const ITERATIONS: u64 = 10_000_000;
+
+fn main() {
+
+    for source in clock::SOURCES.iter() {
+
+        let clock: Box<clock::Clock + Send> = match clock::clock(*source) {
+            Some(value) => value,
+            None => { println!("{:>9}: unavailable", source.name()); continue; },
+        };
+
+        for _ in 0..(ITERATIONS / 10) {
+            hint::black_box(clock.now());
+        }
+
+        let before: ticks::Ticks = ticks::monotonic();
+        for _ in 0..ITERATIONS {
+            hint::black_box(clock.now());
+        }
+        let after: ticks::Ticks = ticks::monotonic();
+
+        println!("{:>9}: {:7.2}ns/call frequency={}Hz resolution={}ticks", source.name(), ((after - before) as f64) / (ITERATIONS as f64), clock.frequency(), clock.resolution());
+
+    }
+
+}
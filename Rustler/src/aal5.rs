@@ -0,0 +1,334 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements ATM Adaptation Layer 5 (AAL5) segmentation and reassembly. A
+/// frame of up to 65535 bytes becomes a CPCS-PDU by appending zero to
+/// forty-seven bytes of padding and an eight byte trailer (CPCS-UU, CPI, the
+/// length of the frame, and a CRC-32 over everything before the CRC) so that
+/// it is a whole number of forty-eight byte cell payloads. Each payload gets
+/// a five byte UNI cell header with a HEC, and the last cell of each frame is
+/// marked with the ATM-user-to-ATM-user (AUU) bit of its payload type. The
+/// resulting fifty-three byte cells can be shaped through any throttle, such
+/// as a Contract, at cell granularity, one event per cell. The Reassembler
+/// puts the frames back together, detecting damaged headers, lost or damaged
+/// cells, and oversized frames.
+///
+/// REFERENCES
+///
+/// ITU-T, "B-ISDN ATM Adaptation Layer specification: Type 5 AAL", I.363.5,
+/// 1996-08
+///
+/// ITU-T, "B-ISDN User-Network Interface - Physical layer specification",
+/// I.432.1, 1999-02
+///
+pub mod aal5 {
+
+    use std::io;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+
+    /// CELL is the size of a cell in bytes.
+    pub const CELL: usize = 53;
+
+    /// HEADER is the size of a cell header in bytes.
+    pub const HEADER: usize = 5;
+
+    /// PAYLOAD is the size of a cell payload in bytes.
+    pub const PAYLOAD: usize = CELL - HEADER;
+
+    /// TRAILER is the size of the CPCS-PDU trailer in bytes.
+    pub const TRAILER: usize = 8;
+
+    /// MAXIMUM is the largest frame in bytes.
+    pub const MAXIMUM: usize = 65535;
+
+    /// AUU is the bit in the payload type that marks the last cell of a frame.
+    pub const AUU: u8 = 0x01;
+
+    /// Cell is a cell, header and payload.
+    pub type Cell = [u8; CELL];
+
+    /// Frame is a reassembled frame: its CPCS-UU byte and its bytes.
+    pub type Frame = (u8, Vec<u8>);
+
+    /*** CRC ***/
+
+    /// table computes the table for the CRC-32 used by AAL5, which is that of
+    /// IEEE 802.3 (polynomial 0x04C11DB7) computed most significant bit first.
+    const fn table() -> [u32; 256] {
+        let mut table: [u32; 256] = [0; 256];
+        let mut ii: usize = 0;
+
+        while ii < 256 {
+            let mut crc: u32 = (ii as u32) << 24;
+            let mut bit: usize = 0;
+            while bit < 8 {
+                crc = if (crc & 0x8000_0000) != 0 { (crc << 1) ^ 0x04c1_1db7 } else { crc << 1 };
+                bit += 1;
+            }
+            table[ii] = crc;
+            ii += 1;
+        }
+
+        table
+    }
+
+    static TABLE: [u32; 256] = table();
+
+    /// crc32 returns the AAL5 CRC-32 of the specified bytes: initialized to
+    /// all ones, and complemented at the end.
+    pub fn crc32(data: & [u8]) -> u32 {
+        let mut crc: u32 = 0xffff_ffff;
+
+        for byte in data {
+            crc = (crc << 8) ^ TABLE[(((crc >> 24) as u8) ^ *byte) as usize];
+        }
+
+        !crc
+    }
+
+    /// hec returns the header error control byte for the first four bytes of
+    /// a cell header: the CRC-8 (polynomial 0x07) of those bytes exclusive-ORed
+    /// with the coset 0x55.
+    pub fn hec(header: & [u8]) -> u8 {
+        let mut crc: u8 = 0;
+
+        for byte in &header[..(HEADER - 1)] {
+            crc ^= *byte;
+            for _ in 0..8 {
+                crc = if (crc & 0x80) != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+            }
+        }
+
+        crc ^ 0x55
+    }
+
+    /*** HEADER ***/
+
+    /// Header is the contents of a UNI cell header.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Header {
+        pub gfc:    u8,                         // Generic flow control (4 bits).
+        pub vpi:    u8,                         // Virtual path identifier.
+        pub vci:    u16,                        // Virtual channel identifier.
+        pub pti:    u8,                         // Payload type (3 bits).
+        pub clp:    bool,                       // Cell loss priority.
+    }
+
+    impl string::ToString for Header {
+
+        fn to_string(& self) -> string::String {
+            format!("Header@{:p}:{{gfc:{},vpi:{},vci:{},pti:{},clp:{}}}",
+                self,
+                self.gfc, self.vpi, self.vci, self.pti, self.clp as u8)
+        }
+
+    }
+
+    impl Header {
+
+        /// Allocate a new Header for user data on the specified virtual path
+        /// and channel with the specified cell loss priority.
+        pub fn new(vpi: u8, vci: u16, clp: bool) -> Header {
+            Header { gfc: 0, vpi, vci, pti: 0, clp }
+        }
+
+        /// encode returns the header as five bytes including the HEC.
+        pub fn encode(& self) -> [u8; HEADER] {
+            let mut bytes: [u8; HEADER] = [
+                ((self.gfc & 0x0f) << 4) | (self.vpi >> 4),
+                ((self.vpi & 0x0f) << 4) | ((self.vci >> 12) as u8),
+                (self.vci >> 4) as u8,
+                (((self.vci & 0x0f) as u8) << 4) | ((self.pti & 0x07) << 1) | (self.clp as u8),
+                0,
+            ];
+
+            bytes[HEADER - 1] = hec(&bytes);
+
+            bytes
+        }
+
+        /// decode returns the header in the first five bytes of a cell, or an
+        /// error if its HEC is not correct.
+        pub fn decode(bytes: & [u8]) -> io::Result<Header> {
+            if bytes.len() < HEADER {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "cell header too short"));
+            }
+            if hec(bytes) != bytes[HEADER - 1] {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "HEC error"));
+            }
+
+            Ok(Header {
+                gfc:    bytes[0] >> 4,
+                vpi:    (bytes[0] << 4) | (bytes[1] >> 4),
+                vci:    (((bytes[1] & 0x0f) as u16) << 12) | ((bytes[2] as u16) << 4) | ((bytes[3] >> 4) as u16),
+                pti:    (bytes[3] >> 1) & 0x07,
+                clp:    (bytes[3] & 0x01) != 0,
+            })
+        }
+
+    }
+
+    /*** SEGMENTATION ***/
+
+    /// cells returns the number of cells that a frame of the specified length
+    /// in bytes segments into.
+    pub fn cells(length: usize) -> usize {
+        (length + TRAILER).div_ceil(PAYLOAD)
+    }
+
+    /// segment returns the cells of the CPCS-PDU of the specified frame with
+    /// the specified CPCS-UU byte, each with the specified header, and the
+    /// last with the AUU bit set in its payload type.
+    pub fn segment(header: Header, uu: u8, frame: & [u8]) -> Vec<Cell> {
+        assert!(frame.len() <= MAXIMUM);
+        let count: usize = cells(frame.len());
+        let mut pdu: Vec<u8> = Vec::with_capacity(count * PAYLOAD);
+        let mut result: Vec<Cell> = Vec::with_capacity(count);
+
+        pdu.extend_from_slice(frame);
+        pdu.resize(count * PAYLOAD - TRAILER, 0);
+        pdu.push(uu);
+        pdu.push(0); // CPI
+        pdu.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+        let crc: u32 = crc32(&pdu);
+        pdu.extend_from_slice(&crc.to_be_bytes());
+
+        for (ii, payload) in pdu.chunks(PAYLOAD).enumerate() {
+            let mut cell: Cell = [0; CELL];
+            let mut each: Header = header;
+            each.pti = if (ii + 1) == count { header.pti | AUU } else { header.pti & !AUU };
+            cell[..HEADER].copy_from_slice(&each.encode());
+            cell[HEADER..].copy_from_slice(payload);
+            result.push(cell);
+        }
+
+        result
+    }
+
+    /// shape passes each of the cells to the emitter as the throttle permits,
+    /// charging it one event per cell and sleeping until the deadline of each
+    /// cell that must be delayed. It stops at the first error.
+    pub fn shape<F: FnMut(& Cell) -> io::Result<()>>(throttle: & mut throttle::Throttle, cells: & [Cell], mut emit: F) -> io::Result<()> {
+        let mut deadline: ticks::Ticks;
+        let mut delay: ticks::Ticks;
+
+        for cell in cells {
+            deadline = ticks::now();
+            delay = throttle.request(deadline);
+            if delay > 0 {
+                deadline += delay;
+                ticks::sleep_until(deadline);
+            }
+            emit(cell)?;
+            throttle.admits(deadline, 1);
+        }
+
+        Ok(())
+    }
+
+    /*** REASSEMBLY ***/
+
+    /// Reassembler puts frames back together from their cells.
+    pub struct Reassembler {
+        pdu:        Vec<u8>,                    // CPCS-PDU so far.
+        discarding: bool,                       // Discarding to end of frame.
+        frames:     u64,                        // Frames reassembled.
+        errors:     u64,                        // Errors detected.
+    }
+
+    impl string::ToString for Reassembler {
+
+        fn to_string(& self) -> string::String {
+            format!("Reassembler@{:p}:{{p:{},d:{},f:{},e:{}}}",
+                self,
+                self.pdu.len(), self.discarding, self.frames, self.errors)
+        }
+
+    }
+
+    impl Reassembler {
+
+        /// Allocate a new Reassembler.
+        pub fn new() -> Reassembler {
+            Reassembler { pdu: Vec::new(), discarding: false, frames: 0, errors: 0 }
+        }
+
+        /// get_frames returns the number of frames reassembled.
+        pub fn get_frames(& self) -> u64 {
+            self.frames
+        }
+
+        /// get_errors returns the number of errors detected.
+        pub fn get_errors(& self) -> u64 {
+            self.errors
+        }
+
+        fn error(& mut self, kind: io::ErrorKind, message: &str) -> io::Result<Option<Frame>> {
+            self.errors += 1;
+            Err(io::Error::new(kind, message))
+        }
+
+        /// push adds a cell to the frame being reassembled and returns the
+        /// frame if the cell was its last, None if more cells are needed, or
+        /// an error if the cell or the frame was damaged. A cell with a bad
+        /// HEC is dropped. A frame that is too long is dropped through its
+        /// last cell. A frame whose length or CRC-32 does not check, as when
+        /// cells were lost, is dropped.
+        pub fn push(& mut self, cell: & [u8]) -> io::Result<Option<Frame>> {
+            if cell.len() != CELL {
+                return self.error(io::ErrorKind::InvalidInput, "cell size not valid");
+            }
+
+            let header: Header = match Header::decode(cell) {
+                Ok(header) => header,
+                Err(error) => { self.errors += 1; return Err(error); },
+            };
+            let last: bool = (header.pti & AUU) != 0;
+
+            if self.discarding {
+                if last {
+                    self.discarding = false;
+                }
+                return Ok(None);
+            }
+
+            self.pdu.extend_from_slice(&cell[HEADER..]);
+
+            if self.pdu.len() > (cells(MAXIMUM) * PAYLOAD) {
+                self.pdu.clear();
+                self.discarding = !last;
+                return self.error(io::ErrorKind::InvalidData, "frame too long");
+            }
+
+            if !last {
+                return Ok(None);
+            }
+
+            let pdu: Vec<u8> = self.pdu.split_off(0);
+            let size: usize = pdu.len();
+            let uu: u8 = pdu[size - TRAILER];
+            let length: usize = u16::from_be_bytes([pdu[size - 6], pdu[size - 5]]) as usize;
+            let crc: u32 = u32::from_be_bytes([pdu[size - 4], pdu[size - 3], pdu[size - 2], pdu[size - 1]]);
+
+            if (length + TRAILER) > size || (size - length - TRAILER) >= PAYLOAD {
+                return self.error(io::ErrorKind::InvalidData, "length error");
+            }
+            if crc32(&pdu[..(size - 4)]) != crc {
+                return self.error(io::ErrorKind::InvalidData, "CRC-32 error");
+            }
+
+            self.frames += 1;
+
+            Ok(Some((uu, pdu[..length].to_vec())))
+        }
+
+    }
+
+}
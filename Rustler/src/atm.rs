@@ -0,0 +1,441 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements the cell conformance definitions of the ATM Forum Traffic
+/// Management Specification for the CBR.1, VBR.1, VBR.2, VBR.3, GFR.1, and
+/// GFR.2 service categories, as applied by usage parameter control (UPC) to
+/// cells arriving on a connection. Each cell carries a cell loss priority
+/// (CLP): CLP=0 cells are the ones the contract protects, and CLP=1 cells are
+/// the ones the network may drop first. Depending on the category, a rate is
+/// enforced on the aggregate CLP=0+1 flow or on the CLP=0 flow alone. Each
+/// cell is given a Verdict: it passes unchanged, it passes tagged as CLP=1
+/// instead of being discarded (VBR.3 and GFR.2), or it is discarded. As in
+/// the specification, which describes policing and not shaping, a cell that
+/// is discarded, or that is tagged and so leaves the CLP=0 flow, does not
+/// advance the theoretical arrival time of the GCRAs that it failed. Each
+/// GCRA is a Gcra whose increment T is the inverse of the contracted rate and
+/// whose limit tau is the tolerance, both in ticks.
+///
+/// REFERENCES
+///
+/// N. Giroux et al., Traffic Management Specification Version 4.1, ATM Forum,
+/// af-tm-0121.000, 1999-03, 4.4 and 4.5
+///
+/// J. Sloan, "ATM Traffic Management", 2005-08,
+/// http://www.diag.com/reports/ATMTrafficManagement.html
+///
+pub mod atm {
+
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle::Throttle;
+    use gcra::gcra;
+    use contract::contract;
+
+    /// Clp is the cell loss priority of a cell.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Clp {
+        Zero,
+        One,
+    }
+
+    /// Verdict is what usage parameter control does with a cell.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Verdict {
+        Pass,                                   // Conforming, CLP unchanged.
+        Tag,                                    // Conforming as CLP=1.
+        Discard,                                // Non-conforming.
+    }
+
+    /// Category is the trait for the conformance definition of a cell based
+    /// service category.
+    pub trait Category {
+
+        /// cell returns the verdict for a cell with the specified CLP arriving
+        /// at the specified time in ticks, charging it to every GCRA for which
+        /// it is conforming and that applies to its flow.
+        fn cell(& mut self, now: ticks::Ticks, clp: Clp) -> Verdict;
+
+        /// reset returns the conformance definition to its initial state at
+        /// the specified time in ticks.
+        fn reset(& mut self, now: ticks::Ticks);
+
+        /// name returns the name of the conformance definition.
+        fn name(& self) -> &'static str;
+
+    }
+
+    /// conforms returns true if a cell arriving at the specified time in ticks
+    /// conforms to the GCRA, without charging it.
+    fn conforms(gcra: & gcra::Gcra, now: ticks::Ticks) -> bool {
+        gcra.peek(now, 1) == 0
+    }
+
+    /// charge charges a conforming cell arriving at the specified time in
+    /// ticks to the GCRA.
+    fn charge(gcra: & mut gcra::Gcra, now: ticks::Ticks) {
+        gcra.admit(now);
+    }
+
+    /*** CBR.1 ***/
+
+    /// Cbr1 is CBR.1: GCRA(T_PCR, tau_PCR) on the CLP=0+1 flow.
+    #[derive(Clone, Copy)]
+    pub struct Cbr1 {
+        pcr01:      gcra::Gcra,                 // PCR on CLP=0+1.
+    }
+
+    impl string::ToString for Cbr1 {
+
+        fn to_string(& self) -> string::String {
+            format!("Cbr1@{:p}:{{p:{}}}", self, self.pcr01.to_string())
+        }
+
+    }
+
+    impl Cbr1 {
+
+        /// Allocate a new Cbr1 given the peak increment and the cell delay
+        /// variation tolerance in ticks, and the current time in ticks.
+        pub fn new(pcrincrement: ticks::Ticks, cdvt: ticks::Ticks, now: ticks::Ticks) -> Cbr1 {
+            Cbr1 { pcr01: gcra::Gcra::new().init(pcrincrement, cdvt, now) }
+        }
+
+    }
+
+    impl Category for Cbr1 {
+
+        fn cell(& mut self, now: ticks::Ticks, _clp: Clp) -> Verdict {
+            if conforms(&self.pcr01, now) {
+                charge(& mut self.pcr01, now);
+                Verdict::Pass
+            } else {
+                Verdict::Discard
+            }
+        }
+
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.pcr01.reset(now);
+        }
+
+        fn name(& self) -> &'static str {
+            "CBR.1"
+        }
+
+    }
+
+    /*** VBR.1 ***/
+
+    /// Vbr1 is VBR.1: GCRA(T_PCR, tau_PCR) and GCRA(T_SCR, tau_SCR + BT) both
+    /// on the CLP=0+1 flow, which is exactly a Contract.
+    #[derive(Clone, Copy)]
+    pub struct Vbr1 {
+        contract:   contract::Contract,         // PCR and SCR on CLP=0+1.
+    }
+
+    impl string::ToString for Vbr1 {
+
+        fn to_string(& self) -> string::String {
+            format!("Vbr1@{:p}:{{c:{}}}", self, self.contract.to_string())
+        }
+
+    }
+
+    impl Vbr1 {
+
+        /// Allocate a new Vbr1 given the peak increment and the cell delay
+        /// variation tolerance in ticks, the sustained increment in ticks and
+        /// the burst tolerance (including any CDVT for the SCR) in ticks as
+        /// computed by contract::bursttolerance, and the current time in ticks.
+        pub fn new(pcrincrement: ticks::Ticks, cdvt: ticks::Ticks, scrincrement: ticks::Ticks, bursttolerance: ticks::Ticks, now: ticks::Ticks) -> Vbr1 {
+            Vbr1 { contract: contract::Contract::new().init(pcrincrement, cdvt, scrincrement, bursttolerance, now) }
+        }
+
+    }
+
+    impl Category for Vbr1 {
+
+        fn cell(& mut self, now: ticks::Ticks, _clp: Clp) -> Verdict {
+            if self.contract.peek(now, 1) == 0 {
+                self.contract.admit(now);
+                Verdict::Pass
+            } else {
+                Verdict::Discard
+            }
+        }
+
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.contract.reset(now);
+        }
+
+        fn name(& self) -> &'static str {
+            "VBR.1"
+        }
+
+    }
+
+    /*** VBR.2 AND VBR.3 ***/
+
+    /// Dual is GCRA(T_PCR, tau_PCR) on the CLP=0+1 flow and GCRA(T_SCR,
+    /// tau_SCR + BT) on the CLP=0 flow alone, with or without tagging.
+    #[derive(Clone, Copy)]
+    struct Dual {
+        pcr01:      gcra::Gcra,                 // PCR on CLP=0+1.
+        scr0:       gcra::Gcra,                 // SCR on CLP=0.
+    }
+
+    impl Dual {
+
+        fn new(pcrincrement: ticks::Ticks, cdvt: ticks::Ticks, scrincrement: ticks::Ticks, bursttolerance: ticks::Ticks, now: ticks::Ticks) -> Dual {
+            Dual {
+                pcr01:      gcra::Gcra::new().init(pcrincrement, cdvt, now),
+                scr0:       gcra::Gcra::new().init(scrincrement, bursttolerance, now),
+            }
+        }
+
+        /// cell applies the conformance definition. A CLP=1 cell need only
+        /// conform to the PCR. A CLP=0 cell that conforms to both passes. A
+        /// CLP=0 cell that conforms to the PCR but not to the SCR is tagged
+        /// and charged only to the PCR if tagging, and is otherwise discarded.
+        fn cell(& mut self, now: ticks::Ticks, clp: Clp, tagging: bool) -> Verdict {
+            if !conforms(&self.pcr01, now) {
+                Verdict::Discard
+            } else if clp == Clp::One {
+                charge(& mut self.pcr01, now);
+                Verdict::Pass
+            } else if conforms(&self.scr0, now) {
+                charge(& mut self.pcr01, now);
+                charge(& mut self.scr0, now);
+                Verdict::Pass
+            } else if tagging {
+                charge(& mut self.pcr01, now);
+                Verdict::Tag
+            } else {
+                Verdict::Discard
+            }
+        }
+
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.pcr01.reset(now);
+            self.scr0.reset(now);
+        }
+
+        /// fields formats both GCRAs for Vbr2 and Vbr3.
+        fn fields(& self) -> string::String {
+            format!("p:{},s:{}", self.pcr01.to_string(), self.scr0.to_string())
+        }
+
+    }
+
+    /// Vbr2 is VBR.2: GCRA(T_PCR, tau_PCR) on the CLP=0+1 flow and
+    /// GCRA(T_SCR, tau_SCR + BT) on the CLP=0 flow, without tagging.
+    #[derive(Clone, Copy)]
+    pub struct Vbr2 {
+        dual:       Dual,                       // PCR0+1 and SCR0.
+    }
+
+    impl string::ToString for Vbr2 {
+
+        fn to_string(& self) -> string::String {
+            format!("Vbr2@{:p}:{{{}}}", self, self.dual.fields())
+        }
+
+    }
+
+    impl Vbr2 {
+
+        /// Allocate a new Vbr2 given the same parameters as a Vbr1.
+        pub fn new(pcrincrement: ticks::Ticks, cdvt: ticks::Ticks, scrincrement: ticks::Ticks, bursttolerance: ticks::Ticks, now: ticks::Ticks) -> Vbr2 {
+            Vbr2 { dual: Dual::new(pcrincrement, cdvt, scrincrement, bursttolerance, now) }
+        }
+
+    }
+
+    impl Category for Vbr2 {
+
+        fn cell(& mut self, now: ticks::Ticks, clp: Clp) -> Verdict {
+            self.dual.cell(now, clp, false)
+        }
+
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.dual.reset(now);
+        }
+
+        fn name(& self) -> &'static str {
+            "VBR.2"
+        }
+
+    }
+
+    /// Vbr3 is VBR.3: the same as VBR.2, except that a CLP=0 cell that is
+    /// non-conforming to the SCR but conforming to the PCR is tagged as CLP=1
+    /// instead of being discarded.
+    #[derive(Clone, Copy)]
+    pub struct Vbr3 {
+        dual:       Dual,                       // PCR0+1 and SCR0.
+    }
+
+    impl string::ToString for Vbr3 {
+
+        fn to_string(& self) -> string::String {
+            format!("Vbr3@{:p}:{{{}}}", self, self.dual.fields())
+        }
+
+    }
+
+    impl Vbr3 {
+
+        /// Allocate a new Vbr3 given the same parameters as a Vbr1.
+        pub fn new(pcrincrement: ticks::Ticks, cdvt: ticks::Ticks, scrincrement: ticks::Ticks, bursttolerance: ticks::Ticks, now: ticks::Ticks) -> Vbr3 {
+            Vbr3 { dual: Dual::new(pcrincrement, cdvt, scrincrement, bursttolerance, now) }
+        }
+
+    }
+
+    impl Category for Vbr3 {
+
+        fn cell(& mut self, now: ticks::Ticks, clp: Clp) -> Verdict {
+            self.dual.cell(now, clp, true)
+        }
+
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.dual.reset(now);
+        }
+
+        fn name(& self) -> &'static str {
+            "VBR.3"
+        }
+
+    }
+
+    /*** GFR.1 AND GFR.2 ***/
+
+    /// Gfr is GFR.1 or, if tagging, GFR.2. GFR is frame based: the cells of a
+    /// frame (an AAL5 CPCS-PDU) are delimited by the last cell of each frame.
+    /// A cell is non-conforming if it does not conform to GCRA(T_PCR, tau_PCR)
+    /// on the CLP=0+1 flow, if its CLP differs from that of the first cell of
+    /// its frame, or if its frame is longer than the maximum frame size (MFS)
+    /// in cells; once a cell of a frame is discarded the rest of the frame is
+    /// discarded too, except for its last cell, which is passed if it conforms
+    /// to the PCR so that the far end can still find the frame boundary.
+    /// Whether a CLP=0 frame is eligible for the minimum cell rate (MCR)
+    /// guarantee is decided at its first cell by the frame based F-GCRA(T_MCR,
+    /// tau_MCR + BT); every cell of an eligible frame is then charged to it.
+    /// A frame that is not eligible is still conforming, but GFR.2 tags all
+    /// of its cells as CLP=1.
+    #[derive(Clone, Copy)]
+    pub struct Gfr {
+        pcr01:      gcra::Gcra,                 // PCR on CLP=0+1.
+        mcr:        gcra::Gcra,                 // F-GCRA for MCR on CLP=0.
+        mfs:        usize,                      // Maximum frame size in cells.
+        tagging:    bool,                       // GFR.2 if true.
+        cells:      usize,                      // Cells so far in this frame.
+        clp:        Clp,                        // CLP of this frame.
+        eligible:   bool,                       // This frame is eligible.
+        discarding: bool,                       // Discarding this frame.
+    }
+
+    impl string::ToString for Gfr {
+
+        fn to_string(& self) -> string::String {
+            format!("Gfr@{:p}:{{p:{},m:{},f:{},t:{},c:{},l:{:?},e:{},d:{}}}",
+                self,
+                self.pcr01.to_string(), self.mcr.to_string(),
+                self.mfs, self.tagging, self.cells, self.clp, self.eligible, self.discarding)
+        }
+
+    }
+
+    impl Gfr {
+
+        /// Allocate a new Gfr given the peak increment and the cell delay
+        /// variation tolerance in ticks, the MCR increment and the burst
+        /// tolerance (including any CDVT for the MCR) in ticks, the maximum
+        /// frame size in cells, whether to tag frames that are not eligible
+        /// (GFR.2) or not (GFR.1), and the current time in ticks.
+        pub fn new(pcrincrement: ticks::Ticks, cdvt: ticks::Ticks, mcrincrement: ticks::Ticks, bursttolerance: ticks::Ticks, mfs: usize, tagging: bool, now: ticks::Ticks) -> Gfr {
+            assert!(mfs > 0);
+            Gfr {
+                pcr01:      gcra::Gcra::new().init(pcrincrement, cdvt, now),
+                mcr:        gcra::Gcra::new().init(mcrincrement, bursttolerance, now),
+                mfs,
+                tagging,
+                cells:      0,
+                clp:        Clp::Zero,
+                eligible:   false,
+                discarding: false,
+            }
+        }
+
+        /// cell returns the verdict for a cell with the specified CLP arriving
+        /// at the specified time in ticks, which is the last cell of its frame
+        /// if last is true.
+        pub fn cell(& mut self, now: ticks::Ticks, clp: Clp, last: bool) -> Verdict {
+            let verdict: Verdict;
+
+            if self.cells == 0 {
+                self.clp = clp;
+                self.discarding = false;
+                self.eligible = (clp == Clp::Zero) && conforms(&self.mcr, now);
+            }
+            self.cells += 1;
+
+            if self.discarding {
+                if last && conforms(&self.pcr01, now) {
+                    charge(& mut self.pcr01, now);
+                    verdict = Verdict::Pass;
+                } else {
+                    verdict = Verdict::Discard;
+                }
+            } else if (clp != self.clp) || (self.cells > self.mfs) || !conforms(&self.pcr01, now) {
+                self.discarding = true;
+                verdict = Verdict::Discard;
+            } else {
+                charge(& mut self.pcr01, now);
+                if self.eligible {
+                    charge(& mut self.mcr, now);
+                    verdict = Verdict::Pass;
+                } else if self.tagging && (clp == Clp::Zero) {
+                    verdict = Verdict::Tag;
+                } else {
+                    verdict = Verdict::Pass;
+                }
+            }
+
+            if last {
+                self.cells = 0;
+            }
+
+            verdict
+        }
+
+        /// reset returns the conformance definition to its initial state at
+        /// the specified time in ticks, at the start of a frame.
+        pub fn reset(& mut self, now: ticks::Ticks) {
+            self.pcr01.reset(now);
+            self.mcr.reset(now);
+            self.cells = 0;
+            self.clp = Clp::Zero;
+            self.eligible = false;
+            self.discarding = false;
+        }
+
+        /// is_eligible returns true if the current (or, between frames, the
+        /// last) frame is eligible for the MCR guarantee.
+        pub fn is_eligible(& self) -> bool {
+            self.eligible
+        }
+
+        /// name returns the name of the conformance definition.
+        pub fn name(& self) -> &'static str {
+            if self.tagging { "GFR.2" } else { "GFR.1" }
+        }
+
+    }
+
+}
@@ -0,0 +1,144 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate clap;
+extern crate rustler;
+
+use std::io;
+use std::io::BufRead;
+use std::process;
+use clap::Arg;
+use clap::App;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::trace::trace;
+
+fn main() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut replayer: Option<Box<throttle::Throttle>> = None;
+    let mut records: usize = 0;
+    let mut differences: usize = 0;
+    let mut invalid: usize = 0;
+    let mut number: usize = 0;
+    let mut record: trace::Record;
+    let mut replayed: trace::Record;
+
+    let matches = App::new("replay")
+                          .version("1.0")
+                          .author("Chip Overclock <coverclock@diag.com>")
+                          .about("Replays a throttle trace from standard input against a traffic contract and reports the decisions that differ.")
+                          .arg(Arg::with_name("Debug")
+                               .short("D")
+                               .help("Enables debug output."))
+                          .arg(Arg::with_name("Verbose")
+                               .short("V")
+                               .help("Enable verbose output."))
+                           .arg(Arg::with_name("peakrate")
+                               .short("p")
+                               .help("Sets peak rate.")
+                               .takes_value(true)
+                               .value_name("EVENTSPERSECOND"))
+                           .arg(Arg::with_name("sustainedrate")
+                               .short("s")
+                               .help("Sets sustained rate and replays against a contract instead of a GCRA.")
+                               .takes_value(true)
+                               .value_name("EVENTSPERSECOND"))
+                           .arg(Arg::with_name("burstsize")
+                               .short("b")
+                               .help("Sets maximum burst size.")
+                               .takes_value(true)
+                               .value_name("EVENTS"))
+                          .get_matches();
+
+    let debug: bool = matches.is_present("Debug");
+
+    let verbose: bool = matches.is_present("Verbose");
+
+    let mut peakrate: throttle::Events = 1;
+    if matches.is_present("peakrate") {
+        let value = match usize::from_str_radix(matches.value_of("peakrate").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        peakrate = value as throttle::Events;
+    }
+    let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
+
+    let mut sustainedrate: throttle::Events = 0;
+    if matches.is_present("sustainedrate") {
+        let value = match usize::from_str_radix(matches.value_of("sustainedrate").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        sustainedrate = value as throttle::Events;
+    }
+    let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
+
+    let mut burstsize: throttle::Events = 1;
+    if matches.is_present("burstsize") {
+        let value = match usize::from_str_radix(matches.value_of("burstsize").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        burstsize = value as throttle::Events;
+    }
+
+    let stdin = io::stdin();
+
+    for line in stdin.lock().lines() {
+
+        let line: String = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        number += 1;
+
+        record = match trace::Record::parse(&line) {
+            Some(record) => record,
+            None if line == trace::HEADER => continue,
+            None => { invalid += 1; eprintln!("Invalid: {}: {}", number, line); continue; },
+        };
+
+        if replayer.is_none() {
+            if matches.is_present("sustainedrate") {
+                let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize);
+                replayer = Some(Box::new(contract::Contract::new().init(peakincrement, 0 /* jittertolerance */, sustainedincrement, bursttolerance, record.now)));
+            } else {
+                let jittertolerance: ticks::Ticks = gcra::jittertolerance(peakincrement, burstsize);
+                replayer = Some(Box::new(gcra::Gcra::new().init(peakincrement, jittertolerance, record.now)));
+            }
+            if verbose { eprintln!("Throttle: {}", replayer.as_ref().unwrap().as_string()); }
+        }
+
+        replayed = trace::replay(&mut **replayer.as_mut().unwrap(), &record);
+        records += 1;
+
+        if !record.is_decision(&replayed) {
+            differences += 1;
+            println!("{}: {} {}", number, record.to_string(), replayed.to_string());
+        } else if debug {
+            eprintln!("Same: {}: {}", number, replayed.to_string());
+        } else {
+            // Do nothing.
+        }
+
+    }
+
+    if verbose {
+        eprintln!("Records: {}.", records);
+        eprintln!("Invalid: {}.", invalid);
+        eprintln!("Differences: {}.", differences);
+    }
+
+    if (differences > 0) || (invalid > 0) {
+        process::exit(1);
+    }
+
+}
@@ -7,12 +7,15 @@
 // https://github.com/coverclock/com-diag-rustler
 
 extern crate clap;
+extern crate libc;
 extern crate rustler;
 
+use std::fs;
 use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::io::ErrorKind;
+use std::process;
 use clap::Arg;
 use clap::App;
 use rustler::ticks::ticks;
@@ -20,6 +23,192 @@ use rustler::throttle::throttle;
 use rustler::throttle::throttle::Throttle;
 use rustler::gcra::gcra;
 use rustler::contract::contract;
+use rustler::shaper::shaper;
+use rustler::cost::cost;
+use rustler::clock::clock;
+use std::sync;
+use std::sync::atomic;
+use std::thread;
+
+/// Shared is the queueing shaper and the end of file indication shared between
+/// the reader and the writer, along with the condition the writer waits on.
+type Shared = sync::Arc<(sync::Mutex<(shaper::Shaper<Vec<u8>, contract::Contract>, bool)>, sync::Condvar)>;
+
+/// Parameters are the peak rate and sustained rate in bytes per second and
+/// the maximum burst size in bytes of a contract.
+type Parameters = (throttle::Events, throttle::Events, throttle::Events);
+
+/// HANGUP is set when SIGHUP is received and cleared when it is handled.
+static HANGUP: atomic::AtomicBool = atomic::AtomicBool::new(false);
+
+/// Note that SIGHUP was received.
+extern "C" fn hangup(_signum: libc::c_int) {
+    HANGUP.store(true, atomic::Ordering::SeqCst);
+}
+
+/// Read a contract file of lines of the form NAME=VALUE, in which NAME is
+/// peakrate, sustainedrate, or burstsize, ignoring blank lines and lines
+/// beginning with #, and return the parameters with those it names replaced.
+fn load(path: &str, parameters: Parameters) -> io::Result<Parameters> {
+    let mut result: Parameters = parameters;
+
+    for line in fs::read_to_string(path)?.lines() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.splitn(2, '=');
+        let name: &str = fields.next().unwrap().trim();
+        let value: throttle::Events = match fields.next().map(|value| value.trim().parse::<throttle::Events>()) {
+            Some(Ok(value)) if value > 0 => value,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("value not valid: {}", line))),
+        };
+        match name {
+            "peakrate" => result.0 = value,
+            "sustainedrate" => result.1 = value,
+            "burstsize" => result.2 = value,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("name not valid: {}", line))),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Return the peak increment, sustained increment, and burst tolerance in
+/// ticks of a contract with the specified parameters, its burst size being
+/// the cost of one block of that many bytes in the specified cost model.
+fn increments(parameters: Parameters, model: cost::Model, frequency: ticks::Ticks) -> (ticks::Ticks, ticks::Ticks, ticks::Ticks) {
+    let peakincrement: ticks::Ticks = gcra::increment(parameters.0, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(parameters.1, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, model.cost(parameters.2 as usize));
+
+    (peakincrement, sustainedincrement, bursttolerance)
+}
+
+/// If SIGHUP was received since the last call, re-read the contract file, if
+/// any, over the parameters from the command line, and reconfigure the
+/// contract in service at the current time, keeping its deficit. If the file
+/// cannot be read the contract is left as it is.
+fn reload(path: Option<&str>, parameters: Parameters, model: cost::Model, contract: & mut contract::Contract, verbose: bool) {
+    if !HANGUP.swap(false, atomic::Ordering::SeqCst) {
+        return;
+    }
+
+    let path: &str = match path {
+        Some(path) => path,
+        None => return,
+    };
+
+    match load(path, parameters) {
+        Ok(parameters) => {
+            let (peakincrement, sustainedincrement, bursttolerance) = increments(parameters, model, ticks::frequency());
+            contract.reconfigure(peakincrement, 0 /* jittertolerance */, sustainedincrement, bursttolerance, ticks::now());
+            if verbose { eprintln!("Contract: {}", contract.to_string()) }
+        },
+        Err(error) => { eprintln!("contract {}: {}!", path, error); },
+    }
+}
+
+/// Read blocks of at most blocksize bytes from standard input and offer them
+/// to the shared shaper, each charged as the cost model says, until end of
+/// file.
+fn reader(shared: Shared, blocksize: usize, model: cost::Model, debug: bool) {
+    let mut buffer = [0u8; 65536];
+    let mut length: usize;
+    let mut queued: bool;
+
+    loop {
+
+        length = match io::stdin().read(& mut buffer[..blocksize]) {
+            Ok(0) => break,
+            Ok(value) => value,
+            Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        if debug { eprintln!("Read: {}", length); }
+
+        {
+            let mut guard = shared.0.lock().unwrap();
+            queued = guard.0.offer(buffer[..length].to_vec(), model.cost(length), ticks::now());
+            shared.1.notify_one();
+        }
+        if debug && !queued { eprintln!("Dropped: {}", length); }
+
+    }
+
+    let mut guard = shared.0.lock().unwrap();
+    guard.1 = true;
+    shared.1.notify_one();
+}
+
+/// Emit the blocks queued in the shared shaper to standard output as its
+/// throttle permits until the reader has reached end of file and the queue
+/// is empty, sleeping (and spinning for the specified window) until each
+/// deadline, and reconfiguring its contract with the specified function.
+/// Returns the total bytes, the number of writes, and the peak rate.
+fn writer(shared: Shared, window: ticks::Ticks, reconfigure: & Fn(& mut contract::Contract), debug: bool) -> (u64, usize, f64) {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut total: u64 = 0;
+    let mut count: usize = 0;
+    let mut peak: f64 = 0.0;
+    let mut now: ticks::Ticks = 0;
+    let mut then: ticks::Ticks;
+    let mut rate: f64;
+    let mut delay: ticks::Ticks;
+    let mut deadline: ticks::Ticks;
+    let mut block: Vec<u8>;
+
+    loop {
+
+        deadline = ticks::now();
+
+        {
+            let mut guard = shared.0.lock().unwrap();
+            reconfigure(guard.0.throttle_mut());
+            delay = match guard.0.request(deadline) {
+                Some(value) => value,
+                None if guard.1 => break,
+                None => { let _guard = shared.1.wait(guard).unwrap(); continue; },
+            };
+        }
+
+        if delay > 0 {
+            if debug { eprintln!("Delay: {}s.", (delay as f64) / (frequency as f64)); }
+            deadline += delay;
+            ticks::spin_until(deadline, window);
+        }
+
+        then = now;
+        now = deadline;
+        block = match shared.0.lock().unwrap().0.poll(now) {
+            Some(value) => value.0,
+            None => continue,
+        };
+
+        match io::stdout().write_all(&block[..]) {
+            Ok(_) => { },
+            Err(_) => break,
+        }
+        if debug { eprintln!("Written: {}", block.len()); }
+
+        if count <= 0 {
+            // Do nothing.
+        } else if now <= then {
+            // Should never happen.
+        } else {
+            rate = (block.len() as f64) * (frequency as f64) / ((now - then) as f64);
+            if rate > peak {
+                peak = rate
+            }
+        }
+
+        total += block.len() as u64;
+        count += 1;
+
+    }
+
+    (total, count, peak)
+}
 
 fn main() {
     let frequency: ticks::Ticks = ticks::frequency();
@@ -29,12 +218,13 @@ fn main() {
     let mut total: u64 = 0;
     let mut peak: f64 = 0.0;
     let mut count: usize = 0;
-    let mut now: ticks::Ticks;
+    let mut now: ticks::Ticks = 0;
     let mut then: ticks::Ticks;
     let mut rate: f64;
     let mut length: usize;
     let mut buffer = [0u8; 65536] ; // Does Rust really have no way to allocate a u8 array on the heap sized at run time?
     let mut delay: ticks::Ticks;
+    let mut deadline: ticks::Ticks;
     let mut admissable: bool;
 
     let matches = App::new("shape")
@@ -62,6 +252,47 @@ fn main() {
                                .help("Sets maximum burst size.")
                                .takes_value(true)
                                .value_name("BYTES"))
+                           .arg(Arg::with_name("queuesize")
+                               .short("q")
+                               .help("Sets queue size and enables queueing.")
+                               .takes_value(true)
+                               .value_name("BYTES"))
+                           .arg(Arg::with_name("discipline")
+                               .short("d")
+                               .help("Sets queue discipline.")
+                               .takes_value(true)
+                               .possible_values(&["tail", "head", "red", "codel"])
+                               .value_name("POLICY"))
+                           .arg(Arg::with_name("spin")
+                               .short("S")
+                               .help("Sets window before each deadline spent spinning instead of sleeping.")
+                               .takes_value(true)
+                               .value_name("MICROSECONDS"))
+                           .arg(Arg::with_name("file")
+                               .short("f")
+                               .help("Sets contract file of peakrate, sustainedrate, and burstsize lines, re-read on SIGHUP.")
+                               .takes_value(true)
+                               .value_name("FILE"))
+                           .arg(Arg::with_name("overhead")
+                               .short("o")
+                               .help("Sets framing overhead charged per block (e.g. 20 for Ethernet, 28 for IPv4 and UDP).")
+                               .takes_value(true)
+                               .value_name("BYTES"))
+                           .arg(Arg::with_name("cells")
+                               .short("A")
+                               .help("Charges each block as the 53 byte cells of its AAL5 CPCS-PDU."))
+                           .arg(Arg::with_name("unit")
+                               .short("u")
+                               .help("Sets unit in which blocks are charged and rates are given.")
+                               .takes_value(true)
+                               .possible_values(&["bytes", "bits"])
+                               .value_name("UNIT"))
+                           .arg(Arg::with_name("clock")
+                               .short("c")
+                               .help("Sets clock source read for all timing.")
+                               .takes_value(true)
+                               .possible_values(&["instant", "monotonic", "raw", "coarse", "tsc"])
+                               .value_name("SOURCE"))
                           .get_matches();
 
     let debug: bool = matches.is_present("Debug");
@@ -76,7 +307,6 @@ fn main() {
         };
         peakrate = value as throttle::Events;
     }
-    let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
 
     let mut sustainedrate: throttle::Events = 1;
     if matches.is_present("sustainedrate") {
@@ -86,7 +316,6 @@ fn main() {
         };
         sustainedrate = value as throttle::Events;
     }
-    let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
 
     let mut burstsize: throttle::Events = 1;
     if matches.is_present("burstsize") {
@@ -96,66 +325,174 @@ fn main() {
         };
         burstsize = value as throttle::Events;
     }
-    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize);
+
+    let mut overhead: usize = 0;
+    if matches.is_present("overhead") {
+        overhead = match usize::from_str_radix(matches.value_of("overhead").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+    }
+
+    let unit: cost::Unit = match matches.value_of("unit") {
+        Some("bits") => cost::Unit::Bits,
+        _ => cost::Unit::Bytes,
+    };
+
+    if let Some(name) = matches.value_of("clock") {
+        let source: clock::Source = clock::Source::parse(name).unwrap();
+        if ticks::select(source).is_none() {
+            eprintln!("shape: clock {} is not available!", name);
+            process::exit(1);
+        }
+    }
+
+    let model: cost::Model = cost::Model::new(overhead, matches.is_present("cells"), unit);
+
+    let defaults: Parameters = (peakrate, sustainedrate, burstsize);
+    let path: Option<&str> = matches.value_of("file");
+    let mut parameters: Parameters = defaults;
+    if let Some(path) = path {
+        parameters = match load(path, defaults) {
+            Ok(value) => value,
+            Err(error) => {
+                eprintln!("shape: contract {}: {}!", path, error);
+                process::exit(1);
+            },
+        };
+        unsafe {
+            libc::signal(libc::SIGHUP, hangup as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+    }
+    burstsize = parameters.2;
+    let (peakincrement, sustainedincrement, bursttolerance) = increments(parameters, model, frequency);
+
+    let mut queuesize: throttle::Events = 0;
+    if matches.is_present("queuesize") {
+        let value = match usize::from_str_radix(matches.value_of("queuesize").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        queuesize = value as throttle::Events;
+        if queuesize < burstsize {
+            eprintln!("shape: queuesize {} is less than burstsize {}!", queuesize, burstsize);
+            eprintln!("{}", matches.usage());
+            process::exit(1);
+        }
+    }
+    let capacity: throttle::Events = if queuesize > 0 { model.cost(queuesize as usize) } else { 0 };
+
+    let policy: shaper::Policy = match matches.value_of("discipline") {
+        Some("head") => shaper::Policy::HeadDrop,
+        Some("red") => shaper::Policy::Red { minimum: capacity / 4, maximum: (3 * capacity) / 4, probability: 0.1 },
+        Some("codel") => shaper::Policy::Codel { target: frequency / 200, interval: frequency / 10 },
+        _ => shaper::Policy::TailDrop,
+    };
+
+    let mut window: ticks::Ticks = 0;
+    if matches.is_present("spin") {
+        let value = match usize::from_str_radix(matches.value_of("spin").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        window = (value as ticks::Ticks) * frequency / 1_000_000;
+    }
 
     before = ticks::now();
     shape.init(peakincrement, 0 /* jittertolerance */, sustainedincrement, bursttolerance, before);
     
     if verbose { eprintln!("Contract: {}", shape.to_string()) }
+    if verbose { eprintln!("Cost: {}", model.to_string()) }
 
-    loop {
-        
-        length = match io::stdin().read(& mut buffer[..(burstsize as usize)]) {
-            Ok(0) => break,
-            Ok(value) => value,
-            Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
-            Err(_) => break,     
-        };
-        if debug { eprintln!("Read: {}", length); }
+    if queuesize > 0 {
 
-        now = ticks::now();
-        delay = shape.request(now);
+        let queue: shaper::Shaper<Vec<u8>, contract::Contract> = shaper::Shaper::new(Box::new(shape), capacity, policy);
+        let shared: Shared = sync::Arc::new((sync::Mutex::new((queue, false)), sync::Condvar::new()));
+        let input = shared.clone();
+        let blocksize: usize = burstsize as usize;
+
+        let producer = thread::spawn(move || { reader(input, blocksize, model, debug); });
+        let reconfigure = |contract: & mut contract::Contract| { reload(path, defaults, model, contract, verbose); };
+        let results = writer(shared.clone(), window, &reconfigure, debug);
+        producer.join().unwrap();
+
+        total = results.0;
+        count = results.1;
+        peak = results.2;
+
+        let guard = shared.0.lock().unwrap();
+        delay = guard.0.throttle().get_expected();
         if debug { eprintln!("Delay: {}s.", (delay as f64) / (frequency as f64)); }
         ticks::sleep(delay);
+        after = ticks::now();
 
-        match io::stdout().write_all(&buffer[..length]) {
-            Ok(_) => { },
-            Err(_) => break,
+        if verbose {
+            eprintln!("Shaper: {}", guard.0.to_string());
+            eprintln!("Dropped: {}B.", guard.0.dropped());
         }
-        if debug { eprintln!("Written: {}", length); }
+
+    } else {
+
+        loop {
         
-        then = now;
-        now = ticks::now();
-        admissable = shape.admits(now, length as throttle::Events);
-        if !admissable { eprintln!("Admissable: {}!", admissable); }
+            length = match io::stdin().read(& mut buffer[..(burstsize as usize)]) {
+                Ok(0) => break,
+                Ok(value) => value,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,     
+            };
+            if debug { eprintln!("Read: {}", length); }
 
-        if count <= 0 {
-            // Do nothing.
-        } else if length <= 0 {
-            // Should never happen.
-        } else if now <= then {
-            // Should never happen.
-        } else {
-            rate = (length as f64) * (frequency as f64) / ((now - then) as f64);
-            if rate > peak {
-                peak = rate
+            reload(path, defaults, model, & mut shape, verbose);
+
+            deadline = ticks::now();
+            delay = shape.request(deadline);
+            if debug { eprintln!("Delay: {}s.", (delay as f64) / (frequency as f64)); }
+            deadline += delay;
+            ticks::spin_until(deadline, window);
+
+            match io::stdout().write_all(&buffer[..length]) {
+                Ok(_) => { },
+                Err(_) => break,
             }
-        }
+            if debug { eprintln!("Written: {}", length); }
+        
+            // Charge the emission at its deadline rather than at the time the
+            // write completed so that lateness does not accumulate as drift.
+            then = now;
+            now = deadline;
+            admissable = shape.admits(now, model.cost(length));
+            if !admissable { eprintln!("Admissable: {}!", admissable); }
 
-        total += length as u64;
-        count += 1;
+            if count <= 0 {
+                // Do nothing.
+            } else if length <= 0 {
+                // Should never happen.
+            } else if now <= then {
+                // Should never happen.
+            } else {
+                rate = (length as f64) * (frequency as f64) / ((now - then) as f64);
+                if rate > peak {
+                    peak = rate
+                }
+            }
+
+            total += length as u64;
+            count += 1;
         
-    }
+        }
 
-    now = ticks::now();
-    shape.update(now);
+        now = ticks::now();
+        shape.update(now);
     
-    delay = shape.get_expected();
-    if debug { eprintln!("Delay: {}s.", (delay as f64) / (frequency as f64)); }
-    ticks::sleep(delay);
+        delay = shape.get_expected();
+        if debug { eprintln!("Delay: {}s.", (delay as f64) / (frequency as f64)); }
+        ticks::sleep(delay);
     
-    after = ticks::now();
-    shape.update(now);
+        after = ticks::now();
+        shape.update(now);
+
+    }
 
     if verbose {
         eprintln!("Total: {}B.", total);
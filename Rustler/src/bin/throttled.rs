@@ -0,0 +1,153 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate clap;
+extern crate rustler;
+
+use std::fs;
+use std::net;
+use std::os::unix::net as unix;
+use std::os::unix::fs::FileTypeExt;
+use std::path;
+use std::process;
+use std::sync;
+use std::thread;
+use clap::Arg;
+use clap::App;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::daemon::daemon;
+
+fn main() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut template: contract::Contract = contract::Contract::new();
+    let mut servers = Vec::new();
+
+    let matches = App::new("throttled")
+                          .version("1.0")
+                          .author("Chip Overclock <coverclock@diag.com>")
+                          .about("Serves a traffic contract per key to clients over a socket.")
+                          .arg(Arg::with_name("Debug")
+                               .short("D")
+                               .help("Enables debug output."))
+                          .arg(Arg::with_name("Verbose")
+                               .short("V")
+                               .help("Enable verbose output."))
+                           .arg(Arg::with_name("peakrate")
+                               .short("p")
+                               .help("Sets peak rate.")
+                               .takes_value(true)
+                               .value_name("EVENTSPERSECOND"))
+                           .arg(Arg::with_name("sustainedrate")
+                               .short("s")
+                               .help("Sets sustained rate.")
+                               .takes_value(true)
+                               .value_name("EVENTSPERSECOND"))
+                           .arg(Arg::with_name("burstsize")
+                               .short("b")
+                               .help("Sets maximum burst size.")
+                               .takes_value(true)
+                               .value_name("EVENTS"))
+                           .arg(Arg::with_name("unix")
+                               .short("u")
+                               .help("Listens on a Unix domain socket.")
+                               .takes_value(true)
+                               .value_name("PATH"))
+                           .arg(Arg::with_name("tcp")
+                               .short("t")
+                               .help("Listens on a TCP socket.")
+                               .takes_value(true)
+                               .value_name("ADDRESS:PORT"))
+                          .get_matches();
+
+    let debug: bool = matches.is_present("Debug");
+
+    let verbose: bool = matches.is_present("Verbose");
+
+    let mut peakrate: throttle::Events = 1;
+    if matches.is_present("peakrate") {
+        let value = match usize::from_str_radix(matches.value_of("peakrate").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        peakrate = value as throttle::Events;
+    }
+    let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
+
+    let mut sustainedrate: throttle::Events = 1;
+    if matches.is_present("sustainedrate") {
+        let value = match usize::from_str_radix(matches.value_of("sustainedrate").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        sustainedrate = value as throttle::Events;
+    }
+    let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
+
+    let mut burstsize: throttle::Events = 1;
+    if matches.is_present("burstsize") {
+        let value = match usize::from_str_radix(matches.value_of("burstsize").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+        burstsize = value as throttle::Events;
+    }
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize);
+
+    if !(matches.is_present("unix") || matches.is_present("tcp")) {
+        panic!("no socket specified!");
+    }
+
+    template.init(peakincrement, 0 /* jittertolerance */, sustainedincrement, bursttolerance, ticks::now());
+
+    if verbose { eprintln!("Contract: {}", template.to_string()) }
+
+    let registry: daemon::Shared<contract::Contract> = sync::Arc::new(sync::Mutex::new(daemon::Registry::new(template)));
+
+    if let Some(value) = matches.value_of("unix") {
+        let path: path::PathBuf = path::PathBuf::from(value);
+        /* Only a stale socket is removed, never a file the path names by mistake. */
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if !metadata.file_type().is_socket() {
+                eprintln!("throttled: unix {}: exists and is not a socket!", value);
+                process::exit(1);
+            }
+            if let Err(error) = fs::remove_file(&path) {
+                eprintln!("throttled: unix {}: {}!", value, error);
+                process::exit(1);
+            }
+        }
+        let listener: unix::UnixListener = match unix::UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(error) => panic!("unix {}: {}!", value, error),
+        };
+        if debug { eprintln!("Unix: {}", value); }
+        let shared = registry.clone();
+        servers.push(thread::spawn(move || { daemon::serve_unix(shared, listener) }));
+    }
+
+    if let Some(value) = matches.value_of("tcp") {
+        let listener: net::TcpListener = match net::TcpListener::bind(value) {
+            Ok(listener) => listener,
+            Err(error) => panic!("tcp {}: {}!", value, error),
+        };
+        if debug { eprintln!("Tcp: {}", listener.local_addr().unwrap()); }
+        let shared = registry.clone();
+        servers.push(thread::spawn(move || { daemon::serve_tcp(shared, listener) }));
+    }
+
+    for server in servers {
+        let error = server.join().unwrap();
+        eprintln!("Error: {}!", error);
+    }
+
+    if verbose { eprintln!("Registry: {}", daemon::lock(&registry).to_string()) }
+
+}
@@ -0,0 +1,238 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate clap;
+extern crate rustler;
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::net;
+use clap::Arg;
+use clap::App;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::trafgen::trafgen;
+
+type Output = Box<FnMut(&[u8]) -> io::Result<()>>;
+
+fn main() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let before: ticks::Ticks;
+    let after: ticks::Ticks;
+    let mut total: u64 = 0;
+    let mut count: usize = 0;
+    let mut delay: ticks::Ticks;
+    let mut length: usize;
+    let mut buffer: Vec<u8> = Vec::new();
+
+    let matches = App::new("trafgen")
+                          .version("1.0")
+                          .author("Chip Overclock <coverclock@diag.com>")
+                          .about("Generates a reproducible data stream following a traffic pattern.")
+                          .arg(Arg::with_name("Debug")
+                               .short("D")
+                               .help("Enables debug output."))
+                          .arg(Arg::with_name("Verbose")
+                               .short("V")
+                               .help("Enable verbose output."))
+                           .arg(Arg::with_name("rate")
+                               .short("r")
+                               .help("Sets mean rate.")
+                               .takes_value(true)
+                               .value_name("BYTESPERSECOND"))
+                           .arg(Arg::with_name("blocksize")
+                               .short("b")
+                               .help("Sets block size.")
+                               .takes_value(true)
+                               .value_name("BYTES"))
+                           .arg(Arg::with_name("pattern")
+                               .short("m")
+                               .help("Sets traffic pattern.")
+                               .takes_value(true)
+                               .possible_values(&["constant", "poisson", "onoff", "trace"])
+                               .value_name("PATTERN"))
+                           .arg(Arg::with_name("on")
+                               .short("o")
+                               .help("Sets mean on period for the onoff pattern.")
+                               .takes_value(true)
+                               .value_name("MILLISECONDS"))
+                           .arg(Arg::with_name("off")
+                               .short("f")
+                               .help("Sets mean off period for the onoff pattern.")
+                               .takes_value(true)
+                               .value_name("MILLISECONDS"))
+                           .arg(Arg::with_name("trace")
+                               .short("t")
+                               .help("Sets trace file for the trace pattern.")
+                               .takes_value(true)
+                               .value_name("FILE"))
+                           .arg(Arg::with_name("total")
+                               .short("n")
+                               .help("Sets total bytes to generate.")
+                               .takes_value(true)
+                               .value_name("BYTES"))
+                           .arg(Arg::with_name("seed")
+                               .short("x")
+                               .help("Sets random seed.")
+                               .takes_value(true)
+                               .value_name("SEED"))
+                           .arg(Arg::with_name("udp")
+                               .short("u")
+                               .help("Sends datagrams to a UDP socket instead of standard output.")
+                               .takes_value(true)
+                               .value_name("ADDRESS:PORT"))
+                           .arg(Arg::with_name("tcp")
+                               .short("c")
+                               .help("Connects to a TCP socket instead of standard output.")
+                               .takes_value(true)
+                               .value_name("ADDRESS:PORT"))
+                          .get_matches();
+
+    let debug: bool = matches.is_present("Debug");
+
+    let verbose: bool = matches.is_present("Verbose");
+
+    let mut rate: throttle::Events = 1024;
+    if matches.is_present("rate") {
+        rate = match usize::from_str_radix(matches.value_of("rate").unwrap(), 10) {
+            Ok(value) => value as throttle::Events,
+            Err(_) => 0,
+        };
+    }
+    if rate <= 0 {
+        panic!("rate not valid!");
+    }
+
+    let mut blocksize: usize = 512;
+    if matches.is_present("blocksize") {
+        blocksize = match usize::from_str_radix(matches.value_of("blocksize").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+    }
+    if !((0 < blocksize) && (blocksize <= 65536)) {
+        panic!("blocksize not valid!");
+    }
+
+    let mut on: ticks::Ticks = 100;
+    if matches.is_present("on") {
+        on = match usize::from_str_radix(matches.value_of("on").unwrap(), 10) {
+            Ok(value) => value as ticks::Ticks,
+            Err(_) => 0,
+        };
+    }
+
+    let mut off: ticks::Ticks = 100;
+    if matches.is_present("off") {
+        off = match usize::from_str_radix(matches.value_of("off").unwrap(), 10) {
+            Ok(value) => value as ticks::Ticks,
+            Err(_) => 0,
+        };
+    }
+
+    let mut limit: u64 = u64::max_value();
+    if matches.is_present("total") {
+        limit = match u64::from_str_radix(matches.value_of("total").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => 0,
+        };
+    }
+
+    let mut seed: u64 = 1;
+    if matches.is_present("seed") {
+        seed = match u64::from_str_radix(matches.value_of("seed").unwrap(), 10) {
+            Ok(value) => value,
+            Err(_) => panic!("seed not valid!"),
+        };
+    }
+
+    let pattern: trafgen::Pattern = match matches.value_of("pattern") {
+        Some("poisson") => trafgen::Pattern::Poisson,
+        Some("onoff") => trafgen::Pattern::OnOff { on: on * frequency / 1000, off: off * frequency / 1000 },
+        Some("trace") => {
+            let name: &str = match matches.value_of("trace") {
+                Some(name) => name,
+                None => panic!("trace not specified!"),
+            };
+            let file: fs::File = match fs::File::open(name) {
+                Ok(file) => file,
+                Err(error) => panic!("trace {}: {}!", name, error),
+            };
+            match trafgen::load(file) {
+                Ok(emissions) => trafgen::Pattern::Trace(emissions),
+                Err(error) => panic!("trace {}: {}!", name, error),
+            }
+        },
+        _ => trafgen::Pattern::Constant,
+    };
+
+    let mut output: Output;
+    if let Some(address) = matches.value_of("udp") {
+        let socket: net::UdpSocket = match net::UdpSocket::bind("0.0.0.0:0") {
+            Ok(socket) => socket,
+            Err(error) => panic!("udp: {}!", error),
+        };
+        if let Err(error) = socket.connect(address) {
+            panic!("udp {}: {}!", address, error);
+        }
+        output = Box::new(move |block| { socket.send(block).map(|_| ()) });
+    } else if let Some(address) = matches.value_of("tcp") {
+        let mut stream: net::TcpStream = match net::TcpStream::connect(address) {
+            Ok(stream) => stream,
+            Err(error) => panic!("tcp {}: {}!", address, error),
+        };
+        output = Box::new(move |block| { stream.write_all(block) });
+    } else {
+        output = Box::new(|block| { let mut stdout = io::stdout(); stdout.write_all(block).and_then(|_| stdout.flush()) });
+    }
+
+    let mut generator: trafgen::Generator = trafgen::Generator::new(pattern, rate as f64, blocksize as throttle::Events, seed);
+
+    if verbose { eprintln!("Generator: {}", generator.to_string()); }
+
+    before = ticks::now();
+
+    while total < limit {
+
+        let (offset, size) = match generator.next() {
+            Some(emission) => emission,
+            None => break,
+        };
+
+        length = size as usize;
+        if (length as u64) > (limit - total) {
+            length = (limit - total) as usize;
+        }
+        buffer.resize(length, 0);
+        generator.fill(& mut buffer[..]);
+
+        delay = before + offset - ticks::now();
+        if debug { eprintln!("Delay: {}s.", (delay as f64) / (frequency as f64)); }
+        ticks::sleep(delay);
+
+        match output(&buffer[..]) {
+            Ok(_) => { },
+            Err(_) => break,
+        }
+        if debug { eprintln!("Written: {}", length); }
+
+        total += length as u64;
+        count += 1;
+
+    }
+
+    after = ticks::now();
+
+    if verbose {
+        eprintln!("Total: {}B.", total);
+        eprintln!("Average: {}B/io.", (total as f64) / (count as f64));
+        eprintln!("Sustained: {}Bps.", (total as f64) * (frequency as f64) / ((after - before) as f64));
+    }
+
+}
@@ -0,0 +1,250 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements the Generic Cell Rate Algorithm (GCRA) in its second form in the
+/// ATM Forum Traffic Management Specification, the continuous-state leaky
+/// bucket, as an independent implementation of the same traffic contract as
+/// the virtual scheduler in gcra. The bucket holds X ticks of content and
+/// drains at one tick per tick since the last conformance time (LCT). On each
+/// arrival at time ta the content is first drained to X' = X - (ta - LCT),
+/// but not below zero. The arrival is conforming if X' is no more than the
+/// limit L, after which it pours I ticks per event into the bucket, so that
+/// X = X' + I and LCT = ta. The two forms are proven equivalent in the
+/// specification; given the same increment, limit, and arrivals a Bucket
+/// makes exactly the same decisions as a Gcra, which is what makes it useful
+/// as a check on either. Like a Gcra, and unlike the specification, a Bucket
+/// is charged for every emission committed to it, conforming or not.
+///
+/// REFERENCES
+///
+/// N. Giroux et al., Traffic Management Specification Version 4.1, ATM Forum,
+/// af-tm-0121.000, 1999-03, 4.4.3.2 and Figure 1
+///
+pub mod bucket {
+
+    use std::string;
+    use std::mem;
+    use ticks::ticks;
+    use throttle::throttle;
+    use units::units;
+    use gcra::gcra;
+
+    #[derive(Clone, Copy, Debug)]
+    #[repr(C)]
+    pub struct Bucket {
+        increment:  ticks::Ticks,               // I: ticks poured per event.
+        limit:      ticks::Ticks,               // L: maximum conforming content.
+        content:    ticks::Ticks,               // X: content at LCT.
+        drained:    ticks::Ticks,               // X': content at arrival.
+        lct:        ticks::Ticks,               // LCT: last conformance time.
+        arrival:    ticks::Ticks,               // ta: time of arrival.
+        full:       [bool; 3],                  // Will fill, filling, filled.
+        empty:      [bool; 3],                  // Will empty, emptying, emptied.
+        alarm:      [bool; 2],                  // Is alarmed, was alarmed.
+    }
+
+    pub static SIZE_OF_BUCKET: usize = mem::size_of::<Bucket>(); // Not actually a function call.
+
+    impl string::ToString for Bucket {
+
+        fn to_string(& self) -> string::String {
+            format!("Bucket@{:p}[{}]:{{i:{},l:{},x:{},x1:{},lct:{},ta:{},f:{:?},e:{:?},a:{:?}}}",
+                self, SIZE_OF_BUCKET,
+                self.increment, self.limit, self.content, self.drained,
+                self.lct, self.arrival,
+                self.full, self.empty, self.alarm)
+        }
+
+    }
+
+    impl Bucket {
+
+        /// Allocate a new Bucket given an increment and a limit in ticks, and
+        /// the current time in ticks. The bucket starts empty.
+        pub fn new(increment: ticks::Ticks, limit: ticks::Ticks, now: ticks::Ticks) -> Bucket {
+            Bucket {
+                increment,
+                limit,
+                content:    0,
+                drained:    0,
+                lct:        now,
+                arrival:    now,
+                full:       [false; 3],
+                empty:      [true; 3],
+                alarm:      [false; 2],
+            }
+        }
+
+        /// Allocate a new Bucket given the rate, the maximum burst size, and
+        /// the current time, all strongly typed. The bucket starts empty.
+        pub fn with(rate: units::Rate, burstsize: units::Events, now: units::Ticks) -> Bucket {
+            let increment: ticks::Ticks = rate.increment().0;
+
+            Bucket::new(increment, gcra::jittertolerance(increment, burstsize.0), now.0)
+        }
+
+        /// get_increment returns the increment in ticks per event.
+        pub fn get_increment(& self) -> ticks::Ticks {
+            self.increment
+        }
+
+        /// get_limit returns the limit in ticks.
+        pub fn get_limit(& self) -> ticks::Ticks {
+            self.limit
+        }
+
+        /// get_content returns the content of the bucket in ticks as of the
+        /// last conformance time.
+        pub fn get_content(& self) -> ticks::Ticks {
+            self.content
+        }
+
+        /// drain returns the content of the bucket in ticks drained to the
+        /// specified time in ticks.
+        fn drain(& self, now: ticks::Ticks) -> ticks::Ticks {
+            let drained: ticks::Ticks = self.content - (now - self.lct);
+
+            if drained < 0 { 0 } else { drained }
+        }
+
+    }
+
+    impl throttle::Throttle for Bucket {
+
+        /*** INSPECTORS ***/
+
+        fn size_of(& self) -> usize {
+            SIZE_OF_BUCKET
+        }
+
+        /*** CONVERTORS ***/
+
+        fn as_string(& self) -> string::String {
+            string::ToString::to_string(self)
+        }
+
+        /*** SETTERS ***/
+
+        /// reset empties the bucket as of the specified time in ticks.
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.content = 0;
+            self.drained = 0;
+            self.lct = now;
+            self.arrival = now;
+            self.full = [false; 3];
+            self.empty = [true; 3];
+            self.alarm = [false; 2];
+        }
+
+        /*** GETTERS ***/
+
+        /// get_expected returns the content of the bucket in ticks as of the
+        /// last conformance time, which is how long it would take to drain.
+        fn get_expected(& self) -> ticks::Ticks {
+            self.content
+        }
+
+        /// peek returns the delay in ticks after which the last of the
+        /// specified number of events, each poured as soon as it conforms,
+        /// would conform, without altering the bucket.
+        fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+            let mut drained: ticks::Ticks = self.drain(now);
+
+            if events > 1 {
+                drained = drained.saturating_add(self.increment.saturating_mul(events - 1));
+            }
+
+            if drained > self.limit { drained - self.limit } else { 0 }
+        }
+
+        fn is_empty(& self) -> bool {
+            self.empty[1]
+        }
+
+        fn is_full(& self) -> bool {
+            self.full[1]
+        }
+
+        fn is_alarmed(& self) -> bool {
+            self.alarm[0]
+        }
+
+        /*** SENSORS ***/
+
+        fn emptied(& self) -> bool {
+            self.empty[1] && !self.empty[2]
+        }
+
+        fn filled(& self) -> bool {
+            self.full[1] && !self.full[2]
+        }
+
+        fn alarmed(& self) -> bool {
+            self.alarm[0] && !self.alarm[1]
+        }
+
+        fn cleared(& self) -> bool {
+            !self.alarm[0] && self.alarm[1]
+        }
+
+        /*** MUTATORS ***/
+
+        /// request drains the bucket to the arrival time in ticks and returns
+        /// how long the arrival would have to be delayed to conform.
+        fn request(& mut self, now: ticks::Ticks) -> ticks::Ticks {
+            self.arrival = now;
+            self.drained = self.drain(now);
+            self.empty[0] = self.drained == 0;
+            self.full[0] = self.drained > self.limit;
+
+            if self.full[0] { self.drained - self.limit } else { 0 }
+        }
+
+        /// commits pours the number of events into the bucket drained at the
+        /// time of the previous request, which becomes the last conformance
+        /// time, and returns false if the bucket is alarmed.
+        fn commits(& mut self, events: throttle::Events) -> bool {
+            self.lct = self.arrival;
+            self.content = self.drained;
+            if events > 0 {
+                self.content += self.increment * events;
+            }
+            self.full = [self.full[0], self.full[0], self.full[1]];
+            self.empty = [self.empty[0], self.empty[0], self.empty[1]];
+            self.alarm[1] = self.alarm[0];
+            if self.emptied() {
+                self.alarm[0] = false;
+            } else if self.filled() {
+                self.alarm[0] = true;
+            } else {
+                // Do nothing.
+            }
+
+            !self.alarm[0]
+        }
+
+        fn commit(& mut self) -> bool {
+            self.commits(1)
+        }
+
+        fn admits(& mut self, now: ticks::Ticks, events: throttle::Events) -> bool {
+            self.request(now);
+            self.commits(events)
+        }
+
+        fn admit(& mut self, now: ticks::Ticks) -> bool {
+            self.admits(now, 1)
+        }
+
+        fn update(& mut self, now: ticks::Ticks) -> bool {
+            self.admits(now, 0)
+        }
+
+    }
+
+}
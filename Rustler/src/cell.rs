@@ -0,0 +1,155 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements the semantics of the CL.THROTTLE command of the redis-cell
+/// module using a Gcra, so that applications using that interface can use
+/// this implementation instead. The command
+///
+/// ```text
+/// CL.THROTTLE KEY MAXBURST COUNT PERIOD QUANTITY
+/// ```
+///
+/// admits QUANTITY events on the throttle for KEY if that does not exceed a
+/// rate of COUNT events per PERIOD seconds with bursts of up to MAXBURST events
+/// beyond the first, and answers with five integers: whether the events were
+/// limited (not admitted), the total limit (MAXBURST plus one), the number of
+/// events that remain in the burst, the number of seconds after which the
+/// events would be admitted (-1 if they were admitted, or if they never can
+/// be), and the number of seconds until the throttle is empty. Times in the
+/// answer are rounded up to the next whole second. Unlike admits, a request
+/// that is limited does not alter the throttle. A negative MAXBURST or
+/// QUANTITY, a COUNT or PERIOD that is not positive, or any whose arithmetic
+/// would overflow, is answered with an error instead.
+///
+/// REFERENCES
+///
+/// B. Leach, "redis-cell", https://github.com/brandur/redis-cell
+///
+pub mod cell {
+
+    use std::io;
+    use ticks::ticks;
+    use throttle::throttle;
+    use throttle::throttle::Throttle;
+    use gcra::gcra;
+
+    /// Reply is the answer to CL.THROTTLE: limited, limit, remaining, retry
+    /// after in seconds, and reset after in seconds.
+    pub type Reply = (bool, throttle::Events, throttle::Events, i64, i64);
+
+    /// Compute the increment in ticks for a rate of count events per period
+    /// seconds.
+    pub fn increment(count: throttle::Events, period: throttle::Events) -> ticks::Ticks {
+        gcra::increment(count, period, ticks::frequency())
+    }
+
+    /// Compute the limit in ticks for an increment in ticks and a maximum
+    /// burst of events beyond the first.
+    pub fn limit(increment: ticks::Ticks, maxburst: throttle::Events) -> ticks::Ticks {
+        gcra::jittertolerance(increment, maxburst + 1)
+    }
+
+    fn invalid(message: & str) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    }
+
+    fn seconds(ticks: ticks::Ticks) -> i64 {
+        let frequency: ticks::Ticks = ticks::frequency();
+
+        (ticks + frequency - 1) / frequency
+    }
+
+    /// throttle performs CL.THROTTLE on the specified Gcra given the maximum
+    /// burst, count, period in seconds, quantity, and current time in ticks. A
+    /// Gcra fresh from new is initialized from the parameters; a Gcra whose
+    /// parameters differ from these is reconfigured without losing its state.
+    /// An error is returned, and the Gcra is left unchanged, if a parameter
+    /// is not valid.
+    pub fn throttle(gcra: & mut gcra::Gcra, maxburst: throttle::Events, count: throttle::Events, period: throttle::Events, quantity: throttle::Events, now: ticks::Ticks) -> io::Result<Reply> {
+        let increment: ticks::Ticks;
+        let limit: ticks::Ticks;
+        let tolerance: ticks::Ticks;
+        let mut checkpoint: gcra::Checkpoint;
+        let mut ttl: ticks::Ticks;
+        let mut retry: ticks::Ticks = -1;
+        let mut limited: bool = false;
+        let mut remaining: throttle::Events = 0;
+        let next: ticks::Ticks;
+        let wait: ticks::Ticks;
+        let cost: ticks::Ticks;
+
+        if maxburst < 0 {
+            return Err(invalid("maxburst not valid"));
+        }
+        if count <= 0 {
+            return Err(invalid("count not valid"));
+        }
+        if (period <= 0) || period.checked_mul(ticks::frequency()).is_none() {
+            return Err(invalid("period not valid"));
+        }
+        if quantity < 0 {
+            return Err(invalid("quantity not valid"));
+        }
+
+        increment = self::increment(count, period);
+        limit = match maxburst.checked_mul(increment) {
+            Some(_) => self::limit(increment, maxburst),
+            None => return Err(invalid("maxburst not valid")),
+        };
+        tolerance = match limit.checked_add(increment) {
+            Some(value) => value,
+            None => return Err(invalid("maxburst not valid")),
+        };
+        cost = match increment.checked_mul(quantity) {
+            Some(value) => value,
+            None => return Err(invalid("quantity not valid")),
+        };
+
+        checkpoint = gcra.checkpoint(now);
+        if checkpoint.increment == 0 {
+            gcra.init(increment, limit, now);
+            checkpoint = gcra.checkpoint(now);
+        } else if (checkpoint.increment != increment) || (checkpoint.limit != limit) {
+            checkpoint.increment = increment;
+            checkpoint.limit = limit;
+            if gcra.restore(&checkpoint, now).is_none() {
+                // Should never happen.
+            }
+        } else {
+            // Do nothing.
+        }
+
+        ttl = checkpoint.expected - checkpoint.then;
+        if ttl < 0 {
+            ttl = 0;
+        }
+
+        wait = ttl.saturating_add(cost) - tolerance;
+        if wait > 0 {
+            limited = true;
+            if cost <= tolerance {
+                retry = wait;
+            }
+        } else {
+            gcra.admits(now, quantity);
+            ttl = gcra.get_expected();
+        }
+
+        next = tolerance - ttl;
+        if next > 0 {
+            remaining = next / increment;
+        }
+
+        if retry > 0 {
+            retry = seconds(retry);
+        }
+
+        Ok((limited, maxburst + 1, remaining, retry, seconds(ttl)))
+    }
+
+}
@@ -0,0 +1,114 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements the client side of the rate limiting daemon, connecting to it
+/// over a Unix domain socket or a TCP socket and speaking the line protocol
+/// described in the daemon module. Each call sends one request and waits for
+/// its response. Errors reported by the daemon are returned as errors of kind
+/// InvalidData.
+///
+pub mod client {
+
+    use std::io;
+    use std::io::BufRead;
+    use std::io::Write;
+    use std::marker;
+    use std::net;
+    use std::os::unix::net as unix;
+    use std::path;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+
+    pub struct Client {
+        reader:     io::BufReader<Box<io::Read + marker::Send>>, // Responses.
+        writer:     Box<io::Write + marker::Send>, // Requests.
+    }
+
+    impl Client {
+
+        /// Connect to the daemon listening on the Unix domain socket at the
+        /// specified path.
+        pub fn unix(path: & path::Path) -> io::Result<Client> {
+            let stream: unix::UnixStream = unix::UnixStream::connect(path)?;
+            let reader: unix::UnixStream = stream.try_clone()?;
+
+            Ok(Client {
+                reader:     io::BufReader::new(Box::new(reader)),
+                writer:     Box::new(stream),
+            })
+        }
+
+        /// Connect to the daemon listening on the TCP socket at the specified
+        /// address.
+        pub fn tcp<A: net::ToSocketAddrs>(address: A) -> io::Result<Client> {
+            let stream: net::TcpStream = net::TcpStream::connect(address)?;
+            let reader: net::TcpStream = stream.try_clone()?;
+
+            stream.set_nodelay(true)?;
+
+            Ok(Client {
+                reader:     io::BufReader::new(Box::new(reader)),
+                writer:     Box::new(stream),
+            })
+        }
+
+        fn transact(& mut self, request: string::String) -> io::Result<i64> {
+            let mut response: string::String = string::String::new();
+
+            self.writer.write_all(request.as_bytes())?;
+            self.writer.flush()?;
+
+            if self.reader.read_line(& mut response)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "daemon closed connection"));
+            }
+
+            let tokens: Vec<& str> = response.split_whitespace().collect();
+
+            match tokens.as_slice() {
+                ["OK", value] => {
+                    value.parse::<i64>().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, response.trim().to_string()))
+                },
+                _ => {
+                    Err(io::Error::new(io::ErrorKind::InvalidData, response.trim().to_string()))
+                },
+            }
+        }
+
+        fn check(key: & str) -> io::Result<()> {
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, "key not valid"))
+            } else {
+                Ok(())
+            }
+        }
+
+        /// admit returns true if the throttle for the key admitted the specified
+        /// number of events.
+        pub fn admit(& mut self, key: & str, events: throttle::Events) -> io::Result<bool> {
+            Client::check(key)?;
+            Ok(self.transact(format!("admit {} {}\n", key, events))? != 0)
+        }
+
+        /// request returns the delay in ticks before the throttle for the key
+        /// would admit one more event.
+        pub fn request(& mut self, key: & str) -> io::Result<ticks::Ticks> {
+            Client::check(key)?;
+            Ok(ticks::from_nanoseconds(self.transact(format!("request {}\n", key))?))
+        }
+
+        /// reset discards the throttle for the key in the daemon.
+        pub fn reset(& mut self, key: & str) -> io::Result<()> {
+            Client::check(key)?;
+            self.transact(format!("reset {}\n", key))?;
+            Ok(())
+        }
+
+    }
+
+}
@@ -0,0 +1,492 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements selectable clock sources as an alternative to ticks::now. Each
+/// Clock reports time in its own units and its real frequency in those units
+/// per second, so a throttle driven by a Clock must be given increments
+/// computed with that frequency (e.g. gcra::increment(rate, 1, c.frequency())).
+/// Instant is the same clock as ticks::now. Monotonic, Raw, and Coarse read
+/// the POSIX clocks CLOCK_MONOTONIC, CLOCK_MONOTONIC_RAW (not slewed by NTP),
+/// and CLOCK_MONOTONIC_COARSE (cheaper, but only as fine as the scheduler
+/// tick). Tsc reads the x86_64 time stamp counter, the cheapest of all, whose
+/// frequency must be calibrated against another clock; it is only usable on
+/// processors with an invariant TSC.
+///
+/// Alternatively, select makes ticks::now itself read one of these sources,
+/// scaled to nanoseconds, so that everything driven by ticks::now (throttles,
+/// sleep_until, shape) uses that source without any change of units.
+///
+pub mod clock {
+
+    use std::string;
+    use std::marker;
+    use std::sync::atomic;
+    use std::hint;
+    use libc;
+    use ticks::ticks;
+
+    /// Clock is the trait for a source of monotonically increasing time.
+    pub trait Clock {
+
+        /// now returns the current time in ticks of this clock.
+        fn now(& self) -> ticks::Ticks;
+
+        /// frequency returns the number of ticks of this clock per second.
+        fn frequency(& self) -> ticks::Ticks;
+
+        /// resolution returns the smallest difference in ticks that this
+        /// clock can report between two calls to now.
+        fn resolution(& self) -> ticks::Ticks;
+
+        /// name returns the name of the source of this clock.
+        fn name(& self) -> &'static str;
+
+    }
+
+    /// Source enumerates the clock sources.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Source {
+        Instant,
+        Monotonic,
+        Raw,
+        Coarse,
+        Tsc,
+    }
+
+    /// SOURCES lists every clock source.
+    pub const SOURCES: [Source; 5] = [ Source::Instant, Source::Monotonic, Source::Raw, Source::Coarse, Source::Tsc ];
+
+    /// CALIBRATION is the interval in nanoseconds over which clock calibrates
+    /// the TSC.
+    pub const CALIBRATION: ticks::Ticks = 100_000_000;
+
+    impl Source {
+
+        /// name returns the name of the source.
+        pub fn name(& self) -> &'static str {
+            match *self {
+                Source::Instant => "instant",
+                Source::Monotonic => "monotonic",
+                Source::Raw => "raw",
+                Source::Coarse => "coarse",
+                Source::Tsc => "tsc",
+            }
+        }
+
+        /// index returns the position of the source in SOURCES.
+        fn index(& self) -> usize {
+            SOURCES.iter().position(|source| source == self).unwrap()
+        }
+
+        /// parse returns the source with the specified name, or None.
+        pub fn parse(name: &str) -> Option<Source> {
+            SOURCES.iter().find(|source| source.name() == name).cloned()
+        }
+
+    }
+
+    /// clock returns a new Clock for the specified source, or None if the
+    /// source is not available on this platform. A Tsc is calibrated, which
+    /// takes CALIBRATION nanoseconds.
+    pub fn clock(source: Source) -> Option<Box<Clock + marker::Send>> {
+        match source {
+            Source::Instant => Some(Box::new(Instant)),
+            Source::Monotonic => Some(Box::new(Posix::monotonic())),
+            Source::Raw => Posix::raw().map(|clock| Box::new(clock) as Box<Clock + marker::Send>),
+            Source::Coarse => Posix::coarse().map(|clock| Box::new(clock) as Box<Clock + marker::Send>),
+            Source::Tsc => Tsc::calibrated(CALIBRATION).map(|clock| Box::new(clock) as Box<Clock + marker::Send>),
+        }
+    }
+
+    /*** INSTANT ***/
+
+    /// Instant is the clock used by ticks::now unless another is selected.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Instant;
+
+    impl Clock for Instant {
+
+        fn now(& self) -> ticks::Ticks {
+            ticks::instant()
+        }
+
+        fn frequency(& self) -> ticks::Ticks {
+            ticks::frequency()
+        }
+
+        fn resolution(& self) -> ticks::Ticks {
+            1
+        }
+
+        fn name(& self) -> &'static str {
+            Source::Instant.name()
+        }
+
+    }
+
+    /*** POSIX ***/
+
+    /// gettime returns the time of the specified POSIX clock in nanoseconds.
+    fn gettime(id: libc::clockid_t) -> ticks::Ticks {
+        let mut timespec: libc::timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+
+        unsafe {
+            libc::clock_gettime(id, & mut timespec);
+        }
+
+        (timespec.tv_sec as ticks::Ticks) * ticks::FREQUENCY + (timespec.tv_nsec as ticks::Ticks)
+    }
+
+    /// getres returns the resolution of the specified POSIX clock in
+    /// nanoseconds, or None if the clock is not supported.
+    fn getres(id: libc::clockid_t) -> Option<ticks::Ticks> {
+        let mut timespec: libc::timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        let rc: libc::c_int;
+
+        unsafe {
+            rc = libc::clock_getres(id, & mut timespec);
+        }
+
+        if rc != 0 {
+            None
+        } else {
+            Some((timespec.tv_sec as ticks::Ticks) * ticks::FREQUENCY + (timespec.tv_nsec as ticks::Ticks))
+        }
+    }
+
+    /// Posix is a clock read with clock_gettime.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Posix {
+        id:         libc::clockid_t,            // POSIX clock identifier.
+        source:     Source,                     // Clock source.
+        resolution: ticks::Ticks,               // Resolution in nanoseconds.
+    }
+
+    impl string::ToString for Posix {
+
+        fn to_string(& self) -> string::String {
+            format!("Posix@{:p}:{{i:{},s:{},r:{}}}",
+                self,
+                self.id, self.source.name(), self.resolution)
+        }
+
+    }
+
+    impl Posix {
+
+        fn new(id: libc::clockid_t, source: Source) -> Option<Posix> {
+            getres(id).map(|resolution| Posix { id, source, resolution })
+        }
+
+        /// monotonic returns a clock that reads CLOCK_MONOTONIC, the same
+        /// clock as ticks::monotonic.
+        pub fn monotonic() -> Posix {
+            Posix::new(libc::CLOCK_MONOTONIC, Source::Monotonic).unwrap()
+        }
+
+        /// raw returns a clock that reads CLOCK_MONOTONIC_RAW, or None if
+        /// it is not available.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        pub fn raw() -> Option<Posix> {
+            Posix::new(libc::CLOCK_MONOTONIC_RAW, Source::Raw)
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        pub fn raw() -> Option<Posix> {
+            None
+        }
+
+        /// coarse returns a clock that reads CLOCK_MONOTONIC_COARSE, or None
+        /// if it is not available.
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        pub fn coarse() -> Option<Posix> {
+            Posix::new(libc::CLOCK_MONOTONIC_COARSE, Source::Coarse)
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        pub fn coarse() -> Option<Posix> {
+            None
+        }
+
+    }
+
+    impl Clock for Posix {
+
+        fn now(& self) -> ticks::Ticks {
+            gettime(self.id)
+        }
+
+        fn frequency(& self) -> ticks::Ticks {
+            ticks::FREQUENCY
+        }
+
+        fn resolution(& self) -> ticks::Ticks {
+            self.resolution
+        }
+
+        fn name(& self) -> &'static str {
+            self.source.name()
+        }
+
+    }
+
+    /*** TSC ***/
+
+    /// counter returns the value of the time stamp counter, or None if this
+    /// platform does not have one.
+    #[cfg(target_arch = "x86_64")]
+    pub fn counter() -> Option<u64> {
+        use std::arch::x86_64;
+        Some(unsafe { x86_64::_rdtsc() })
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn counter() -> Option<u64> {
+        None
+    }
+
+    /// reference returns the time in nanoseconds of the clock against which
+    /// the TSC is calibrated, CLOCK_MONOTONIC_RAW if available, since it is
+    /// not slewed.
+    fn reference() -> ticks::Ticks {
+        match Posix::raw() {
+            Some(clock) => clock.now(),
+            None => ticks::monotonic(),
+        }
+    }
+
+    /// sample returns a reading of the reference clock and of the TSC taken
+    /// as close together as possible, by taking the reading bracketed most
+    /// tightly by two reference readings out of several.
+    fn sample() -> Option<(ticks::Ticks, u64)> {
+        let mut best: Option<(ticks::Ticks, ticks::Ticks, u64)> = None;
+
+        for _ in 0..16 {
+            let before: ticks::Ticks = reference();
+            let count: u64 = counter()?;
+            let after: ticks::Ticks = reference();
+            let width: ticks::Ticks = after - before;
+            match best {
+                Some((narrowest, _, _)) if narrowest <= width => { },
+                _ => { best = Some((width, before + width / 2, count)); },
+            }
+        }
+
+        best.map(|(_, time, count)| (time, count))
+    }
+
+    /// calibrate measures the frequency of the TSC in Hertz against the
+    /// reference clock over the specified interval in nanoseconds, and
+    /// returns it, or None if this platform does not have a TSC. A longer
+    /// interval gives a more accurate frequency.
+    pub fn calibrate(interval: ticks::Ticks) -> Option<ticks::Ticks> {
+        assert!(interval > 0);
+        let before: (ticks::Ticks, u64) = sample()?;
+        ticks::sleep(interval);
+        let after: (ticks::Ticks, u64) = sample()?;
+
+        if after.0 <= before.0 {
+            return None;
+        }
+
+        let counts: u64 = after.1.checked_sub(before.1)?;
+
+        Some(((counts as f64) * (ticks::FREQUENCY as f64) / ((after.0 - before.0) as f64)) as ticks::Ticks)
+    }
+
+    /// Tsc is a clock that reads the time stamp counter.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Tsc {
+        frequency:  ticks::Ticks,               // Counts per second.
+        origin:     u64,                        // Count at allocation.
+    }
+
+    impl string::ToString for Tsc {
+
+        fn to_string(& self) -> string::String {
+            format!("Tsc@{:p}:{{f:{},o:{}}}",
+                self,
+                self.frequency, self.origin)
+        }
+
+    }
+
+    impl Tsc {
+
+        /// Allocate a new Tsc given its frequency in Hertz, or None if this
+        /// platform does not have a TSC. Its time starts at zero.
+        pub fn new(frequency: ticks::Ticks) -> Option<Tsc> {
+            assert!(frequency > 0);
+            counter().map(|origin| Tsc { frequency, origin })
+        }
+
+        /// calibrated allocates a new Tsc whose frequency is calibrated over
+        /// the specified interval in nanoseconds, or None if this platform
+        /// does not have a TSC.
+        pub fn calibrated(interval: ticks::Ticks) -> Option<Tsc> {
+            calibrate(interval).and_then(Tsc::new)
+        }
+
+    }
+
+    impl Clock for Tsc {
+
+        fn now(& self) -> ticks::Ticks {
+            (counter().unwrap().wrapping_sub(self.origin)) as ticks::Ticks
+        }
+
+        fn frequency(& self) -> ticks::Ticks {
+            self.frequency
+        }
+
+        fn resolution(& self) -> ticks::Ticks {
+            1
+        }
+
+        fn name(& self) -> &'static str {
+            Source::Tsc.name()
+        }
+
+    }
+
+    /*** SELECTION ***/
+
+    /// Slot is everything read needs to know about the selected source.
+    #[derive(Clone, Copy, Debug)]
+    struct Slot {
+        index:      usize,                      // Index of the source in SOURCES.
+        id:         libc::clockid_t,            // POSIX clock identifier.
+        frequency:  ticks::Ticks,               // TSC counts per second.
+        origin:     u64,                        // TSC count at selection.
+        offset:     ticks::Ticks,               // Nanoseconds added at selection.
+    }
+
+    /* The fields of the slot are guarded by a sequence lock: select makes the
+     * sequence odd while it changes them and even again when it is done, and
+     * read retries until it sees the same even sequence before and after. */
+
+    static SEQUENCE: atomic::AtomicUsize = atomic::AtomicUsize::new(0);     // Odd while select changes the slot.
+    static INDEX: atomic::AtomicUsize = atomic::AtomicUsize::new(0);        // Slot index.
+    static ID: atomic::AtomicI64 = atomic::AtomicI64::new(0);               // Slot id.
+    static FREQUENCY: atomic::AtomicI64 = atomic::AtomicI64::new(ticks::FREQUENCY); // Slot frequency.
+    static ORIGIN: atomic::AtomicU64 = atomic::AtomicU64::new(0);           // Slot origin.
+    static OFFSET: atomic::AtomicI64 = atomic::AtomicI64::new(0);           // Slot offset.
+
+    /// load returns the fields of the slot, which are only consistent if the
+    /// sequence is even and unchanged across the call.
+    fn load() -> Slot {
+        Slot {
+            index: INDEX.load(atomic::Ordering::Relaxed),
+            id: ID.load(atomic::Ordering::Relaxed) as libc::clockid_t,
+            frequency: FREQUENCY.load(atomic::Ordering::Relaxed),
+            origin: ORIGIN.load(atomic::Ordering::Relaxed),
+            offset: OFFSET.load(atomic::Ordering::Relaxed),
+        }
+    }
+
+    /// store changes the fields of the slot, which only select may do while
+    /// it holds the sequence odd.
+    fn store(slot: & Slot) {
+        INDEX.store(slot.index, atomic::Ordering::Relaxed);
+        ID.store(slot.id as i64, atomic::Ordering::Relaxed);
+        FREQUENCY.store(slot.frequency, atomic::Ordering::Relaxed);
+        ORIGIN.store(slot.origin, atomic::Ordering::Relaxed);
+        OFFSET.store(slot.offset, atomic::Ordering::Relaxed);
+    }
+
+    /// nanoseconds returns the time in nanoseconds of the source of the
+    /// specified slot, before its offset is added.
+    fn nanoseconds(slot: & Slot) -> ticks::Ticks {
+        match SOURCES[slot.index] {
+            Source::Instant => ticks::instant(),
+            Source::Tsc => {
+                let count: u64 = counter().unwrap().wrapping_sub(slot.origin);
+                ((count as u128) * (ticks::FREQUENCY as u128) / (slot.frequency as u128)) as ticks::Ticks
+            },
+            _ => gettime(slot.id),
+        }
+    }
+
+    /// consistent applies the specified function to the slot and returns
+    /// its result, retrying until no select has changed the slot meanwhile.
+    fn consistent<R, F: Fn(& Slot) -> R>(function: F) -> R {
+        loop {
+            let before: usize = SEQUENCE.load(atomic::Ordering::Acquire);
+            if (before & 1) == 0 {
+                let result: R = function(& load());
+                atomic::fence(atomic::Ordering::Acquire);
+                if SEQUENCE.load(atomic::Ordering::Relaxed) == before {
+                    return result;
+                }
+            }
+            hint::spin_loop();
+        }
+    }
+
+    /// selected returns the source read by ticks::now.
+    pub fn selected() -> Source {
+        consistent(|slot| SOURCES[slot.index])
+    }
+
+    /// read returns the time in nanoseconds of the selected source, offset to
+    /// continue from the time ticks::now returned when it was selected. This
+    /// is what ticks::now returns. The source is read within the sequence
+    /// lock, so a reading is either entirely from before a select or entirely
+    /// from after it, and time read by any one thread never goes backwards.
+    pub fn read() -> ticks::Ticks {
+        consistent(|slot| nanoseconds(slot) + slot.offset)
+    }
+
+    /// select makes ticks::now read the specified source from now on, and
+    /// returns the previously selected source, or None if the source is not
+    /// available on this platform, in which case nothing changes. Selecting
+    /// a Tsc calibrates it, which takes CALIBRATION nanoseconds. Time read
+    /// by ticks::now continues without a jump across the change, and select
+    /// may be called while other threads are reading ticks::now, which wait
+    /// for the change to complete rather than read a mix of the two sources.
+    pub fn select(source: Source) -> Option<Source> {
+        let mut slot: Slot = Slot { index: source.index(), id: 0, frequency: ticks::FREQUENCY, origin: 0, offset: 0 };
+
+        match source {
+            Source::Instant => { },
+            Source::Monotonic => { slot.id = Posix::monotonic().id; },
+            Source::Raw => { slot.id = Posix::raw()?.id; },
+            Source::Coarse => { slot.id = Posix::coarse()?.id; },
+            Source::Tsc => {
+                let tsc: Tsc = Tsc::calibrated(CALIBRATION)?;
+                slot.frequency = tsc.frequency;
+                slot.origin = tsc.origin;
+            },
+        }
+
+        let mut sequence: usize = SEQUENCE.load(atomic::Ordering::Relaxed);
+        loop {
+            if (sequence & 1) == 0 {
+                match SEQUENCE.compare_exchange_weak(sequence, sequence + 1, atomic::Ordering::Acquire, atomic::Ordering::Relaxed) {
+                    Ok(_) => { break; },
+                    Err(actual) => { sequence = actual; },
+                }
+            } else {
+                hint::spin_loop();
+                sequence = SEQUENCE.load(atomic::Ordering::Relaxed);
+            }
+        }
+        atomic::fence(atomic::Ordering::Release);
+
+        /* Read the current source while readers wait, so none can read a later time from it. */
+        let previous: Slot = load();
+        let current: ticks::Ticks = nanoseconds(& previous) + previous.offset;
+        slot.offset = current - nanoseconds(& slot);
+        store(& slot);
+
+        SEQUENCE.store(sequence + 2, atomic::Ordering::Release);
+
+        Some(SOURCES[previous.index])
+    }
+
+}
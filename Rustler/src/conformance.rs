@@ -0,0 +1,275 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a kit of conformance checks that any implementation of the
+/// Throttle trait should pass. Each check is given a factory, a closure that
+/// returns a newly initialized throttle given the current time in ticks, and
+/// drives the throttles it makes with a reproducible but irregular schedule
+/// of bursts, conforming emissions, and idle periods derived from a seed and
+/// from the throttle's own notion of delay, so the same checks work for any
+/// traffic contract. Each check returns Ok, or an Err describing the first
+/// violation found, so that the caller's test can report it. The kit is only
+/// built with the "conformance" feature.
+///
+pub mod conformance {
+
+    use std::result;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+    use random::random;
+
+    /// Result is what each check returns.
+    pub type Result = result::Result<(), string::String>;
+
+    /// EPOCH is the time in ticks at which the checks start.
+    pub const EPOCH: ticks::Ticks = 1_000_000_000;
+
+    /// Schedule generates the irregular schedule used by the checks.
+    struct Schedule {
+        random:     random::Random,             // Random state.
+        burst:      u64,                        // Emissions left in burst.
+    }
+
+    impl Schedule {
+
+        fn new(seed: u64) -> Schedule {
+            Schedule { random: random::Random::new(seed), burst: 0 }
+        }
+
+        /// events returns the number of events in the next emission.
+        fn events(& mut self) -> throttle::Events {
+            self.random.range(1, 4) as throttle::Events
+        }
+
+        /// gap returns the time in ticks until the next emission: none at all
+        /// during a burst of up to thirty-two emissions, some fraction of the
+        /// delay the throttle would require, exactly that delay, or long
+        /// enough for it to empty.
+        fn gap(& mut self, throttle: & throttle::Throttle, now: ticks::Ticks) -> ticks::Ticks {
+            let delay: ticks::Ticks = throttle.peek(now, 1);
+
+            if self.burst > 0 {
+                self.burst -= 1;
+                return 0;
+            }
+
+            match self.random.range(0, 3) {
+                0 => { self.burst = self.random.range(0, 31); 0 },
+                1 => ((delay as f64) * 2.0 * self.random.uniform()) as ticks::Ticks,
+                2 => delay,
+                _ => throttle.get_expected() + 1,
+            }
+        }
+
+    }
+
+    /// levels returns the empty, full, and alarmed states of the throttle.
+    fn levels(throttle: & throttle::Throttle) -> (bool, bool, bool) {
+        (throttle.is_empty(), throttle.is_full(), throttle.is_alarmed())
+    }
+
+    /// sensors returns the emptied, filled, alarmed, and cleared sensors of
+    /// the throttle.
+    fn sensors(throttle: & throttle::Throttle) -> (bool, bool, bool, bool) {
+        (throttle.emptied(), throttle.filled(), throttle.alarmed(), throttle.cleared())
+    }
+
+    /// nonnegative checks that request, peek, and get_expected never return a
+    /// negative delay.
+    pub fn nonnegative<T: throttle::Throttle, F: Fn(ticks::Ticks) -> T>(factory: &F, seed: u64, iterations: usize) -> Result {
+        let mut schedule: Schedule = Schedule::new(seed);
+        let mut now: ticks::Ticks = EPOCH;
+        let mut throttle: T = factory(now);
+
+        for ii in 0..iterations {
+            now += schedule.gap(&throttle, now);
+            let events: throttle::Events = schedule.events();
+            let peek: ticks::Ticks = throttle.peek(now, events);
+            if peek < 0 {
+                return Err(format!("nonnegative: {}: peek {} < 0: {}", ii, peek, throttle.as_string()));
+            }
+            let delay: ticks::Ticks = throttle.request(now);
+            if delay < 0 {
+                return Err(format!("nonnegative: {}: request {} < 0: {}", ii, delay, throttle.as_string()));
+            }
+            throttle.commits(events);
+            let expected: ticks::Ticks = throttle.get_expected();
+            if expected < 0 {
+                return Err(format!("nonnegative: {}: get_expected {} < 0: {}", ii, expected, throttle.as_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// compliant checks that waiting for the delay returned by request makes
+    /// a second request return zero, that peek agrees with request, and that
+    /// an emission made after waiting never alarms the throttle.
+    pub fn compliant<T: throttle::Throttle, F: Fn(ticks::Ticks) -> T>(factory: &F, seed: u64, iterations: usize) -> Result {
+        let mut schedule: Schedule = Schedule::new(seed);
+        let mut now: ticks::Ticks = EPOCH;
+        let mut throttle: T = factory(now);
+
+        for ii in 0..iterations {
+            now += schedule.gap(&throttle, now) / 2;
+            let peek: ticks::Ticks = throttle.peek(now, 1);
+            let delay: ticks::Ticks = throttle.request(now);
+            if peek != delay {
+                return Err(format!("compliant: {}: peek {} != request {}: {}", ii, peek, delay, throttle.as_string()));
+            }
+            now += delay;
+            let delay: ticks::Ticks = throttle.request(now);
+            if delay != 0 {
+                return Err(format!("compliant: {}: request {} != 0 after delay: {}", ii, delay, throttle.as_string()));
+            }
+            if !throttle.commits(schedule.events()) {
+                return Err(format!("compliant: {}: alarmed after delay: {}", ii, throttle.as_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// edge checks that the sensors fired for whatever state changes the last
+    /// action made since the levels before it, noting which states were
+    /// entered, and returns the levels after it.
+    fn edge(throttle: & throttle::Throttle, before: (bool, bool, bool), entered: & mut (bool, bool, bool), ii: usize) -> result::Result<(bool, bool, bool), string::String> {
+        let after: (bool, bool, bool) = levels(throttle);
+        let fired: (bool, bool, bool, bool) = sensors(throttle);
+
+        if after.0 && !before.0 {
+            entered.0 = true;
+            if !fired.0 { return Err(format!("edges: {}: emptied did not fire: {}", ii, throttle.as_string())); }
+        }
+        if after.1 && !before.1 {
+            entered.1 = true;
+            if !fired.1 { return Err(format!("edges: {}: filled did not fire: {}", ii, throttle.as_string())); }
+        }
+        if after.2 && !before.2 {
+            entered.2 = true;
+            if !fired.2 { return Err(format!("edges: {}: alarmed did not fire: {}", ii, throttle.as_string())); }
+        }
+        if !after.2 && before.2 && !fired.3 {
+            return Err(format!("edges: {}: cleared did not fire: {}", ii, throttle.as_string()));
+        }
+
+        Ok(after)
+    }
+
+    /// edges checks that the sensors are edge triggered: each time the empty,
+    /// full, or alarmed state is entered the corresponding sensor fires, each
+    /// time the alarmed state is left the cleared sensor fires, and repeating
+    /// an update at the same time fires no sensor at all. It also checks that
+    /// the schedule entered each state at least once.
+    pub fn edges<T: throttle::Throttle, F: Fn(ticks::Ticks) -> T>(factory: &F, seed: u64, iterations: usize) -> Result {
+        let mut schedule: Schedule = Schedule::new(seed);
+        let mut now: ticks::Ticks = EPOCH;
+        let mut throttle: T = factory(now);
+        let mut before: (bool, bool, bool) = levels(&throttle);
+        let mut entered: (bool, bool, bool) = (false, false, false);
+
+        for ii in 0..iterations {
+            now += schedule.gap(&throttle, now);
+            throttle.admits(now, schedule.events());
+            before = edge(&throttle, before, & mut entered, ii)?;
+            throttle.update(now);
+            before = edge(&throttle, before, & mut entered, ii)?;
+            throttle.update(now);
+            if sensors(&throttle) != (false, false, false, false) {
+                return Err(format!("edges: {}: sensor fired on repeated update: {}", ii, throttle.as_string()));
+            }
+        }
+
+        if !entered.0 { return Err("edges: never emptied".to_string()); }
+        if !entered.1 { return Err("edges: never filled".to_string()); }
+        if !entered.2 { return Err("edges: never alarmed".to_string()); }
+
+        Ok(())
+    }
+
+    /// reset checks that a throttle that has been driven through the schedule
+    /// and then reset behaves identically to a throttle newly made by the
+    /// factory at the same time.
+    pub fn reset<T: throttle::Throttle, F: Fn(ticks::Ticks) -> T>(factory: &F, seed: u64, iterations: usize) -> Result {
+        let mut schedule: Schedule = Schedule::new(seed);
+        let mut now: ticks::Ticks = EPOCH;
+        let mut used: T = factory(now);
+
+        for _ in 0..iterations {
+            now += schedule.gap(&used, now);
+            used.admits(now, schedule.events());
+        }
+
+        used.reset(now);
+        let mut fresh: T = factory(now);
+
+        for ii in 0..iterations {
+            if levels(&used) != levels(&fresh) {
+                return Err(format!("reset: {}: levels {:?} != {:?}: {} {}", ii, levels(&used), levels(&fresh), used.as_string(), fresh.as_string()));
+            }
+            if used.get_expected() != fresh.get_expected() {
+                return Err(format!("reset: {}: get_expected {} != {}: {} {}", ii, used.get_expected(), fresh.get_expected(), used.as_string(), fresh.as_string()));
+            }
+            now += schedule.gap(&fresh, now);
+            let events: throttle::Events = schedule.events();
+            if used.peek(now, events) != fresh.peek(now, events) {
+                return Err(format!("reset: {}: peek {} != {}: {} {}", ii, used.peek(now, events), fresh.peek(now, events), used.as_string(), fresh.as_string()));
+            }
+            if used.admits(now, events) != fresh.admits(now, events) {
+                return Err(format!("reset: {}: admits differ: {} {}", ii, used.as_string(), fresh.as_string()));
+            }
+            if sensors(&used) != sensors(&fresh) {
+                return Err(format!("reset: {}: sensors {:?} != {:?}: {} {}", ii, sensors(&used), sensors(&fresh), used.as_string(), fresh.as_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// sustained checks that an event stream shaped by waiting for each delay
+    /// returned by request has, over the specified number of emissions of the
+    /// specified number of events, a sustained rate within the specified
+    /// fraction of the specified rate in events per second.
+    pub fn sustained<T: throttle::Throttle, F: Fn(ticks::Ticks) -> T>(factory: &F, rate: f64, events: throttle::Events, iterations: usize, tolerance: f64) -> Result {
+        let mut now: ticks::Ticks = EPOCH;
+        let mut throttle: T = factory(now);
+        let mut total: throttle::Events = 0;
+
+        for _ in 0..iterations {
+            now += throttle.request(now);
+            throttle.commits(events);
+            total += events;
+        }
+        now += throttle.get_expected();
+
+        let measured: f64 = (total as f64) * (ticks::frequency() as f64) / ((now - EPOCH) as f64);
+
+        if (measured - rate).abs() > (rate * tolerance) {
+            return Err(format!("sustained: rate {} not within {} of {}: {}", measured, tolerance, rate, throttle.as_string()));
+        }
+
+        Ok(())
+    }
+
+    /// conforms runs every check with the specified seed, expecting the
+    /// specified sustained rate in events per second, and returns every
+    /// violation found.
+    pub fn conforms<T: throttle::Throttle, F: Fn(ticks::Ticks) -> T>(factory: &F, rate: f64, seed: u64) -> Vec<string::String> {
+        let results: [Result; 5] = [
+            nonnegative(factory, seed, 10000),
+            compliant(factory, seed, 10000),
+            edges(factory, seed, 10000),
+            reset(factory, seed, 1000),
+            sustained(factory, rate, 1, 100000, 0.01),
+        ];
+
+        results.iter().filter_map(|result| result.clone().err()).collect()
+    }
+
+}
@@ -44,12 +44,25 @@ pub mod contract {
     use ticks::ticks;
     use throttle::throttle;
     use gcra::gcra;
+    use units::units;
   
+    #[repr(C)]
     pub struct Contract {
         peak:       gcra::Gcra,
         sustained:  gcra::Gcra,
     }
     
+    /// Checkpoint is the complete state of a Contract with its timestamps
+    /// expressed relative to the time at which the checkpoint was taken. With
+    /// the serde feature it can be serialized to persist a Contract across a
+    /// restart.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct Checkpoint {
+        pub peak:       gcra::Checkpoint,
+        pub sustained:  gcra::Checkpoint,
+    }
+
     pub const CONTRACT: Contract = Contract {
         peak:       gcra::GCRA,
         sustained:  gcra::GCRA,
@@ -137,6 +150,26 @@ pub mod contract {
             
             delay
         }
+
+        /// peek computes, given the current time in ticks, how long of a delay in
+        /// ticks would be necessary before the last of the specified number of events,
+        /// each emitted as soon as the traffic contract permits, could itself be
+        /// emitted in compliance with the traffic contract. For zero or one event
+        /// this is the same value that request would return, but unlike
+        /// request, peek does not alter the state of the throttle.
+        fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+            let delay: ticks::Ticks;
+            let peak: ticks::Ticks = self.peak.peek(now, events);
+            let sustained: ticks::Ticks = self.sustained.peek(now, events);
+            
+            if peak > sustained {
+                delay = peak;
+            } else {
+                delay = sustained;
+            }
+            
+            delay
+        }
         
         /// is_empty returns true if the throttle is empty, that is, it has no accumulated
         /// deficit ticks.
@@ -293,6 +326,99 @@ pub mod contract {
             *self
         }
 
+        /// Allocate and initialize a Contract object given the peak rate, the
+        /// jitter tolerance, the sustained rate, the maximum burst size, and
+        /// the current time, all strongly typed.
+        pub fn with(peak: units::Rate, jittertolerance: units::Ticks, sustained: units::Rate, burstsize: units::Events, now: units::Ticks) -> Contract {
+            let peakincrement: ticks::Ticks = peak.increment().0;
+            let sustainedincrement: ticks::Ticks = sustained.increment().0;
+
+            Contract::new().init(peakincrement, jittertolerance.0, sustainedincrement, bursttolerance(peakincrement, jittertolerance.0, sustainedincrement, burstsize.0), now.0)
+        }
+
+        /// Capture the complete state of a Contract object given the current
+        /// time in ticks since the epoch.
+        pub fn checkpoint(& self, now: ticks::Ticks) -> Checkpoint {
+            Checkpoint {
+                peak:       self.peak.checkpoint(now),
+                sustained:  self.sustained.checkpoint(now),
+            }
+        }
+
+        /// Restore a Contract object to a previously captured state given the
+        /// current time in ticks since the epoch, which need not be the same
+        /// epoch as when the state was captured. If either GCRA rejects its
+        /// checkpoint, None is returned and the Contract object is left
+        /// unchanged.
+        pub fn restore(& mut self, checkpoint: & Checkpoint, now: ticks::Ticks) -> Option<Self> {
+            let mut peak: gcra::Gcra = self.peak;
+            let mut sustained: gcra::Gcra = self.sustained;
+
+            peak.restore(&checkpoint.peak, now)?;
+            sustained.restore(&checkpoint.sustained, now)?;
+
+            self.peak = peak;
+            self.sustained = sustained;
+            Some(*self)
+        }
+
+        /// Reconfigure a Contract object in service given a new peak increment
+        /// and jitter tolerance in ticks, a new sustained increment and burst
+        /// tolerance in ticks, and the current time in ticks since the epoch,
+        /// keeping the deficit of each GCRA as Gcra::reconfigure does.
+        pub fn reconfigure(& mut self, peakincrement: ticks::Ticks, jittertolerance: ticks::Ticks, sustainedincrement: ticks::Ticks, bursttolerance: ticks::Ticks, now: ticks::Ticks) -> Self {
+            self.peak.reconfigure(peakincrement, jittertolerance, now);
+            self.sustained.reconfigure(sustainedincrement, bursttolerance, now);
+            *self
+        }
+
+        fn burst(& self, deficit: ticks::Ticks) -> throttle::Events {
+            let peakincrement: ticks::Ticks = self.peak.get_increment();
+            let sustainedincrement: ticks::Ticks = self.sustained.get_increment();
+            let limit: ticks::Ticks = self.sustained.get_limit();
+
+            if peakincrement >= sustainedincrement {
+                i64::max_value()
+            } else if deficit > limit {
+                0
+            } else {
+                ((limit - deficit) / (sustainedincrement - peakincrement)) + 1
+            }
+        }
+
+        /// get_burstsize returns the number of events that an empty Contract
+        /// admits at the peak rate, which is the inverse of bursttolerance with
+        /// no jitter tolerance.
+        pub fn get_burstsize(& self) -> throttle::Events {
+            self.burst(0)
+        }
+
+        /// get_remaining returns the number of events that the Contract would
+        /// admit at the peak rate, starting as soon as the peak rate permits,
+        /// given the current time in ticks.
+        pub fn get_remaining(& self, now: ticks::Ticks) -> throttle::Events {
+            self.burst(self.sustained.get_reset(now + self.peak.get_retry(now)))
+        }
+
+        /// get_retry returns the delay in ticks before the Contract would admit
+        /// one more event given the current time in ticks.
+        pub fn get_retry(& self, now: ticks::Ticks) -> ticks::Ticks {
+            throttle::Throttle::peek(self, now, 1)
+        }
+
+        /// get_reset returns the delay in ticks before the Contract would be
+        /// empty given the current time in ticks.
+        pub fn get_reset(& self, now: ticks::Ticks) -> ticks::Ticks {
+            let peak: ticks::Ticks = self.peak.get_reset(now);
+            let sustained: ticks::Ticks = self.sustained.get_reset(now);
+
+            if peak > sustained {
+                peak
+            } else {
+                sustained
+            }
+        }
+
     }
     
     /// Compute the burst tolerance in ticks given the peak increment, jitter
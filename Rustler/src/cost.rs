@@ -0,0 +1,103 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a cost model that converts the length of a block of data into
+/// the number of events charged to a throttle for emitting it, so that a
+/// contract can be expressed in terms of what the block occupies on the
+/// wire instead of its payload alone. A fixed overhead per block accounts
+/// for framing, for example the preamble, start of frame delimiter, and
+/// minimum interframe gap of Ethernet, or the IPv4 and UDP headers. With
+/// the cell tax, the block and its overhead are charged as the whole
+/// fifty-three byte cells that its AAL5 CPCS-PDU segments into, so the
+/// overhead should then be whatever is carried inside the PDU, such as an
+/// LLC/SNAP header. The result is charged in bytes or in bits; a contract
+/// charged in bits has its rates in bits per second.
+///
+pub mod cost {
+
+    use std::string;
+    use throttle::throttle;
+    use aal5::aal5;
+
+    /// ETHERNET is the overhead in bytes of the preamble, start of frame
+    /// delimiter, and minimum interframe gap of an Ethernet frame.
+    pub const ETHERNET: usize = 20;
+
+    /// UDP is the overhead in bytes of the IPv4 and UDP headers.
+    pub const UDP: usize = 28;
+
+    /// Unit is what the cost of a block is measured in.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Unit {
+        Bytes,
+        Bits,
+    }
+
+    /// Model is a cost model.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Model {
+        overhead:   usize,                      // Bytes added to each block.
+        cells:      bool,                       // Charge whole AAL5 cells.
+        unit:       Unit,                       // Bytes or bits.
+    }
+
+    impl string::ToString for Model {
+
+        fn to_string(& self) -> string::String {
+            format!("Model@{:p}:{{o:{},c:{},u:{:?}}}",
+                self,
+                self.overhead, self.cells, self.unit)
+        }
+
+    }
+
+    impl Model {
+
+        /// Allocate a new Model given the overhead in bytes added to each
+        /// block, whether to charge the whole cells of its AAL5 CPCS-PDU, and
+        /// the unit charged. Model::new(0, false, Unit::Bytes) charges just
+        /// the length of each block.
+        pub fn new(overhead: usize, cells: bool, unit: Unit) -> Model {
+            Model { overhead, cells, unit }
+        }
+
+        /// cost returns the number of events charged for a block of the
+        /// specified length in bytes.
+        pub fn cost(& self, length: usize) -> throttle::Events {
+            let mut bytes: usize = length + self.overhead;
+
+            if self.cells {
+                bytes = aal5::cells(bytes) * aal5::CELL;
+            }
+
+            match self.unit {
+                Unit::Bytes => bytes as throttle::Events,
+                Unit::Bits => (bytes as throttle::Events) * 8,
+            }
+        }
+
+        /*** GETTERS ***/
+
+        /// get_overhead returns the overhead in bytes added to each block.
+        pub fn get_overhead(& self) -> usize {
+            self.overhead
+        }
+
+        /// get_cells returns true if the cell tax is charged.
+        pub fn get_cells(& self) -> bool {
+            self.cells
+        }
+
+        /// get_unit returns the unit charged.
+        pub fn get_unit(& self) -> Unit {
+            self.unit
+        }
+
+    }
+
+}
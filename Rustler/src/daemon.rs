@@ -0,0 +1,299 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements the server side of a rate limiting daemon that hosts many named
+/// throttles, typically Contracts, and answers queries about them from other
+/// processes, which need not be written in Rust, over a Unix domain socket or
+/// a TCP socket. Each named throttle is created from a copy of a template
+/// throttle the first time its name (the key) is used. All times are measured
+/// by the daemon using its own clock, so clients need not share a clock with
+/// it. Every connection is served by its own thread.
+///
+/// The protocol is a line protocol. Each request is a single line of ASCII
+/// text terminated by a newline, consisting of a command and its arguments
+/// separated by white space. Keys are any sequence of non-white space
+/// characters. Each request is answered by a single line, either "OK" followed
+/// by a value, or "ERR" followed by a message. Requests on a connection are
+/// answered in the order in which they were received.
+///
+/// ```text
+/// admit KEY EVENTS    OK 1 if the events were admitted, OK 0 otherwise,
+///                     ERR events if EVENTS is negative or more than
+///                     the constant EVENTS.
+/// request KEY         OK and the delay in nanoseconds before one more
+///                     event would be admitted.
+/// reset KEY           OK 0 after discarding the throttle for the key,
+///                     which is recreated from the template when next used.
+/// ```
+///
+/// A request for a key that is not yet known when the registry is at capacity
+/// first evicts the keys whose throttles have been idle long enough to be
+/// indistinguishable from new ones, and is answered with "ERR capacity" if
+/// that frees nothing. A panic while the registry is locked does not render
+/// it unusable for the other connections.
+///
+/// For example, using a shell:
+///
+/// ```text
+/// $ echo "admit alpha 1" | nc -U /tmp/throttled.sock
+/// OK 1
+/// ```
+///
+pub mod daemon {
+
+    use std::collections;
+    use std::io;
+    use std::io::BufRead;
+    use std::io::Read;
+    use std::marker;
+    use std::net;
+    use std::os::unix::net as unix;
+    use std::string;
+    use std::sync;
+    use std::thread;
+    use ticks::ticks;
+    use throttle::throttle;
+
+    /// MAXIMUM is the maximum length in bytes of a request line.
+    pub const MAXIMUM: usize = 1024;
+
+    /// EVENTS is the maximum number of events in a single admit request. Its
+    /// product with the increment of a throttle cannot overflow for any rate
+    /// of more than one event every couple of hours.
+    pub const EVENTS: throttle::Events = 1 << 20;
+
+    /// CAPACITY is the default maximum number of keys in a registry.
+    pub const CAPACITY: usize = 65536;
+
+    pub struct Registry<T: throttle::Throttle + marker::Copy> {
+        template:   T,                          // Copied for each new key.
+        capacity:   usize,                      // Maximum number of keys.
+        throttles:  collections::HashMap<string::String, T>, // Throttles by key.
+    }
+
+    impl<T: throttle::Throttle + marker::Copy> string::ToString for Registry<T> {
+
+        fn to_string(& self) -> string::String {
+            format!("Registry@{:p}:{{k:{},c:{},t:{}}}",
+                self,
+                self.throttles.len(), self.capacity,
+                self.template.as_string())
+        }
+
+    }
+
+    impl<T: throttle::Throttle + marker::Copy> Registry<T> {
+
+        /// Allocate a new Registry object given the template throttle from which
+        /// the throttle for each key is copied, holding at most CAPACITY keys.
+        pub fn new(template: T) -> Registry<T> {
+            Registry::with_capacity(template, CAPACITY)
+        }
+
+        /// Allocate a new Registry object given the template throttle from which
+        /// the throttle for each key is copied and the maximum number of keys.
+        pub fn with_capacity(template: T, capacity: usize) -> Registry<T> {
+            assert!(capacity > 0);
+            Registry {
+                template,
+                capacity,
+                throttles:  collections::HashMap::new(),
+            }
+        }
+
+        /// len returns the number of keys having throttles.
+        pub fn len(& self) -> usize {
+            self.throttles.len()
+        }
+
+        /// is_empty returns true if no key has a throttle.
+        pub fn is_empty(& self) -> bool {
+            self.throttles.is_empty()
+        }
+
+        /// get_capacity returns the maximum number of keys.
+        pub fn get_capacity(& self) -> usize {
+            self.capacity
+        }
+
+        /// evict discards the throttle of every key that has been idle long
+        /// enough as of the current time in ticks that it has emptied, and so
+        /// would behave no differently if it were recreated from the template.
+        /// It returns the number of keys evicted.
+        pub fn evict(& mut self, now: ticks::Ticks) -> usize {
+            let before: usize = self.throttles.len();
+
+            self.throttles.retain(|_, throttle| {
+                let mut copy: T = *throttle;
+                copy.update(now);
+                !copy.is_empty()
+            });
+
+            before - self.throttles.len()
+        }
+
+        fn throttle(& mut self, key: & str, now: ticks::Ticks) -> Option<& mut T> {
+            let template: T = self.template;
+
+            if self.throttles.contains_key(key) {
+                // Do nothing.
+            } else if self.throttles.len() < self.capacity {
+                // Do nothing.
+            } else if self.evict(now) > 0 {
+                // Do nothing.
+            } else {
+                return None;
+            }
+
+            Some(self.throttles.entry(key.to_string()).or_insert_with(|| {
+                let mut throttle: T = template;
+                throttle.reset(now);
+                throttle
+            }))
+        }
+
+        /// admits returns true if the throttle for the key admits the specified
+        /// number of events at the current time in ticks, or None if the key
+        /// is new and the registry is at capacity.
+        pub fn admits(& mut self, key: & str, events: throttle::Events, now: ticks::Ticks) -> Option<bool> {
+            self.throttle(key, now).map(|throttle| { throttle.admits(now, events) })
+        }
+
+        /// request returns the delay in ticks before the throttle for the key
+        /// would admit one more event, given the current time in ticks, or None
+        /// if the key is new and the registry is at capacity.
+        pub fn request(& mut self, key: & str, now: ticks::Ticks) -> Option<ticks::Ticks> {
+            self.throttle(key, now).map(|throttle| { throttle.peek(now, 1) })
+        }
+
+        /// reset discards the throttle for the key.
+        pub fn reset(& mut self, key: & str) {
+            self.throttles.remove(key);
+        }
+
+        /// handle a single request line given the current time in ticks and
+        /// return the response line without its terminating newline.
+        pub fn handle(& mut self, line: & str, now: ticks::Ticks) -> string::String {
+            let tokens: Vec<& str> = line.split_whitespace().collect();
+
+            match tokens.as_slice() {
+                ["admit", key, events] => {
+                    match events.parse::<throttle::Events>() {
+                        Ok(events) if (0..=EVENTS).contains(&events) => match self.admits(key, events, now) {
+                            Some(admitted) => format!("OK {}", admitted as i32),
+                            None => "ERR capacity".to_string(),
+                        },
+                        _ => "ERR events".to_string(),
+                    }
+                },
+                ["request", key] => {
+                    match self.request(key, now) {
+                        Some(delay) => format!("OK {}", ticks::to_nanoseconds(delay)),
+                        None => "ERR capacity".to_string(),
+                    }
+                },
+                ["reset", key] => {
+                    self.reset(key);
+                    "OK 0".to_string()
+                },
+                [] => {
+                    "ERR empty".to_string()
+                },
+                _ => {
+                    "ERR command".to_string()
+                },
+            }
+        }
+
+    }
+
+    /// Shared is a registry that may be used by many connections at once.
+    pub type Shared<T> = sync::Arc<sync::Mutex<Registry<T>>>;
+
+    /// lock locks a shared registry. If a thread panicked while holding the
+    /// lock, the registry is used as that thread left it rather than every
+    /// later request panicking too.
+    pub fn lock<T: throttle::Throttle + marker::Copy>(registry: & Shared<T>) -> sync::MutexGuard<'_, Registry<T>> {
+        registry.lock().unwrap_or_else(|error| { error.into_inner() })
+    }
+
+    /// session answers each request read from the reader by writing a response
+    /// to the writer until the reader reaches end of file or an error occurs.
+    pub fn session<T, R, W>(registry: & Shared<T>, reader: R, mut writer: W) -> io::Result<()>
+        where T: throttle::Throttle + marker::Copy, R: io::Read, W: io::Write {
+        let mut reader = io::BufReader::new(reader);
+        let mut line: string::String = string::String::new();
+        let mut response: string::String;
+
+        loop {
+
+            line.clear();
+            if (& mut reader).take(MAXIMUM as u64).read_line(& mut line)? == 0 {
+                break;
+            }
+
+            if !line.ends_with('\n') && (line.len() >= MAXIMUM) {
+                writer.write_all(b"ERR length\n")?;
+                break;
+            }
+
+            response = lock(registry).handle(&line, ticks::now());
+            response.push('\n');
+            writer.write_all(response.as_bytes())?;
+
+        }
+
+        Ok(())
+    }
+
+    /// serve_unix accepts connections on the Unix domain socket listener and
+    /// serves each in its own thread. It returns only if accepting fails.
+    pub fn serve_unix<T>(registry: Shared<T>, listener: unix::UnixListener) -> io::Error
+        where T: throttle::Throttle + marker::Copy + marker::Send + 'static {
+
+        loop {
+
+            let stream: unix::UnixStream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(error) => return error,
+            };
+
+            let registry: Shared<T> = registry.clone();
+            thread::spawn(move || {
+                if let Ok(reader) = stream.try_clone() {
+                    let _ = session(&registry, reader, stream);
+                }
+            });
+
+        }
+    }
+
+    /// serve_tcp accepts connections on the TCP socket listener and serves each
+    /// in its own thread. It returns only if accepting fails.
+    pub fn serve_tcp<T>(registry: Shared<T>, listener: net::TcpListener) -> io::Error
+        where T: throttle::Throttle + marker::Copy + marker::Send + 'static {
+
+        loop {
+
+            let stream: net::TcpStream = match listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(error) => return error,
+            };
+
+            let registry: Shared<T> = registry.clone();
+            thread::spawn(move || {
+                let _ = stream.set_nodelay(true);
+                if let Ok(reader) = stream.try_clone() {
+                    let _ = session(&registry, reader, stream);
+                }
+            });
+
+        }
+    }
+
+}
@@ -0,0 +1,370 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements fair queueing schedulers that share a single shaped aggregate
+/// among many flows. Each scheduler holds a queue of pending emissions for each
+/// flow (each emission being some number of events, for example the length of
+/// a data block) and chooses which flow emits next so that every backlogged
+/// flow receives a share of the aggregate in proportion to its weight (for
+/// weighted fair queueing) or its quantum (for deficit round robin). The
+/// chosen emissions are in turn fed through an aggregate throttle, typically a
+/// Contract, which determines when the next emission of any flow may occur.
+/// Each scheduler keeps a count of the events served on behalf of each flow.
+///
+/// The weighted fair queueing scheduler uses self-clocked virtual time: each
+/// emission is stamped with a virtual finish time when it is enqueued, and the
+/// emission with the smallest finish time is served first. The deficit round
+/// robin scheduler visits the backlogged flows in turn, crediting each with its
+/// quantum per visit and serving emissions while the credit allows.
+///
+/// REFERENCES
+///
+/// A. Demers, S. Keshav, S. Shenker, "Analysis and Simulation of a Fair
+/// Queueing Algorithm", ACM SIGCOMM, 1989
+///
+/// S. Golestani, "A Self-Clocked Fair Queueing Scheme for Broadband
+/// Applications", IEEE INFOCOM, 1994
+///
+/// M. Shreedhar, G. Varghese, "Efficient Fair Queueing Using Deficit Round
+/// Robin", ACM SIGCOMM, 1995
+///
+pub mod fairness {
+
+    use std::cmp;
+    use std::collections;
+    use std::option;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+    use scheduler::scheduler;
+
+    /// SCALE is the number of units of virtual time corresponding to one event
+    /// served on behalf of a flow having a weight of one.
+    pub const SCALE: u64 = 1_000_000;
+
+    /***************************************************************************
+     * WEIGHTED FAIR QUEUEING
+     **************************************************************************/
+
+    struct WfqQueue {
+        weight:     throttle::Events,           // Relative share of the aggregate.
+        finish:     u64,                        // Finish time of last enqueued.
+        pending:    collections::VecDeque<(throttle::Events, u64)>, // Emissions waiting.
+        served:     u64,                        // Events served so far.
+    }
+
+    pub struct Wfq {
+        aggregate:  Box<throttle::Throttle>,
+        queues:     Vec<WfqQueue>,
+        calendar:   collections::BinaryHeap<cmp::Reverse<(u64, u64, scheduler::Flow)>>,
+        vtime:      u64,
+        sequence:   u64,
+    }
+
+    impl string::ToString for Wfq {
+
+        fn to_string(& self) -> string::String {
+            format!("Wfq@{:p}:{{f:{},c:{},v:{},a:{}}}",
+                self,
+                self.queues.len(), self.calendar.len(), self.vtime,
+                self.aggregate.as_string())
+        }
+
+    }
+
+    impl Wfq {
+
+        /// Allocate a new Wfq object whose output is governed by the specified
+        /// aggregate throttle.
+        pub fn new(aggregate: Box<throttle::Throttle>) -> Wfq {
+            Wfq {
+                aggregate,
+                queues:     Vec::new(),
+                calendar:   collections::BinaryHeap::new(),
+                vtime:      0,
+                sequence:   0,
+            }
+        }
+
+        /// add a flow with the specified weight, which must be positive, to the
+        /// scheduler, returning the identifier of the new flow.
+        pub fn add(& mut self, weight: throttle::Events) -> scheduler::Flow {
+            assert!(weight > 0);
+            self.queues.push(WfqQueue {
+                weight,
+                finish:     0,
+                pending:    collections::VecDeque::new(),
+                served:     0,
+            });
+
+            self.queues.len() - 1
+        }
+
+        /// flows returns the number of flows in the scheduler.
+        pub fn flows(& self) -> usize {
+            self.queues.len()
+        }
+
+        /// aggregate returns a reference to the aggregate throttle.
+        pub fn aggregate(& self) -> & throttle::Throttle {
+            &*self.aggregate
+        }
+
+        /// pending returns the number of emissions queued on the specified flow.
+        pub fn pending(& self, flow: scheduler::Flow) -> usize {
+            self.queues[flow].pending.len()
+        }
+
+        /// served returns the number of events served on the specified flow.
+        pub fn served(& self, flow: scheduler::Flow) -> u64 {
+            self.queues[flow].served
+        }
+
+        /// is_empty returns true if no flow has any emissions queued.
+        pub fn is_empty(& self) -> bool {
+            self.calendar.is_empty()
+        }
+
+        /// enqueue an emission of the specified number of events on the specified
+        /// flow, stamping it with its virtual finish time, and returns true. An
+        /// emission of zero or fewer events is not queued and returns false.
+        pub fn enqueue(& mut self, flow: scheduler::Flow, events: throttle::Events) -> bool {
+            let start: u64;
+            let finish: u64;
+            let idle: bool;
+
+            if events <= 0 {
+                return false;
+            }
+
+            {
+                let queue: & mut WfqQueue = & mut self.queues[flow];
+                idle = queue.pending.is_empty();
+                start = cmp::max(queue.finish, self.vtime);
+                finish = start + ((events as u64) * SCALE) / (queue.weight as u64);
+                queue.finish = finish;
+                queue.pending.push_back((events, finish));
+            }
+
+            if idle {
+                self.calendar.push(cmp::Reverse((finish, self.sequence, flow)));
+                self.sequence += 1;
+            }
+
+            true
+        }
+
+        /// request computes, given the current time in ticks, how long of a delay
+        /// in ticks would be necessary before the aggregate throttle permits the
+        /// next emission, or None if no flow has emissions queued.
+        pub fn request(& self, now: ticks::Ticks) -> option::Option<ticks::Ticks> {
+            if self.calendar.is_empty() {
+                None
+            } else {
+                Some(self.aggregate.peek(now, 1))
+            }
+        }
+
+        /// dispatch removes the emission with the smallest virtual finish time if
+        /// the aggregate throttle permits an emission at the current time in ticks,
+        /// commits it to the aggregate throttle, and returns the flow and the number
+        /// of events. None is returned if nothing may be emitted yet.
+        pub fn dispatch(& mut self, now: ticks::Ticks) -> option::Option<(scheduler::Flow, throttle::Events)> {
+            let flow: scheduler::Flow;
+            let events: throttle::Events;
+            let finish: u64;
+            let mut next: option::Option<u64> = None;
+
+            match self.request(now) {
+                Some(0) => { },
+                _ => { return None; },
+            }
+
+            flow = match self.calendar.pop() {
+                Some(cmp::Reverse((_, _, flow))) => flow,
+                None => { return None; },
+            };
+
+            {
+                let queue: & mut WfqQueue = & mut self.queues[flow];
+                let pair = queue.pending.pop_front().unwrap();
+                events = pair.0;
+                finish = pair.1;
+                queue.served += events as u64;
+                if let Some(&(_, tag)) = queue.pending.front() {
+                    next = Some(tag);
+                }
+            }
+
+            self.vtime = finish;
+            if let Some(tag) = next {
+                self.calendar.push(cmp::Reverse((tag, self.sequence, flow)));
+                self.sequence += 1;
+            }
+
+            self.aggregate.admits(now, events);
+
+            Some((flow, events))
+        }
+
+    }
+
+    /***************************************************************************
+     * DEFICIT ROUND ROBIN
+     **************************************************************************/
+
+    struct DrrQueue {
+        quantum:    throttle::Events,           // Credit per round.
+        deficit:    throttle::Events,           // Credit remaining this round.
+        credited:   bool,                       // Credit granted this visit.
+        pending:    collections::VecDeque<throttle::Events>, // Emissions waiting.
+        served:     u64,                        // Events served so far.
+    }
+
+    pub struct Drr {
+        aggregate:  Box<throttle::Throttle>,
+        queues:     Vec<DrrQueue>,
+        active:     collections::VecDeque<scheduler::Flow>,
+    }
+
+    impl string::ToString for Drr {
+
+        fn to_string(& self) -> string::String {
+            format!("Drr@{:p}:{{f:{},r:{},a:{}}}",
+                self,
+                self.queues.len(), self.active.len(),
+                self.aggregate.as_string())
+        }
+
+    }
+
+    impl Drr {
+
+        /// Allocate a new Drr object whose output is governed by the specified
+        /// aggregate throttle.
+        pub fn new(aggregate: Box<throttle::Throttle>) -> Drr {
+            Drr {
+                aggregate,
+                queues:     Vec::new(),
+                active:     collections::VecDeque::new(),
+            }
+        }
+
+        /// add a flow with the specified quantum in events, which must be
+        /// positive, to the scheduler, returning the identifier of the new flow.
+        pub fn add(& mut self, quantum: throttle::Events) -> scheduler::Flow {
+            assert!(quantum > 0);
+            self.queues.push(DrrQueue {
+                quantum,
+                deficit:    0,
+                credited:   false,
+                pending:    collections::VecDeque::new(),
+                served:     0,
+            });
+
+            self.queues.len() - 1
+        }
+
+        /// flows returns the number of flows in the scheduler.
+        pub fn flows(& self) -> usize {
+            self.queues.len()
+        }
+
+        /// aggregate returns a reference to the aggregate throttle.
+        pub fn aggregate(& self) -> & throttle::Throttle {
+            &*self.aggregate
+        }
+
+        /// pending returns the number of emissions queued on the specified flow.
+        pub fn pending(& self, flow: scheduler::Flow) -> usize {
+            self.queues[flow].pending.len()
+        }
+
+        /// served returns the number of events served on the specified flow.
+        pub fn served(& self, flow: scheduler::Flow) -> u64 {
+            self.queues[flow].served
+        }
+
+        /// is_empty returns true if no flow has any emissions queued.
+        pub fn is_empty(& self) -> bool {
+            self.active.is_empty()
+        }
+
+        /// enqueue an emission of the specified number of events on the specified
+        /// flow, and returns true. An emission of zero or fewer events is not
+        /// queued and returns false.
+        pub fn enqueue(& mut self, flow: scheduler::Flow, events: throttle::Events) -> bool {
+            let queue: & mut DrrQueue = & mut self.queues[flow];
+
+            if events <= 0 {
+                return false;
+            }
+
+            if queue.pending.is_empty() {
+                self.active.push_back(flow);
+            }
+            queue.pending.push_back(events);
+
+            true
+        }
+
+        /// request computes, given the current time in ticks, how long of a delay
+        /// in ticks would be necessary before the aggregate throttle permits the
+        /// next emission, or None if no flow has emissions queued.
+        pub fn request(& self, now: ticks::Ticks) -> option::Option<ticks::Ticks> {
+            if self.active.is_empty() {
+                None
+            } else {
+                Some(self.aggregate.peek(now, 1))
+            }
+        }
+
+        /// dispatch removes the next emission in round robin order if the
+        /// aggregate throttle permits an emission at the current time in ticks,
+        /// commits it to the aggregate throttle, and returns the flow and the number
+        /// of events. None is returned if nothing may be emitted yet.
+        pub fn dispatch(& mut self, now: ticks::Ticks) -> option::Option<(scheduler::Flow, throttle::Events)> {
+            let mut flow: scheduler::Flow;
+            let events: throttle::Events;
+
+            match self.request(now) {
+                Some(0) => { },
+                _ => { return None; },
+            }
+
+            loop {
+                flow = *self.active.front().unwrap();
+                let queue: & mut DrrQueue = & mut self.queues[flow];
+                if !queue.credited {
+                    queue.deficit += queue.quantum;
+                    queue.credited = true;
+                }
+                if *queue.pending.front().unwrap() <= queue.deficit {
+                    events = queue.pending.pop_front().unwrap();
+                    queue.deficit -= events;
+                    queue.served += events as u64;
+                    if queue.pending.is_empty() {
+                        queue.deficit = 0;
+                        queue.credited = false;
+                        self.active.pop_front();
+                    }
+                    break;
+                }
+                queue.credited = false;
+                self.active.pop_front();
+                self.active.push_back(flow);
+            }
+
+            self.aggregate.admits(now, events);
+
+            Some((flow, events))
+        }
+
+    }
+
+}
@@ -52,7 +52,9 @@ pub mod gcra {
     use std::i64;
     use ticks::ticks;
     use throttle::throttle;
+    use units::units;
   
+    #[repr(C)]
     pub struct Gcra {
         now:        ticks::Ticks,         // Current timestamp
         then:       ticks::Ticks,         // Prior timestamp
@@ -70,6 +72,29 @@ pub mod gcra {
         alarmed2:   bool,                // The gcra was alarmed.
     }
     
+    /// Checkpoint is the complete state of a Gcra with its timestamps expressed
+    /// relative to the time at which the checkpoint was taken instead of to the
+    /// epoch of ticks, which differs from process to process. With the serde
+    /// feature it can be serialized to persist a Gcra across a restart.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+    pub struct Checkpoint {
+        pub now:        ticks::Ticks,         // Ticks since current timestamp
+        pub then:       ticks::Ticks,         // Ticks since prior timestamp
+        pub increment:  ticks::Ticks,         // GCRA i: ticks per event
+        pub limit:      ticks::Ticks,         // GCRA l: maximum deficit ticks
+        pub expected:   ticks::Ticks,         // GCRA x: expected ticks until next event
+        pub deficit:    ticks::Ticks,         // GCRA x1: current deficit ticks
+        pub full0:      bool,                // The leaky bucket will fill.
+        pub full1:      bool,                // The leaky bucket is filling.
+        pub full2:      bool,                // The leaky bucket was filled.
+        pub empty0:     bool,                // The leaky bucket will empty.
+        pub empty1:     bool,                // The leaky bucket is emptying.
+        pub empty2:     bool,                // The leaky bucket was emptied.
+        pub alarmed1:   bool,                // The gcra is alarmed.
+        pub alarmed2:   bool,                // The gcra was alarmed.
+    }
+
     pub const GCRA: Gcra = Gcra {
         now:        0,
         then:       0,
@@ -174,6 +199,26 @@ pub mod gcra {
         fn get_expected(& self) -> ticks::Ticks {
             self.expected
         }
+
+        /// peek computes, given the current time in ticks, how long of a delay in
+        /// ticks would be necessary before the last of the specified number of events,
+        /// each emitted as soon as the traffic contract permits, could itself be
+        /// emitted in compliance with the traffic contract. For zero or one event
+        /// this is the same value that request would return, but unlike
+        /// request, peek does not alter the state of the throttle.
+        fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+            let mut delay: ticks::Ticks = 0;
+            let mut deficit: ticks::Ticks = deficit(self.expected, now - self.then);
+
+            if events > 1 {
+                deficit = deficit.saturating_add(self.increment.saturating_mul(events - 1));
+            }
+            if deficit > self.limit {
+                delay = deficit - self.limit;
+            }
+
+            delay
+        }
         
         /// is_empty returns true if the throttle is empty, that is, it has no accumulated
         /// deficit ticks.
@@ -233,22 +278,19 @@ pub mod gcra {
             
             self.now = now;
             elapsed = self.now - self.then;
-            if self.expected <= elapsed {
-                self.deficit = 0;
+            self.deficit = deficit(self.expected, elapsed);
+            if self.deficit == 0 {
                 self.full0 = false;
                 self.empty0 = true;
                 delay = 0;
+            } else if self.deficit <= self.limit {
+                self.full0 = false;
+                self.empty0 = false;
+                delay = 0;
             } else {
-                self.deficit = self.expected - elapsed;
-                if self.deficit <= self.limit {
-                    self.full0 = false;
-                    self.empty0 = false;
-                    delay = 0;
-                } else {
-                    self.full0 = true;
-                    self.empty0 = false;
-                    delay = self.deficit - self.limit;
-                }
+                self.full0 = true;
+                self.empty0 = false;
+                delay = self.deficit - self.limit;
             }
             
             delay
@@ -332,8 +374,316 @@ pub mod gcra {
             *self
         }
 
+        /// Allocate and initialize a Gcra object given the rate, the maximum
+        /// burst size, and the current time, all strongly typed.
+        pub fn with(rate: units::Rate, burstsize: units::Events, now: units::Ticks) -> Gcra {
+            let increment: ticks::Ticks = rate.increment().0;
+
+            Gcra::new().init(increment, jittertolerance(increment, burstsize.0), now.0)
+        }
+
+        /// Capture the complete state of a Gcra object given the current time
+        /// in ticks since the epoch.
+        pub fn checkpoint(& self, now: ticks::Ticks) -> Checkpoint {
+            Checkpoint {
+                now:        now - self.now,
+                then:       now - self.then,
+                increment:  self.increment,
+                limit:      self.limit,
+                expected:   self.expected,
+                deficit:    self.deficit,
+                full0:      self.full0,
+                full1:      self.full1,
+                full2:      self.full2,
+                empty0:     self.empty0,
+                empty1:     self.empty1,
+                empty2:     self.empty2,
+                alarmed1:   self.alarmed1,
+                alarmed2:   self.alarmed2,
+            }
+        }
+
+        /// Restore a Gcra object to a previously captured state given the
+        /// current time in ticks since the epoch, which need not be the same
+        /// epoch as when the state was captured. The time between capturing and
+        /// restoring the state does not count as time elapsed for the throttle.
+        /// If the checkpoint cannot be expressed relative to the current time
+        /// without overflow, as may be the case for one that is corrupt or was
+        /// captured with a clock of a different type, it is rejected: None is
+        /// returned and the Gcra object is left unchanged.
+        pub fn restore(& mut self, checkpoint: & Checkpoint, now: ticks::Ticks) -> Option<Self> {
+            let earlier: ticks::Ticks = now.checked_sub(checkpoint.now)?;
+            let then: ticks::Ticks = now.checked_sub(checkpoint.then)?;
+            self.now = earlier;
+            self.then = then;
+            self.increment = checkpoint.increment;
+            self.limit = checkpoint.limit;
+            self.expected = checkpoint.expected;
+            self.deficit = checkpoint.deficit;
+            self.full0 = checkpoint.full0;
+            self.full1 = checkpoint.full1;
+            self.full2 = checkpoint.full2;
+            self.empty0 = checkpoint.empty0;
+            self.empty1 = checkpoint.empty1;
+            self.empty2 = checkpoint.empty2;
+            self.alarmed1 = checkpoint.alarmed1;
+            self.alarmed2 = checkpoint.alarmed2;
+            Some(*self)
+        }
+
+        /// Reconfigure a Gcra object in service given a new increment and
+        /// limit in ticks, and the current time in ticks since the epoch.
+        /// Unlike init, which resets it, the deficit outstanding at the current
+        /// time is kept: it is the events not yet paid for times the old
+        /// increment, and becomes the same events times the new increment,
+        /// rounded up. So a client whose rate is cut does not start afresh
+        /// with a full burst, and one whose rate is raised pays its debt off
+        /// sooner. Whether it is alarmed is then decided against the new
+        /// limit as if it were updated at the current time.
+        pub fn reconfigure(& mut self, increment: ticks::Ticks, limit: ticks::Ticks, now: ticks::Ticks) -> Self {
+            let deficit: i128 = self.get_reset(now) as i128;
+            let mut scaled: i128 = 0;
+
+            if deficit <= 0 {
+                // Do nothing.
+            } else if self.increment <= 0 {
+                // Do nothing.
+            } else if increment == self.increment {
+                scaled = deficit;
+            } else {
+                scaled = ((deficit * (increment as i128)) + (self.increment as i128) - 1) / (self.increment as i128);
+                if scaled > (i64::max_value() as i128) {
+                    scaled = i64::max_value() as i128;
+                }
+            }
+
+            self.increment = increment;
+            self.limit = limit;
+            self.then = now;
+            self.expected = scaled as ticks::Ticks;
+            self.update(now);
+            *self
+        }
+
+        /// get_increment returns the increment in ticks per event.
+        pub fn get_increment(& self) -> ticks::Ticks {
+            self.increment
+        }
+
+        /// get_limit returns the limit in ticks.
+        pub fn get_limit(& self) -> ticks::Ticks {
+            self.limit
+        }
+
+        /// get_burstsize returns the number of events that an empty Gcra admits
+        /// back to back, which is the inverse of jittertolerance.
+        pub fn get_burstsize(& self) -> throttle::Events {
+            if self.increment <= 0 {
+                i64::max_value()
+            } else {
+                (self.limit / self.increment) + 1
+            }
+        }
+
+        /// get_remaining returns the number of events that the Gcra would admit
+        /// back to back given the current time in ticks.
+        pub fn get_remaining(& self, now: ticks::Ticks) -> throttle::Events {
+            let deficit: ticks::Ticks = self.get_reset(now);
+
+            if self.increment <= 0 {
+                i64::max_value()
+            } else if deficit > self.limit {
+                0
+            } else {
+                ((self.limit - deficit) / self.increment) + 1
+            }
+        }
+
+        /// get_retry returns the delay in ticks before the Gcra would admit one
+        /// more event given the current time in ticks.
+        pub fn get_retry(& self, now: ticks::Ticks) -> ticks::Ticks {
+            self.peek(now, 1)
+        }
+
+        /// get_reset returns the delay in ticks before the Gcra would be empty
+        /// given the current time in ticks.
+        pub fn get_reset(& self, now: ticks::Ticks) -> ticks::Ticks {
+            deficit(self.expected, now - self.then)
+        }
+
     }
     
+    /// PoliceGcra is a Gcra with policing instead of shaping semantics. A Gcra
+    /// charges every emission committed to it, conforming or not, to its
+    /// virtual schedule, which is what a shaper wants since the emission was
+    /// in fact made. But a policer discards the events that do not conform,
+    /// and (as in ATM usage parameter control) those events must not advance
+    /// the theoretical arrival time, or a source that briefly exceeds its
+    /// contract is penalized further for traffic that was already dropped.
+    /// A PoliceGcra commits the events of a conforming emission as a Gcra
+    /// would, but only marks the passage of time for a non-conforming one,
+    /// and counts its events as rejected. Its commits and admits return true
+    /// if the events were admitted and false if they were rejected.
+    #[derive(Clone, Copy)]
+    #[repr(C)]
+    pub struct PoliceGcra {
+        gcra:       Gcra,                 // Underlying GCRA.
+        delay:      ticks::Ticks,         // Delay returned by last request.
+        rejected:   throttle::Events,     // Total events rejected.
+    }
+
+    pub static SIZE_OF_POLICEGCRA: usize = mem::size_of::<PoliceGcra>(); // Not actually a function call.
+
+    impl string::ToString for PoliceGcra {
+
+        fn to_string(& self) -> string::String {
+            format!("PoliceGcra@{:p}[{}]:{{g:{},d:{},r:{}}}",
+                self, SIZE_OF_POLICEGCRA,
+                self.gcra.to_string(), self.delay, self.rejected)
+        }
+
+    }
+
+    impl throttle::Throttle for PoliceGcra {
+
+        fn size_of(& self) -> usize {
+            SIZE_OF_POLICEGCRA
+        }
+
+        fn as_string(& self) -> string::String {
+            string::ToString::to_string(self)
+        }
+
+        /// reset the throttle back to its initial state, including the count
+        /// of rejected events.
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.gcra.reset(now);
+            self.delay = 0;
+            self.rejected = 0;
+        }
+
+        fn get_expected(& self) -> ticks::Ticks {
+            self.gcra.get_expected()
+        }
+
+        fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+            self.gcra.peek(now, events)
+        }
+
+        fn is_empty(& self) -> bool {
+            self.gcra.is_empty()
+        }
+
+        fn is_full(& self) -> bool {
+            self.gcra.is_full()
+        }
+
+        fn is_alarmed(& self) -> bool {
+            self.gcra.is_alarmed()
+        }
+
+        fn emptied(& self) -> bool {
+            self.gcra.emptied()
+        }
+
+        fn filled(& self) -> bool {
+            self.gcra.filled()
+        }
+
+        fn alarmed(& self) -> bool {
+            self.gcra.alarmed()
+        }
+
+        fn cleared(& self) -> bool {
+            self.gcra.cleared()
+        }
+
+        fn request(& mut self, now: ticks::Ticks) -> ticks::Ticks {
+            self.delay = self.gcra.request(now);
+            self.delay
+        }
+
+        /// commits admits the number of events if the emission was conforming
+        /// at the time specified in the previous request, and returns true.
+        /// Otherwise it rejects them without charging them to the virtual
+        /// schedule, and returns false. Committing zero events only marks the
+        /// passage of time, and returns false if the throttle is alarmed.
+        fn commits(& mut self, events: throttle::Events) -> bool {
+            if events <= 0 {
+                self.gcra.commits(0)
+            } else if self.delay > 0 {
+                self.gcra.commits(0);
+                self.rejected += events;
+                false
+            } else {
+                self.gcra.commits(events);
+                true
+            }
+        }
+
+        fn commit(& mut self) -> bool {
+            self.commits(1)
+        }
+
+        fn admits(& mut self, now: ticks::Ticks, events: throttle::Events) -> bool {
+            self.request(now);
+            self.commits(events)
+        }
+
+        fn admit(& mut self, now: ticks::Ticks) -> bool {
+            self.admits(now, 1)
+        }
+
+        fn update(& mut self, now: ticks::Ticks) -> bool {
+            self.admits(now, 0)
+        }
+
+    }
+
+    impl PoliceGcra {
+
+        /// Allocate a new PoliceGcra object with zero values for all its fields.
+        pub fn new() -> PoliceGcra {
+            PoliceGcra { gcra: GCRA, delay: 0, rejected: 0 }
+        }
+
+        /// Initialize a PoliceGcra object given an increment and limit in
+        /// ticks, and the current time in ticks since the epoch.
+        pub fn init(& mut self, increment: ticks::Ticks, limit: ticks::Ticks, now: ticks::Ticks) -> Self {
+            self.gcra.init(increment, limit, now);
+            self.reset(now);
+            *self
+        }
+
+        /// Allocate and initialize a PoliceGcra object given the rate, the
+        /// maximum burst size, and the current time, all strongly typed.
+        pub fn with(rate: units::Rate, burstsize: units::Events, now: units::Ticks) -> PoliceGcra {
+            PoliceGcra { gcra: Gcra::with(rate, burstsize, now), delay: 0, rejected: 0 }
+        }
+
+        /// Reconfigure a PoliceGcra object in service given a new increment
+        /// and limit in ticks, and the current time in ticks since the epoch,
+        /// keeping its deficit as Gcra::reconfigure does and its count of
+        /// rejected events.
+        pub fn reconfigure(& mut self, increment: ticks::Ticks, limit: ticks::Ticks, now: ticks::Ticks) -> Self {
+            self.gcra.reconfigure(increment, limit, now);
+            self.delay = 0;
+            *self
+        }
+
+        /// get_rejected returns the total number of events rejected since the
+        /// throttle was initialized or reset.
+        pub fn get_rejected(& self) -> throttle::Events {
+            self.rejected
+        }
+
+        /// gcra returns a reference to the underlying Gcra.
+        pub fn gcra(& self) -> & Gcra {
+            &self.gcra
+        }
+
+    }
+
     /// Compute an increment in ticks given the rate specified as the ratio of
     /// a numerator and a denominator, and the frequency.
     pub fn increment(numerator: throttle::Events, denominator: throttle::Events, frequency: ticks::Ticks) -> ticks::Ticks {
@@ -390,4 +740,14 @@ pub mod gcra {
         limit
     }
 
+    /// Compute the deficit in ticks remaining of the specified expected ticks
+    /// after the specified elapsed ticks, which is never less than zero.
+    pub fn deficit(expected: ticks::Ticks, elapsed: ticks::Ticks) -> ticks::Ticks {
+        if expected > elapsed {
+            expected - elapsed
+        } else {
+            0
+        }
+    }
+
 }
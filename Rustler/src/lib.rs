@@ -6,6 +6,12 @@
 // mailto:coverclock@diag.com
 // https://github.com/coverclock/com-diag-rustler
 
+extern crate libc;
+
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+
 #[allow(dead_code)]
 
 pub mod ticks;
@@ -13,3 +19,24 @@ pub mod fletcher;
 pub mod throttle;
 pub mod gcra;
 pub mod contract;
+pub mod scheduler;
+pub mod fairness;
+pub mod shaper;
+pub mod shared;
+pub mod daemon;
+pub mod client;
+pub mod cell;
+pub mod ratelimit;
+pub mod trace;
+pub mod trafgen;
+pub mod random;
+pub mod simulator;
+pub mod units;
+pub mod clock;
+pub mod atm;
+pub mod bucket;
+pub mod aal5;
+pub mod mef;
+pub mod cost;
+#[cfg(feature = "conformance")]
+pub mod conformance;
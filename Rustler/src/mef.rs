@@ -0,0 +1,200 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements the Metro Ethernet Forum (MEF) bandwidth profile algorithm used
+/// to specify Carrier Ethernet services, as applied to frames arriving on a
+/// flow. A bandwidth profile has a committed information rate (CIR) and
+/// committed burst size (CBS), an excess information rate (EIR) and excess
+/// burst size (EBS), a coupling flag (CF), and a color mode (CM). Each frame
+/// is declared green (within the CIR and CBS), yellow (within the EIR and
+/// EBS, delivered on a best effort basis), or red (discarded). The committed
+/// and excess token buckets start full and fill at the CIR and EIR. Unlike a
+/// Contract, in which every event must conform to both the peak and the
+/// sustained rates, and unlike the two rate three color marker of RFC 2698,
+/// in which the committed bucket is a subset of the peak bucket, the two
+/// buckets are independent, and if the coupling flag is set, tokens that
+/// would overflow the committed bucket go to the excess bucket instead, so
+/// that committed bandwidth left unused can carry yellow frames. In color
+/// blind mode the color a frame arrives with is ignored; in color aware mode
+/// a yellow frame can never be green, and a red frame is always red. Time is
+/// in ticks; rates are in bits per second and burst sizes in bytes, and each
+/// bucket holds bits scaled by the frequency so that the arithmetic is exact
+/// for any rate. Each bucket is kept as its deficit, the tokens it lacks of
+/// being full, which drains with the same arithmetic as the deficit of a Gcra,
+/// only by the CIR or EIR per tick instead of by one; the drain beyond what
+/// empties the committed deficit is the overflow. A frame arriving earlier
+/// than the one before it is treated as arriving at the same time.
+///
+/// REFERENCES
+///
+/// MEF, "Ethernet Services Attributes Phase 2", MEF 10.2, 2009-10, 7.11
+///
+/// MEF, "Ethernet Services Attributes Phase 3", MEF 10.3, 2013-10, 12
+///
+/// J. Heinanen, R. Guerin, "A Two Rate Three Color Marker", RFC 2698,
+/// 1999-09
+///
+pub mod mef {
+
+    use std::string;
+    use std::i64;
+    use ticks::ticks;
+    use gcra::gcra;
+
+    /// Color is the color of a frame.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Color {
+        Green,                                  // Within CIR and CBS.
+        Yellow,                                 // Within EIR and EBS.
+        Red,                                    // Discarded.
+    }
+
+    /// Mode is the color mode of a bandwidth profile.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Mode {
+        Blind,                                  // Ignore the frame color.
+        Aware,                                  // Never promote a frame.
+    }
+
+    /// scale returns the number of bits in the specified number of bytes
+    /// scaled by the specified frequency, saturating at the largest Ticks.
+    fn scale(bytes: i64, frequency: ticks::Ticks) -> ticks::Ticks {
+        bytes.saturating_mul(8).saturating_mul(frequency)
+    }
+
+    /// Profile is a MEF bandwidth profile.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Profile {
+        cir:        i64,                        // CIR in bits per second.
+        cbs:        ticks::Ticks,               // CBS in scaled bits.
+        eir:        i64,                        // EIR in bits per second.
+        ebs:        ticks::Ticks,               // EBS in scaled bits.
+        coupled:    bool,                       // CF.
+        mode:       Mode,                       // CM.
+        frequency:  ticks::Ticks,               // Ticks per second.
+        committed:  ticks::Ticks,               // Committed deficit in scaled bits.
+        excess:     ticks::Ticks,               // Excess deficit in scaled bits.
+        then:       ticks::Ticks,               // Time of the last arrival.
+    }
+
+    impl string::ToString for Profile {
+
+        fn to_string(& self) -> string::String {
+            format!("Profile@{:p}:{{cir:{},cbs:{},eir:{},ebs:{},cf:{},cm:{:?},f:{},c:{},e:{},t:{}}}",
+                self,
+                self.cir, self.cbs, self.eir, self.ebs, self.coupled as u8, self.mode,
+                self.frequency, self.committed, self.excess, self.then)
+        }
+
+    }
+
+    impl Profile {
+
+        /// Allocate a new Profile given the CIR in bits per second, the CBS
+        /// in bytes, the EIR in bits per second, the EBS in bytes, the
+        /// coupling flag, the color mode, and the current time in ticks. Both
+        /// buckets start full.
+        pub fn new(cir: i64, cbs: i64, eir: i64, ebs: i64, coupled: bool, mode: Mode, now: ticks::Ticks) -> Profile {
+            assert!(cir >= 0);
+            assert!(cbs >= 0);
+            assert!(eir >= 0);
+            assert!(ebs >= 0);
+            let frequency: ticks::Ticks = ticks::frequency();
+            assert!(scale(cbs, frequency) < i64::max_value());
+            assert!(scale(ebs, frequency) < i64::max_value());
+            Profile {
+                cir,
+                cbs:        scale(cbs, frequency),
+                eir,
+                ebs:        scale(ebs, frequency),
+                coupled,
+                mode,
+                frequency,
+                committed:  0,
+                excess:     0,
+                then:       now,
+            }
+        }
+
+        /// reset fills both buckets as of the specified time in ticks.
+        pub fn reset(& mut self, now: ticks::Ticks) {
+            self.committed = 0;
+            self.excess = 0;
+            self.then = now;
+        }
+
+        /// fill drains the deficits of both buckets by the tokens accumulated
+        /// since the last arrival, overflowing the committed bucket into the
+        /// excess bucket if the profile is coupled. Time that goes backwards
+        /// accumulates nothing and is otherwise ignored.
+        fn fill(& mut self, now: ticks::Ticks) {
+            if now <= self.then {
+                return;
+            }
+
+            let elapsed: ticks::Ticks = now - self.then;
+            let committed: ticks::Ticks = self.cir.saturating_mul(elapsed);
+            let mut excess: ticks::Ticks = self.eir.saturating_mul(elapsed);
+
+            if self.coupled {
+                excess = excess.saturating_add(gcra::deficit(committed, self.committed));
+            }
+
+            self.committed = gcra::deficit(self.committed, committed);
+            self.excess = gcra::deficit(self.excess, excess);
+            self.then = now;
+        }
+
+        /// frame returns the color of a frame of the specified length in
+        /// bytes and with the specified color arriving at the specified time
+        /// in ticks, taking its length in tokens from the bucket for that
+        /// color, if any.
+        pub fn frame(& mut self, now: ticks::Ticks, length: usize, color: Color) -> Color {
+            let blind: bool = self.mode == Mode::Blind;
+            let tokens: ticks::Ticks = scale(length as i64, self.frequency);
+
+            self.fill(now);
+
+            if (blind || (color == Color::Green)) && (self.committed.saturating_add(tokens) <= self.cbs) {
+                self.committed += tokens;
+                Color::Green
+            } else if (blind || (color != Color::Red)) && (self.excess.saturating_add(tokens) <= self.ebs) {
+                self.excess += tokens;
+                Color::Yellow
+            } else {
+                Color::Red
+            }
+        }
+
+        /*** GETTERS ***/
+
+        /// get_committed returns the whole bytes in the committed bucket as of
+        /// the last arrival.
+        pub fn get_committed(& self) -> i64 {
+            (self.cbs - self.committed) / 8 / self.frequency
+        }
+
+        /// get_excess returns the whole bytes in the excess bucket as of the
+        /// last arrival.
+        pub fn get_excess(& self) -> i64 {
+            (self.ebs - self.excess) / 8 / self.frequency
+        }
+
+        /// is_coupled returns the coupling flag.
+        pub fn is_coupled(& self) -> bool {
+            self.coupled
+        }
+
+        /// get_mode returns the color mode.
+        pub fn get_mode(& self) -> Mode {
+            self.mode
+        }
+
+    }
+
+}
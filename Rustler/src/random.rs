@@ -0,0 +1,128 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a small seedable pseudo-random number generator, Marsaglia's
+/// 64-bit xorshift, with the handful of distributions the rest of the crate
+/// and its tests need. It is not cryptographically secure. Its virtue is that
+/// the same seed always produces the same sequence on every platform, so a
+/// run that fails can be replayed exactly given its seed. Each Random object
+/// has its own state; objects are not shared between threads.
+///
+pub mod random {
+
+    use std::env;
+    use std::string;
+    use ticks::ticks;
+
+    /// SEED is the name of the environment variable that, if set to an
+    /// unsigned decimal integer, is returned by seed().
+    pub const SEED: &str = "RUSTLER_SEED";
+
+    /// GOLDEN replaces a seed of zero, which xorshift cannot escape.
+    const GOLDEN: u64 = 0x9e37_79b9_7f4a_7c15;
+
+    /// seed returns the value of the RUSTLER_SEED environment variable if it
+    /// is set and valid, or else a seed derived from the monotonic clock. A
+    /// caller that logs the seed it was given can be replayed by setting the
+    /// variable to that value.
+    pub fn seed() -> u64 {
+        if let Ok(value) = env::var(SEED) {
+            if let Ok(seed) = value.trim().parse::<u64>() {
+                return seed;
+            }
+        }
+
+        let mut seed: u64 = ticks::monotonic() as u64;
+        seed ^= seed >> 33;
+        seed = seed.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        seed ^= seed >> 33;
+
+        seed
+    }
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Random {
+        seed:       u64,                        // Initial seed.
+        state:      u64,                        // Current state.
+    }
+
+    impl string::ToString for Random {
+
+        fn to_string(& self) -> string::String {
+            format!("Random@{:p}:{{s:{},x:{}}}",
+                self,
+                self.seed, self.state)
+        }
+
+    }
+
+    impl Random {
+
+        /// Allocate a new Random object given its seed. A seed of zero is
+        /// replaced by a fixed non-zero value.
+        pub fn new(seed: u64) -> Random {
+            Random {
+                seed,
+                state:      if seed == 0 { GOLDEN } else { seed },
+            }
+        }
+
+        /// get_seed returns the seed with which the generator was allocated.
+        pub fn get_seed(& self) -> u64 {
+            self.seed
+        }
+
+        /// generate returns the next 64-bit value in the sequence.
+        pub fn generate(& mut self) -> u64 {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 7;
+            self.state ^= self.state << 17;
+
+            self.state
+        }
+
+        /// uniform returns a value uniformly distributed in [0.0, 1.0).
+        pub fn uniform(& mut self) -> f64 {
+            ((self.generate() >> 11) as f64) / ((1u64 << 53) as f64)
+        }
+
+        /// range returns a value uniformly distributed between low and high
+        /// inclusive.
+        pub fn range(& mut self, low: u64, high: u64) -> u64 {
+            assert!(low <= high);
+
+            let span: u64 = high - low;
+
+            if span == u64::max_value() {
+                self.generate()
+            } else {
+                low + (self.generate() % (span + 1))
+            }
+        }
+
+        /// exponential returns a value exponentially distributed with the
+        /// specified mean.
+        pub fn exponential(& mut self, mean: f64) -> f64 {
+            -mean * (1.0 - self.uniform()).ln()
+        }
+
+        /// printable returns a printable ASCII character.
+        pub fn printable(& mut self) -> u8 {
+            self.range(b' ' as u64, b'~' as u64) as u8
+        }
+
+        /// fill fills the buffer with printable ASCII characters.
+        pub fn fill(& mut self, buffer: & mut [u8]) {
+            for byte in buffer.iter_mut() {
+                *byte = self.printable();
+            }
+        }
+
+    }
+
+}
@@ -0,0 +1,75 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Formats the HTTP header fields with which a server tells its clients about
+/// the rate limit to which they are subject, given values such as those
+/// returned by the get_burstsize, get_remaining, get_retry, and get_reset
+/// getters of a Gcra or a Contract. The RateLimit-Limit, RateLimit-Remaining,
+/// and RateLimit-Reset fields are those of the IETF draft, and Retry-After is
+/// that of HTTP, added only when no event remains. Times are given in whole
+/// seconds, rounded up so that a client that waits as long as it is told will
+/// not be limited.
+///
+/// REFERENCES
+///
+/// R. Polli, A. Martinez, "RateLimit header fields for HTTP",
+/// draft-ietf-httpapi-ratelimit-headers, IETF
+///
+/// R. Fielding, M. Nottingham, J. Reschke, "HTTP Semantics", RFC 9110,
+/// section 10.2.3, IETF, June 2022
+///
+pub mod ratelimit {
+
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+
+    pub const LIMIT: &str = "RateLimit-Limit";
+    pub const REMAINING: &str = "RateLimit-Remaining";
+    pub const RESET: &str = "RateLimit-Reset";
+    pub const RETRY: &str = "Retry-After";
+
+    /// seconds converts ticks to whole seconds, rounding up.
+    pub fn seconds(ticks: ticks::Ticks) -> i64 {
+        let frequency: ticks::Ticks = ticks::frequency();
+
+        if ticks <= 0 {
+            0
+        } else {
+            (ticks / frequency) + (((ticks % frequency) > 0) as i64)
+        }
+    }
+
+    /// fields returns the name and value of each header field given the limit
+    /// and remaining in events, and the retry and reset delays in ticks.
+    pub fn fields(limit: throttle::Events, remaining: throttle::Events, retry: ticks::Ticks, reset: ticks::Ticks) -> Vec<(&'static str, string::String)> {
+        let mut fields: Vec<(&'static str, string::String)> = Vec::new();
+
+        fields.push((LIMIT, limit.to_string()));
+        fields.push((REMAINING, remaining.to_string()));
+        fields.push((RESET, seconds(reset).to_string()));
+        if remaining <= 0 {
+            fields.push((RETRY, seconds(retry).to_string()));
+        }
+
+        fields
+    }
+
+    /// headers returns the header fields as lines each terminated by a carriage
+    /// return and a line feed, ready to be placed in an HTTP response.
+    pub fn headers(limit: throttle::Events, remaining: throttle::Events, retry: ticks::Ticks, reset: ticks::Ticks) -> string::String {
+        let mut headers: string::String = string::String::new();
+
+        for (name, value) in fields(limit, remaining, retry, reset) {
+            headers.push_str(&format!("{}: {}\r\n", name, value));
+        }
+
+        headers
+    }
+
+}
@@ -0,0 +1,180 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements an earliest eligible scheduler that multiplexes many throttled
+/// flows onto a single output. Each flow has its own queue of pending emissions
+/// (each emission being some number of events, for example the length of a
+/// data block) and its own throttle, typically a Gcra or a Contract. The
+/// scheduler keeps the flows that have pending emissions in a priority queue
+/// keyed on the absolute time in ticks at which the throttle of each flow would
+/// permit its next emission, and always dispatches the flow whose throttle
+/// permits the earliest emission. This is similar to a virtual clock or an
+/// earliest deadline first scheduler in which the deadline of each flow is
+/// derived from its traffic contract. Because the eligibility of a flow is
+/// computed with the non-mutating peek, only the throttle of the dispatched
+/// flow is ever altered by the scheduler.
+///
+/// REFERENCES
+///
+/// L. Zhang, "Virtual Clock: A New Traffic Control Algorithm for Packet
+/// Switching Networks", ACM SIGCOMM, 1990
+///
+/// C. Overclock, "Rate Control Using Throttles", 2007-01,
+/// http://coverclock.blogspot.com/2007/01/rate-control-and-throttles.html
+///
+pub mod scheduler {
+
+    use std::cmp;
+    use std::collections;
+    use std::option;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+
+    /// Flow is the type used to identify a flow within a scheduler.
+    pub type Flow = usize;
+
+    struct Queue {
+        throttle:   Box<throttle::Throttle>,    // Throttle for this flow.
+        pending:    collections::VecDeque<throttle::Events>, // Emissions waiting.
+        scheduled:  bool,                       // Flow is in the calendar.
+    }
+
+    pub struct Scheduler {
+        queues:     Vec<Queue>,
+        calendar:   collections::BinaryHeap<cmp::Reverse<(ticks::Ticks, u64, Flow)>>,
+        sequence:   u64,
+    }
+
+    impl string::ToString for Scheduler {
+
+        fn to_string(& self) -> string::String {
+            format!("Scheduler@{:p}:{{f:{},c:{},s:{}}}",
+                self,
+                self.queues.len(), self.calendar.len(), self.sequence)
+        }
+
+    }
+
+    impl Scheduler {
+
+        /// Allocate a new Scheduler object with no flows.
+        pub fn new() -> Scheduler {
+            Scheduler {
+                queues:     Vec::new(),
+                calendar:   collections::BinaryHeap::new(),
+                sequence:   0,
+            }
+        }
+
+        /// add a flow governed by the specified throttle to the scheduler,
+        /// returning the identifier of the new flow.
+        pub fn add(& mut self, throttle: Box<throttle::Throttle>) -> Flow {
+            self.queues.push(Queue {
+                throttle,
+                pending:    collections::VecDeque::new(),
+                scheduled:  false,
+            });
+
+            self.queues.len() - 1
+        }
+
+        /// flows returns the number of flows in the scheduler.
+        pub fn flows(& self) -> usize {
+            self.queues.len()
+        }
+
+        /// throttle returns a reference to the throttle of the specified flow.
+        pub fn throttle(& self, flow: Flow) -> & throttle::Throttle {
+            &*self.queues[flow].throttle
+        }
+
+        /// pending returns the number of emissions queued on the specified flow.
+        pub fn pending(& self, flow: Flow) -> usize {
+            self.queues[flow].pending.len()
+        }
+
+        /// is_empty returns true if no flow has any emissions queued.
+        pub fn is_empty(& self) -> bool {
+            self.calendar.is_empty()
+        }
+
+        fn schedule(& mut self, flow: Flow, now: ticks::Ticks) {
+            let eligible: ticks::Ticks;
+            let queue: & mut Queue = & mut self.queues[flow];
+
+            if queue.scheduled {
+                // Do nothing.
+            } else if queue.pending.is_empty() {
+                // Do nothing.
+            } else {
+                eligible = now + queue.throttle.peek(now, 1);
+                queue.scheduled = true;
+                self.calendar.push(cmp::Reverse((eligible, self.sequence, flow)));
+                self.sequence += 1;
+            }
+        }
+
+        /// enqueue an emission of the specified number of events on the specified
+        /// flow given the current time in ticks.
+        pub fn enqueue(& mut self, flow: Flow, events: throttle::Events, now: ticks::Ticks) {
+            self.queues[flow].pending.push_back(events);
+            self.schedule(flow, now);
+        }
+
+        /// eligible returns the absolute time in ticks at which the earliest
+        /// eligible flow may next emit, or None if no flow has emissions queued.
+        pub fn eligible(& self) -> option::Option<ticks::Ticks> {
+            self.calendar.peek().map(|&cmp::Reverse((eligible, _, _))| eligible)
+        }
+
+        /// request computes, given the current time in ticks, how long of a delay
+        /// in ticks would be necessary before the earliest eligible flow could emit
+        /// in compliance with its traffic contract, or None if no flow has emissions
+        /// queued.
+        pub fn request(& self, now: ticks::Ticks) -> option::Option<ticks::Ticks> {
+            match self.eligible() {
+                Some(eligible) if eligible > now => Some(eligible - now),
+                Some(_) => Some(0),
+                None => None,
+            }
+        }
+
+        /// dispatch removes the next emission from the earliest eligible flow if
+        /// that flow may emit at the current time in ticks, commits it to the
+        /// throttle of that flow, and returns the flow and the number of events.
+        /// None is returned if no flow may emit yet.
+        pub fn dispatch(& mut self, now: ticks::Ticks) -> option::Option<(Flow, throttle::Events)> {
+            let flow: Flow;
+            let events: throttle::Events;
+
+            match self.request(now) {
+                Some(0) => { },
+                _ => { return None; },
+            }
+
+            flow = match self.calendar.pop() {
+                Some(cmp::Reverse((_, _, flow))) => flow,
+                None => { return None; },
+            };
+
+            {
+                let queue: & mut Queue = & mut self.queues[flow];
+                events = queue.pending.pop_front().unwrap();
+                queue.scheduled = false;
+                queue.throttle.admits(now, events);
+            }
+
+            self.schedule(flow, now);
+
+            Some((flow, events))
+        }
+
+    }
+
+}
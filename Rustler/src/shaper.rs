@@ -0,0 +1,334 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a traffic shaper consisting of a bounded queue in front of a
+/// throttle. Emissions (each being some number of events, for example the
+/// length of a data block, along with an arbitrary payload) are offered to the
+/// shaper as they arrive and are polled from it when the throttle permits. When
+/// the queue would overflow, or when the active queue management policy says so,
+/// emissions are dropped instead of blocking the producer, and the shaper keeps
+/// count of what it has dropped. The supported policies are tail drop (drop the
+/// arriving emission), head drop (drop the oldest emissions to make room),
+/// Random Early Detection (drop arriving emissions with a probability that
+/// rises with the average queue depth), and Controlled Delay (drop departing
+/// emissions whose time in the queue has stayed above a target for at least an
+/// interval). RED and CoDel also tail drop when the queue is full.
+///
+/// REFERENCES
+///
+/// S. Floyd, V. Jacobson, "Random Early Detection Gateways for Congestion
+/// Avoidance", IEEE/ACM Transactions on Networking, 1993-08
+///
+/// K. Nichols, V. Jacobson, A. McGregor, J. Iyengar, "Controlled Delay Active
+/// Queue Management", RFC 8289, https://tools.ietf.org/html/rfc8289, IETF,
+/// January 2018
+///
+pub mod shaper {
+
+    use std::collections;
+    use std::marker;
+    use std::option;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+    use random::random;
+
+    /// Policy describes what a shaper does when its queue is congested.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Policy {
+        /// Drop the arriving emission if it does not fit.
+        TailDrop,
+        /// Drop the oldest emissions until the arriving emission fits.
+        HeadDrop,
+        /// Random Early Detection given the minimum and maximum thresholds of the
+        /// average queue depth in events and the maximum drop probability.
+        Red { minimum: throttle::Events, maximum: throttle::Events, probability: f64 },
+        /// Controlled Delay given the target and interval in ticks.
+        Codel { target: ticks::Ticks, interval: ticks::Ticks },
+    }
+
+    /// WEIGHT is the weight given to each new sample of the queue depth in the
+    /// exponentially weighted moving average used by RED.
+    pub const WEIGHT: f64 = 0.002;
+
+    struct Entry<T> {
+        payload:    T,
+        events:     throttle::Events,
+        arrival:    ticks::Ticks,
+    }
+
+    /// Shaper is a bounded queue of emissions with payloads of type T in front
+    /// of a throttle of type H, which by default is any throttle that is Send.
+    /// Naming a concrete throttle type instead lets the owner of the shaper
+    /// reach its inherent methods, for example to reconfigure it in service.
+    pub struct Shaper<T, H: ?Sized = throttle::Throttle + marker::Send> {
+        throttle:   Box<H>,                     // Throttle for the output.
+        policy:     Policy,                     // Congestion policy.
+        capacity:   throttle::Events,           // Maximum backlog in events.
+        queue:      collections::VecDeque<Entry<T>>, // Emissions waiting.
+        backlog:    throttle::Events,           // Events in the queue.
+        dropped:    u64,                        // Events dropped.
+        drops:      u64,                        // Emissions dropped.
+        average:    f64,                        // RED: average queue depth.
+        count:      i64,                        // RED: arrivals since drop.
+        random:     random::Random,             // RED: random state.
+        above:      ticks::Ticks,               // CoDel: first above time.
+        next:       ticks::Ticks,               // CoDel: drop next time.
+        dropping:   bool,                       // CoDel: in dropping state.
+        drops1:     i64,                        // CoDel: drops this state.
+        drops2:     i64,                        // CoDel: drops last state.
+    }
+
+    impl<T, H: throttle::Throttle + ?Sized> string::ToString for Shaper<T, H> {
+
+        fn to_string(& self) -> string::String {
+            format!("Shaper@{:p}:{{p:{:?},c:{},q:{},b:{},d:{},n:{},t:{}}}",
+                self,
+                self.policy, self.capacity, self.queue.len(), self.backlog,
+                self.dropped, self.drops,
+                self.throttle.as_string())
+        }
+
+    }
+
+    impl<T, H: throttle::Throttle + ?Sized> Shaper<T, H> {
+
+        /// Allocate a new Shaper object given its output throttle, the capacity
+        /// of its queue in events, and its congestion policy. The throttle must
+        /// be Send if the shaper is to be shared between the threads that
+        /// offer and poll emissions.
+        pub fn new(throttle: Box<H>, capacity: throttle::Events, policy: Policy) -> Shaper<T, H> {
+            Shaper {
+                throttle,
+                policy,
+                capacity,
+                queue:      collections::VecDeque::new(),
+                backlog:    0,
+                dropped:    0,
+                drops:      0,
+                average:    0.0,
+                count:      -1,
+                random:     random::Random::new(0x2545_f491_4f6c_dd1d),
+                above:      0,
+                next:       0,
+                dropping:   false,
+                drops1:     0,
+                drops2:     0,
+            }
+        }
+
+        /***********************************************************************
+         * GETTERS
+         **********************************************************************/
+
+        /// throttle returns a reference to the output throttle.
+        pub fn throttle(& self) -> & H {
+            &self.throttle
+        }
+
+        /// throttle_mut returns a mutable reference to the output throttle.
+        pub fn throttle_mut(& mut self) -> & mut H {
+            & mut self.throttle
+        }
+
+        /// len returns the number of emissions in the queue.
+        pub fn len(& self) -> usize {
+            self.queue.len()
+        }
+
+        /// is_empty returns true if the queue is empty.
+        pub fn is_empty(& self) -> bool {
+            self.queue.is_empty()
+        }
+
+        /// backlog returns the number of events in the queue.
+        pub fn backlog(& self) -> throttle::Events {
+            self.backlog
+        }
+
+        /// dropped returns the number of events dropped so far.
+        pub fn dropped(& self) -> u64 {
+            self.dropped
+        }
+
+        /// drops returns the number of emissions dropped so far.
+        pub fn drops(& self) -> u64 {
+            self.drops
+        }
+
+        /***********************************************************************
+         * MUTATORS
+         **********************************************************************/
+
+        fn discard(& mut self, events: throttle::Events) {
+            self.dropped += events as u64;
+            self.drops += 1;
+        }
+
+        fn early(& mut self, events: throttle::Events) -> bool {
+            let mut drop: bool = false;
+
+            if let Policy::Red { minimum, maximum, probability } = self.policy {
+                self.average += WEIGHT * ((self.backlog as f64) - self.average);
+                if self.average < (minimum as f64) {
+                    self.count = -1;
+                } else if self.average >= (maximum as f64) {
+                    self.count = 0;
+                    drop = true;
+                } else {
+                    self.count += 1;
+                    let pb: f64 = probability * (self.average - (minimum as f64)) / ((maximum - minimum) as f64);
+                    let mut pa: f64 = 1.0;
+                    if ((self.count as f64) * pb) < 1.0 {
+                        pa = pb / (1.0 - ((self.count as f64) * pb));
+                    }
+                    if self.random.uniform() < pa {
+                        self.count = 0;
+                        drop = true;
+                    }
+                }
+            }
+
+            if drop {
+                self.discard(events);
+            }
+
+            drop
+        }
+
+        /// offer an emission of the specified number of events with its payload
+        /// to the shaper given the current time in ticks. Returns true if the
+        /// emission was queued, false if it was dropped.
+        pub fn offer(& mut self, payload: T, events: throttle::Events, now: ticks::Ticks) -> bool {
+
+            if events > self.capacity {
+                self.discard(events);
+                return false;
+            }
+
+            if self.early(events) {
+                return false;
+            }
+
+            if (self.backlog + events) <= self.capacity {
+                // Do nothing.
+            } else if self.policy == Policy::HeadDrop {
+                while (self.backlog + events) > self.capacity {
+                    let entry: Entry<T> = self.queue.pop_front().unwrap();
+                    self.backlog -= entry.events;
+                    self.discard(entry.events);
+                }
+            } else {
+                self.discard(events);
+                return false;
+            }
+
+            self.backlog += events;
+            self.queue.push_back(Entry { payload, events, arrival: now });
+
+            true
+        }
+
+        fn dequeue(& mut self, now: ticks::Ticks) -> (option::Option<Entry<T>>, bool) {
+            let mut permitted: bool = false;
+
+            let entry: Entry<T> = match self.queue.pop_front() {
+                Some(entry) => entry,
+                None => { self.above = 0; return (None, false); },
+            };
+            self.backlog -= entry.events;
+
+            if let Policy::Codel { target, interval } = self.policy {
+                if (now - entry.arrival) < target {
+                    self.above = 0;
+                } else if self.above == 0 {
+                    self.above = now + interval;
+                } else if now >= self.above {
+                    permitted = true;
+                } else {
+                    // Do nothing.
+                }
+            }
+
+            (Some(entry), permitted)
+        }
+
+        fn law(& self, then: ticks::Ticks, interval: ticks::Ticks) -> ticks::Ticks {
+            then + (((interval as f64) / (self.drops1 as f64).sqrt()) as ticks::Ticks)
+        }
+
+        /// request computes, given the current time in ticks, how long of a delay
+        /// in ticks would be necessary before the emission at the head of the queue
+        /// could be polled, or None if the queue is empty.
+        pub fn request(& self, now: ticks::Ticks) -> option::Option<ticks::Ticks> {
+            if self.queue.is_empty() {
+                None
+            } else {
+                Some(self.throttle.peek(now, 1))
+            }
+        }
+
+        /// poll removes the emission at the head of the queue if the throttle
+        /// permits it to be emitted at the current time in ticks, commits it to
+        /// the throttle, and returns its payload and number of events. None is
+        /// returned if nothing may be emitted yet, or if the active queue
+        /// management policy dropped everything that was waiting.
+        pub fn poll(& mut self, now: ticks::Ticks) -> option::Option<(T, throttle::Events)> {
+            let mut pair: (option::Option<Entry<T>>, bool);
+
+            match self.request(now) {
+                Some(0) => { },
+                _ => { return None; },
+            }
+
+            pair = self.dequeue(now);
+
+            if let Policy::Codel { interval, .. } = self.policy {
+                if self.dropping {
+                    if !pair.1 {
+                        self.dropping = false;
+                    }
+                    while self.dropping && (now >= self.next) {
+                        if let Some(entry) = pair.0.take() { self.discard(entry.events); }
+                        self.drops1 += 1;
+                        pair = self.dequeue(now);
+                        if !pair.1 {
+                            self.dropping = false;
+                        } else {
+                            self.next = self.law(self.next, interval);
+                        }
+                    }
+                } else if pair.1 {
+                    if let Some(entry) = pair.0.take() { self.discard(entry.events); }
+                    pair = self.dequeue(now);
+                    self.dropping = true;
+                    let delta: i64 = self.drops1 - self.drops2;
+                    if (delta > 1) && ((now - self.next) < (16 * interval)) {
+                        self.drops1 = delta;
+                    } else {
+                        self.drops1 = 1;
+                    }
+                    self.next = self.law(now, interval);
+                    self.drops2 = self.drops1;
+                } else {
+                    // Do nothing.
+                }
+            }
+
+            match pair.0 {
+                Some(entry) => {
+                    self.throttle.admits(now, entry.events);
+                    Some((entry.payload, entry.events))
+                },
+                None => None,
+            }
+        }
+
+    }
+
+}
@@ -0,0 +1,345 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a throttle that resides in memory shared by multiple processes
+/// on the same host, so that all of them police or shape against one traffic
+/// contract instead of each having a contract of its own. Because a Gcra or a
+/// Contract contains no pointers or heap data, it can be placed as is in a file
+/// mapped into the address space of each process. Use a file in /dev/shm for a
+/// POSIX shared memory segment, or any other file to have the state persist.
+/// Each access to the throttle is serialized by a spin lock that resides in the
+/// same mapping. Since the timestamps in the throttle are compared between
+/// processes, all of them must use the same clock, for example ticks::monotonic
+/// rather than ticks::now whose epoch differs from process to process.
+///
+/// The mapping is tagged with the layout version of the segment, the size of
+/// the throttle type, and the explicit tag the throttle type declares by
+/// implementing the Tag trait, and a process that opens it with a different
+/// one gets an error instead of reinterpreting another type's state. Gcra,
+/// PoliceGcra, Contract, and Bucket are repr(C), so their layout is the same
+/// for every build for the same target, and their tags are defined here.
+///
+/// A request followed by a commits are two separate accesses, between which
+/// another process may update the throttle. Use admits, or use the with method
+/// to perform several operations while holding the lock, when that matters.
+/// A function passed to the with method that panics releases the lock as it
+/// unwinds, but a process that dies while holding the lock leaves it held, so
+/// those functions should be brief.
+///
+pub mod shared {
+
+    use std::fs;
+    use std::io;
+    use std::marker;
+    use std::mem;
+    use std::path;
+    use std::ptr;
+    use std::string;
+    use std::sync::atomic;
+    use std::os::unix::io::AsRawFd;
+    use libc;
+    use ticks::ticks;
+    use throttle::throttle;
+    use gcra::gcra;
+    use contract::contract;
+    use bucket::bucket;
+
+    /// MAGIC marks a mapping whose throttle has been initialized.
+    pub const MAGIC: u64 = 0x5275_7374_6c65_7221; // "Rustler!"
+
+    /// VERSION is the layout version of the mapping.
+    pub const VERSION: u64 = 3;
+
+    /// Tag is implemented by each throttle type that may reside in a mapping.
+    pub trait Tag {
+
+        /// TAG identifies the throttle type and its layout in a mapping. It
+        /// must differ from that of every other type, and change whenever the
+        /// layout of the type changes.
+        const TAG: u64;
+
+    }
+
+    impl Tag for gcra::Gcra {
+        const TAG: u64 = 0x4763_7261_0000_0001; // "Gcra" 1
+    }
+
+    impl Tag for gcra::PoliceGcra {
+        const TAG: u64 = 0x506f_6c69_0000_0001; // "Poli" 1
+    }
+
+    impl Tag for contract::Contract {
+        const TAG: u64 = 0x436f_6e74_0000_0001; // "Cont" 1
+    }
+
+    impl Tag for bucket::Bucket {
+        const TAG: u64 = 0x4275_636b_0000_0001; // "Buck" 1
+    }
+
+    #[repr(C)]
+    struct Segment<T> {
+        lock:       atomic::AtomicUsize,    // Zero when unlocked.
+        magic:      u64,                    // MAGIC when initialized.
+        version:    u64,                    // VERSION of the layout.
+        size:       u64,                    // Size of the throttle.
+        tag:        u64,                    // Tag of the throttle type.
+        throttle:   T,                      // Shared throttle.
+    }
+
+    /// Guard holds the lock of a mapping, and releases it when dropped, even
+    /// when dropped as a panic unwinds.
+    struct Guard<'a, T: 'a + throttle::Throttle + marker::Copy + Tag> {
+        shared:     &'a Shared<T>,          // Mapping whose lock is held.
+    }
+
+    impl<'a, T: 'a + throttle::Throttle + marker::Copy + Tag> Drop for Guard<'a, T> {
+
+        fn drop(& mut self) {
+            self.shared.unlock();
+        }
+
+    }
+
+    pub struct Shared<T: throttle::Throttle + marker::Copy + Tag> {
+        segment:    * mut Segment<T>,
+        length:     usize,
+    }
+
+    /// The mapping may be used by any thread since every access to it is
+    /// serialized by the lock within it.
+    unsafe impl<T: throttle::Throttle + marker::Copy + Tag + marker::Send> marker::Send for Shared<T> { }
+
+    impl<T: throttle::Throttle + marker::Copy + Tag> Drop for Shared<T> {
+
+        fn drop(& mut self) {
+            unsafe {
+                libc::munmap(self.segment as * mut libc::c_void, self.length);
+            }
+        }
+
+    }
+
+    impl<T: throttle::Throttle + marker::Copy + Tag> string::ToString for Shared<T> {
+
+        fn to_string(& self) -> string::String {
+            format!("Shared@{:p}[{}]:{{s:{:p},t:{}}}",
+                self, self.length,
+                self.segment,
+                self.with(|throttle| { throttle.as_string() }))
+        }
+
+    }
+
+    impl<T: throttle::Throttle + marker::Copy + Tag> Shared<T> {
+
+        /// Open a shared throttle residing in the file at the specified path,
+        /// creating the file if necessary. If no process has yet initialized the
+        /// throttle in the file, it is initialized with a copy of the specified
+        /// throttle; otherwise the specified throttle is ignored. Every process
+        /// sharing the file must use the same throttle type, or an error is
+        /// returned.
+        pub fn open(path: & path::Path, throttle: T) -> io::Result<Shared<T>> {
+            let length: usize = mem::size_of::<Segment<T>>();
+            let file: fs::File = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+            let segment: * mut Segment<T>;
+
+            if file.metadata()?.len() < (length as u64) {
+                file.set_len(length as u64)?;
+            }
+
+            unsafe {
+                let address = libc::mmap(ptr::null_mut(), length, libc::PROT_READ | libc::PROT_WRITE, libc::MAP_SHARED, file.as_raw_fd(), 0);
+                if address == libc::MAP_FAILED {
+                    return Err(io::Error::last_os_error());
+                }
+                segment = address as * mut Segment<T>;
+            }
+
+            let that: Shared<T> = Shared { segment, length };
+
+            {
+                let _guard: Guard<'_, T> = that.lock();
+                unsafe {
+                    if (*segment).magic != MAGIC {
+                        ptr::write(& mut (*segment).throttle, throttle);
+                        (*segment).version = VERSION;
+                        (*segment).size = mem::size_of::<T>() as u64;
+                        (*segment).tag = T::TAG;
+                        (*segment).magic = MAGIC;
+                    }
+                }
+            }
+
+            unsafe {
+                if (*that.segment).version != VERSION {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "throttle version mismatch"));
+                }
+                if (*that.segment).size != (mem::size_of::<T>() as u64) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "throttle size mismatch"));
+                }
+                if (*that.segment).tag != T::TAG {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "throttle type mismatch"));
+                }
+            }
+
+            Ok(that)
+        }
+
+        /// lock acquires the lock of the mapping and returns the guard that
+        /// releases it.
+        fn lock(& self) -> Guard<'_, T> {
+            let lock: & atomic::AtomicUsize = unsafe { & (*self.segment).lock };
+
+            while lock.compare_exchange_weak(0, 1, atomic::Ordering::Acquire, atomic::Ordering::Relaxed).is_err() {
+                ticks::sleep(0);
+            }
+
+            Guard { shared: self }
+        }
+
+        fn unlock(& self) {
+            let lock: & atomic::AtomicUsize = unsafe { & (*self.segment).lock };
+
+            lock.store(0, atomic::Ordering::Release);
+        }
+
+        /// with calls the specified function with exclusive access to the shared
+        /// throttle and returns whatever the function returns. The lock is
+        /// released even if the function panics.
+        pub fn with<R, F: FnOnce(& mut T) -> R>(& self, function: F) -> R {
+            let _guard: Guard<'_, T> = self.lock();
+
+            function(unsafe { & mut (*self.segment).throttle })
+        }
+
+        /// get returns a copy of the shared throttle.
+        pub fn get(& self) -> T {
+            self.with(|throttle| { *throttle })
+        }
+
+    }
+
+    impl<T: throttle::Throttle + marker::Copy + Tag> throttle::Throttle for Shared<T> {
+
+        /***************************************************************************
+         * INSPECTORS
+         **************************************************************************/
+
+        /// size_of returns the size of the shared throttle in bytes.
+        fn size_of(& self) -> usize {
+            mem::size_of::<T>()
+        }
+
+        /***************************************************************************
+         * CONVERTORS
+         **************************************************************************/
+
+        /// as_string returns the underlying object as a printable string.
+        fn as_string(& self) -> string::String {
+            string::ToString::to_string(self)
+        }
+
+        /***************************************************************************
+         * SETTERS
+         **************************************************************************/
+
+        /// reset the shared throttle back to its initial state.
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.with(|throttle| { throttle.reset(now) })
+        }
+
+        /***************************************************************************
+         * GETTERS
+         **************************************************************************/
+
+        /// get_expected returns the expected ticks of the shared throttle.
+        fn get_expected(& self) -> ticks::Ticks {
+            self.with(|throttle| { throttle.get_expected() })
+        }
+
+        /// peek computes the delay of the shared throttle without altering it.
+        fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+            self.with(|throttle| { throttle.peek(now, events) })
+        }
+
+        /// is_empty returns true if the shared throttle is empty.
+        fn is_empty(& self) -> bool {
+            self.with(|throttle| { throttle.is_empty() })
+        }
+
+        /// is_full returns true if the shared throttle is full.
+        fn is_full(& self) -> bool {
+            self.with(|throttle| { throttle.is_full() })
+        }
+
+        /// is_alarmed returns true if the shared throttle is alarmed.
+        fn is_alarmed(& self) -> bool {
+            self.with(|throttle| { throttle.is_alarmed() })
+        }
+
+        /***************************************************************************
+         * SENSORS
+         **************************************************************************/
+
+        /// emptied returns true if the shared throttle just emptied.
+        fn emptied(& self) -> bool {
+            self.with(|throttle| { throttle.emptied() })
+        }
+
+        /// filled returns true if the shared throttle just filled.
+        fn filled(& self) -> bool {
+            self.with(|throttle| { throttle.filled() })
+        }
+
+        /// alarmed returns true if the shared throttle just alarmed.
+        fn alarmed(& self) -> bool {
+            self.with(|throttle| { throttle.alarmed() })
+        }
+
+        /// cleared returns true if the shared throttle just unalarmed.
+        fn cleared(& self) -> bool {
+            self.with(|throttle| { throttle.cleared() })
+        }
+
+        /***************************************************************************
+         * MUTATORS
+         **************************************************************************/
+
+        /// request computes the delay of the shared throttle.
+        fn request(& mut self, now: ticks::Ticks) -> ticks::Ticks {
+            self.with(|throttle| { throttle.request(now) })
+        }
+
+        /// commits updates the shared throttle with the number of events.
+        fn commits(& mut self, events: throttle::Events) -> bool {
+            self.with(|throttle| { throttle.commits(events) })
+        }
+
+        /// commit is equivalent to calling commits with one event.
+        fn commit(& mut self) -> bool {
+            self.with(|throttle| { throttle.commit() })
+        }
+
+        /// admits atomically requests and commits on the shared throttle.
+        fn admits(& mut self, now: ticks::Ticks, events: throttle::Events) -> bool {
+            self.with(|throttle| { throttle.admits(now, events) })
+        }
+
+        /// admit is equivalent to calling admits with one event.
+        fn admit(& mut self, now: ticks::Ticks) -> bool {
+            self.with(|throttle| { throttle.admit(now) })
+        }
+
+        /// update is equivalent to calling admits with zero events.
+        fn update(& mut self, now: ticks::Ticks) -> bool {
+            self.with(|throttle| { throttle.update(now) })
+        }
+
+    }
+
+}
@@ -0,0 +1,445 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a discrete-event simulator in which sources, shapers, policers,
+/// links, and sinks are wired together as a graph and advanced on a virtual
+/// clock, so that traffic contracts can be evaluated offline without ever
+/// sleeping. Each node sends what it emits to at most one other node, but any
+/// number of nodes may send to the same node. A source emits the schedule of a
+/// trafgen::Generator. A shaper is a shaper::Shaper, a bounded queue in front
+/// of a throttle with its congestion policy. A policer admits what conforms to
+/// its throttle and drops the rest. A link serializes what it is sent at a
+/// fixed rate in events per second, queueing without bound while it is busy,
+/// and delivers each emission after a fixed latency. A sink consumes
+/// everything it is sent. Each node keeps statistics of what arrived at it,
+/// what departed from it, what it dropped, and how long emissions spent in it;
+/// the delay recorded by a sink is instead the end-to-end delay since the
+/// emission left its source. The calendar is a priority queue keyed on the
+/// virtual time of each pending action and a sequence number, so that actions
+/// scheduled for the same time are performed in the order in which they were
+/// scheduled, and the simulation is entirely deterministic.
+///
+pub mod simulator {
+
+    use std::cmp;
+    use std::collections;
+    use std::marker;
+    use std::option;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+    use shaper::shaper;
+    use trafgen::trafgen;
+
+    /// Node is the type used to identify a node within a simulator.
+    pub type Node = usize;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Packet {
+        events:     throttle::Events,           // Size of the emission.
+        created:    ticks::Ticks,               // Time it left its source.
+        arrived:    ticks::Ticks,               // Time it arrived at this node.
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    enum Action {
+        Emit(Node),                             // Source emits.
+        Arrive(Node, Packet),                   // Packet arrives at node.
+        Poll(Node),                             // Shaper may emit.
+        Deliver(Node, Packet),                  // Link finishes a packet.
+    }
+
+    enum Kind {
+        Source { generator: trafgen::Generator, pending: option::Option<throttle::Events>, limit: ticks::Ticks },
+        Shaper { shaper: shaper::Shaper<Packet>, scheduled: bool },
+        Policer { throttle: Box<throttle::Throttle> },
+        Link { rate: f64, latency: ticks::Ticks, busy: ticks::Ticks },
+        Sink,
+    }
+
+    /// Statistics records what has happened at a node.
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct Statistics {
+        pub arrivals:   u64,                    // Emissions arrived.
+        pub received:   u64,                    // Events arrived.
+        pub departures: u64,                    // Emissions departed.
+        pub sent:       u64,                    // Events departed.
+        pub drops:      u64,                    // Emissions dropped.
+        pub dropped:    u64,                    // Events dropped.
+        pub delay:      ticks::Ticks,           // Total delay of departures.
+        pub maximum:    ticks::Ticks,           // Maximum delay of departures.
+    }
+
+    impl Statistics {
+
+        /// throughput returns the mean rate in events per second at which
+        /// events departed from the node over the specified duration in ticks.
+        pub fn throughput(& self, duration: ticks::Ticks) -> f64 {
+            if duration <= 0 {
+                0.0
+            } else {
+                (self.sent as f64) * (ticks::frequency() as f64) / (duration as f64)
+            }
+        }
+
+        /// mean returns the mean delay in ticks of the emissions that departed
+        /// from the node.
+        pub fn mean(& self) -> f64 {
+            if self.departures == 0 {
+                0.0
+            } else {
+                (self.delay as f64) / (self.departures as f64)
+            }
+        }
+
+    }
+
+    struct Vertex {
+        kind:       Kind,                       // What the node does.
+        output:     option::Option<Node>,       // Where departures go.
+        statistics: Statistics,                 // What has happened.
+    }
+
+    pub struct Simulator {
+        vertices:   Vec<Vertex>,
+        calendar:   collections::BinaryHeap<cmp::Reverse<(ticks::Ticks, u64, Action)>>,
+        start:      ticks::Ticks,
+        now:        ticks::Ticks,
+        sequence:   u64,
+    }
+
+    impl string::ToString for Simulator {
+
+        fn to_string(& self) -> string::String {
+            format!("Simulator@{:p}:{{n:{},c:{},b:{},t:{},s:{}}}",
+                self,
+                self.vertices.len(), self.calendar.len(),
+                self.start, self.now, self.sequence)
+        }
+
+    }
+
+    impl Simulator {
+
+        /// Allocate a new Simulator object whose virtual clock starts at the
+        /// specified time in ticks. This should be the time with which the
+        /// throttles given to the simulator were initialized.
+        pub fn new(now: ticks::Ticks) -> Simulator {
+            Simulator {
+                vertices:   Vec::new(),
+                calendar:   collections::BinaryHeap::new(),
+                start:      now,
+                now,
+                sequence:   0,
+            }
+        }
+
+        fn add(& mut self, kind: Kind) -> Node {
+            self.vertices.push(Vertex { kind, output: None, statistics: Statistics::default() });
+
+            self.vertices.len() - 1
+        }
+
+        fn schedule(& mut self, when: ticks::Ticks, action: Action) {
+            self.calendar.push(cmp::Reverse((when, self.sequence, action)));
+            self.sequence += 1;
+        }
+
+        /***********************************************************************
+         * BUILDERS
+         **********************************************************************/
+
+        /// add_source adds a node that emits the schedule of the generator,
+        /// whose offsets are relative to the start of the simulation, until
+        /// the generator is exhausted or its offsets exceed the limit in ticks.
+        pub fn add_source(& mut self, generator: trafgen::Generator, limit: ticks::Ticks) -> Node {
+            let node: Node = self.add(Kind::Source { generator, pending: None, limit });
+
+            self.next(node);
+
+            node
+        }
+
+        /// add_shaper adds a node that queues what it is sent in a shaper with
+        /// the specified throttle, capacity in events, and congestion policy.
+        pub fn add_shaper(& mut self, throttle: Box<throttle::Throttle + marker::Send>, capacity: throttle::Events, policy: shaper::Policy) -> Node {
+            self.add(Kind::Shaper { shaper: shaper::Shaper::new(throttle, capacity, policy), scheduled: false })
+        }
+
+        /// add_policer adds a node that forwards what conforms to the specified
+        /// throttle and drops what does not.
+        pub fn add_policer(& mut self, throttle: Box<throttle::Throttle>) -> Node {
+            self.add(Kind::Policer { throttle })
+        }
+
+        /// add_link adds a node that serializes what it is sent at the
+        /// specified rate in events per second and delivers it after the
+        /// specified latency in ticks.
+        pub fn add_link(& mut self, rate: f64, latency: ticks::Ticks) -> Node {
+            assert!((rate > 0.0) && (latency >= 0));
+
+            self.add(Kind::Link { rate, latency, busy: 0 })
+        }
+
+        /// add_sink adds a node that consumes whatever it is sent.
+        pub fn add_sink(& mut self) -> Node {
+            self.add(Kind::Sink)
+        }
+
+        /// connect sends whatever departs from the first node to the second.
+        /// A node that is not connected to anything discards its departures.
+        pub fn connect(& mut self, from: Node, to: Node) {
+            assert!((from < self.vertices.len()) && (to < self.vertices.len()));
+
+            self.vertices[from].output = Some(to);
+        }
+
+        /***********************************************************************
+         * GETTERS
+         **********************************************************************/
+
+        /// now returns the current time in ticks on the virtual clock.
+        pub fn now(& self) -> ticks::Ticks {
+            self.now
+        }
+
+        /// elapsed returns the number of ticks simulated so far.
+        pub fn elapsed(& self) -> ticks::Ticks {
+            self.now - self.start
+        }
+
+        /// nodes returns the number of nodes in the simulator.
+        pub fn nodes(& self) -> usize {
+            self.vertices.len()
+        }
+
+        /// is_idle returns true if nothing remains to be simulated.
+        pub fn is_idle(& self) -> bool {
+            self.calendar.is_empty()
+        }
+
+        /// statistics returns the statistics of the specified node.
+        pub fn statistics(& self, node: Node) -> & Statistics {
+            &self.vertices[node].statistics
+        }
+
+        /// throttle returns the throttle of a shaper or policer node, or None
+        /// for any other kind of node.
+        pub fn throttle(& self, node: Node) -> option::Option<& throttle::Throttle> {
+            match self.vertices[node].kind {
+                Kind::Shaper { ref shaper, .. } => Some(shaper.throttle()),
+                Kind::Policer { ref throttle } => Some(&**throttle),
+                _ => None,
+            }
+        }
+
+        /***********************************************************************
+         * ACTIONS
+         **********************************************************************/
+
+        fn next(& mut self, node: Node) {
+            let emission: option::Option<(ticks::Ticks, throttle::Events)> = match self.vertices[node].kind {
+                Kind::Source { ref mut generator, ref mut pending, limit } => {
+                    match generator.next() {
+                        Some((offset, events)) if offset <= limit => { *pending = Some(events); Some((offset, events)) },
+                        _ => { *pending = None; None },
+                    }
+                },
+                _ => None,
+            };
+
+            if let Some((offset, _)) = emission {
+                let when: ticks::Ticks = self.start + offset;
+                self.schedule(when, Action::Emit(node));
+            }
+        }
+
+        fn depart(& mut self, node: Node, packet: Packet) {
+            let now: ticks::Ticks = self.now;
+            let delay: ticks::Ticks = now - packet.arrived;
+
+            {
+                let statistics: & mut Statistics = & mut self.vertices[node].statistics;
+                statistics.departures += 1;
+                statistics.sent += packet.events as u64;
+                statistics.delay += delay;
+                if delay > statistics.maximum { statistics.maximum = delay; }
+            }
+
+            if let Some(output) = self.vertices[node].output {
+                self.schedule(now, Action::Arrive(output, Packet { arrived: now, .. packet }));
+            }
+        }
+
+        fn discard(& mut self, node: Node, drops: u64, dropped: u64) {
+            let statistics: & mut Statistics = & mut self.vertices[node].statistics;
+            statistics.drops += drops;
+            statistics.dropped += dropped;
+        }
+
+        fn emit(& mut self, node: Node) {
+            let now: ticks::Ticks = self.now;
+
+            let pending: option::Option<throttle::Events> = match self.vertices[node].kind {
+                Kind::Source { pending, .. } => pending,
+                _ => None,
+            };
+
+            if let Some(events) = pending {
+                self.depart(node, Packet { events, created: now, arrived: now });
+            }
+
+            self.next(node);
+        }
+
+        fn arrive(& mut self, node: Node, packet: Packet) {
+            let now: ticks::Ticks = self.now;
+            let mut poll: option::Option<ticks::Ticks> = None;
+            let mut forward: bool = false;
+            let mut deliver: option::Option<ticks::Ticks> = None;
+            let mut loss: (u64, u64) = (0, 0);
+
+            {
+                let statistics: & mut Statistics = & mut self.vertices[node].statistics;
+                statistics.arrivals += 1;
+                statistics.received += packet.events as u64;
+            }
+
+            match self.vertices[node].kind {
+                Kind::Source { .. } => {
+                    forward = true;
+                },
+                Kind::Shaper { ref mut shaper, ref mut scheduled } => {
+                    let drops: u64 = shaper.drops();
+                    let dropped: u64 = shaper.dropped();
+                    shaper.offer(packet, packet.events, now);
+                    loss = (shaper.drops() - drops, shaper.dropped() - dropped);
+                    if *scheduled {
+                        // Do nothing.
+                    } else if let Some(delay) = shaper.request(now) {
+                        *scheduled = true;
+                        poll = Some(now + delay);
+                    } else {
+                        // Do nothing.
+                    }
+                },
+                Kind::Policer { ref mut throttle } => {
+                    if throttle.admits(now, packet.events) {
+                        forward = true;
+                    } else {
+                        loss = (1, packet.events as u64);
+                    }
+                },
+                Kind::Link { rate, latency, ref mut busy } => {
+                    let serialization: ticks::Ticks = ((packet.events as f64) * (ticks::frequency() as f64) / rate) as ticks::Ticks;
+                    *busy = cmp::max(*busy, now) + serialization;
+                    deliver = Some(*busy + latency);
+                },
+                Kind::Sink => {
+                    let statistics: & mut Statistics = & mut self.vertices[node].statistics;
+                    let delay: ticks::Ticks = now - packet.created;
+                    statistics.departures += 1;
+                    statistics.sent += packet.events as u64;
+                    statistics.delay += delay;
+                    if delay > statistics.maximum { statistics.maximum = delay; }
+                },
+            }
+
+            self.discard(node, loss.0, loss.1);
+
+            if forward {
+                self.depart(node, packet);
+            }
+
+            if let Some(when) = poll {
+                self.schedule(when, Action::Poll(node));
+            }
+
+            if let Some(when) = deliver {
+                self.schedule(when, Action::Deliver(node, packet));
+            }
+        }
+
+        fn poll(& mut self, node: Node) {
+            let now: ticks::Ticks = self.now;
+            let mut departures: Vec<Packet> = Vec::new();
+            let mut poll: option::Option<ticks::Ticks> = None;
+            let mut loss: (u64, u64) = (0, 0);
+
+            if let Kind::Shaper { ref mut shaper, ref mut scheduled } = self.vertices[node].kind {
+                let drops: u64 = shaper.drops();
+                let dropped: u64 = shaper.dropped();
+                while let Some((packet, _)) = shaper.poll(now) {
+                    departures.push(packet);
+                }
+                loss = (shaper.drops() - drops, shaper.dropped() - dropped);
+                match shaper.request(now) {
+                    Some(delay) => { poll = Some(now + delay); },
+                    None => { *scheduled = false; },
+                }
+            }
+
+            self.discard(node, loss.0, loss.1);
+
+            for packet in departures {
+                self.depart(node, packet);
+            }
+
+            if let Some(when) = poll {
+                self.schedule(when, Action::Poll(node));
+            }
+        }
+
+        /***********************************************************************
+         * SIMULATION
+         **********************************************************************/
+
+        /// step performs the earliest pending action, advancing the virtual
+        /// clock to its time, provided that time is no later than the specified
+        /// time in ticks. Returns true if an action was performed.
+        pub fn step(& mut self, until: ticks::Ticks) -> bool {
+            let (when, _, action) = match self.calendar.peek() {
+                Some(&cmp::Reverse(entry)) if entry.0 <= until => entry,
+                _ => { return false; },
+            };
+            self.calendar.pop();
+
+            if when > self.now {
+                self.now = when;
+            }
+
+            match action {
+                Action::Emit(node) => { self.emit(node); },
+                Action::Arrive(node, packet) => { self.arrive(node, packet); },
+                Action::Poll(node) => { self.poll(node); },
+                Action::Deliver(node, packet) => { self.depart(node, packet); },
+            }
+
+            true
+        }
+
+        /// run performs every pending action up to and including the
+        /// specified time in ticks, then advances the virtual clock to that
+        /// time. Returns the number of actions performed.
+        pub fn run(& mut self, until: ticks::Ticks) -> u64 {
+            let mut count: u64 = 0;
+
+            while self.step(until) {
+                count += 1;
+            }
+
+            if until > self.now {
+                self.now = until;
+            }
+
+            count
+        }
+
+    }
+
+}
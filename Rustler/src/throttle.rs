@@ -11,12 +11,20 @@
 /// police event admission rates. Frequently, throttles are implemented using
 /// a virtual scheduler or a leaky bucket.
 ///
+/// The required methods take and return bare ticks::Ticks and Events. Each
+/// method that takes or returns a time or a count has a counterpart whose name
+/// ends in _typed, provided by the trait in terms of the required method, that
+/// takes and returns the strongly typed units::Ticks and units::Events. An
+/// implementation provides only the required methods, so both forms always
+/// agree.
+///
 /// This module is based on the Go implementation in the Vamoose repository.
 ///
 pub mod throttle {
 
     use std::string;
     use ticks::ticks;
+    use units::units;
  
     /// Events is the type used to indicate how many events have been emitted since
     /// the last update of the throttle. An event can be the emission of a single
@@ -96,6 +104,19 @@ pub mod throttle {
         /// Algorithm, the value returned may be the same as that returned by Request
         /// given the current state of the throttle, or some other value entirely.
         fn get_expected(& self) -> ticks::Ticks;
+
+        /// peek computes, given the current time in ticks, how long of a delay in
+        /// ticks would be necessary before the last of the specified number of events,
+        /// each emitted as soon as the traffic contract permits, could itself be
+        /// emitted in compliance with the traffic contract. For zero or one event
+        /// this is the same value that request would return, but unlike
+        /// request, peek does not alter the state of the throttle, so it can be used
+        /// to compare the eligibility of many throttles without side effects.
+        /// peek has no default implementation, because none can be derived
+        /// from the other methods without altering the state of the throttle,
+        /// so adding it was a breaking change: implementations of Throttle
+        /// outside of this crate must provide it as of version 4.0.0.
+        fn peek(& self, now: ticks::Ticks, events: Events) -> ticks::Ticks;
         
         /// is_empty returns true if the throttle is empty, that is, it has no accumulated
         /// deficit ticks.
@@ -127,6 +148,50 @@ pub mod throttle {
         /// indicating that the event emission stream has returned to being
         /// compliant with the traffic contract.
         fn cleared(& self) -> bool;
+
+        /***************************************************************************
+         * TYPED
+         **************************************************************************/
+
+        /// reset_typed is reset in typed ticks.
+        fn reset_typed(& mut self, now: units::Ticks) {
+            self.reset(now.0)
+        }
+
+        /// request_typed is request in typed ticks.
+        fn request_typed(& mut self, now: units::Ticks) -> units::Ticks {
+            units::Ticks(self.request(now.0))
+        }
+
+        /// commits_typed is commits in typed events.
+        fn commits_typed(& mut self, events: units::Events) -> bool {
+            self.commits(events.0)
+        }
+
+        /// admits_typed is admits in typed ticks and events.
+        fn admits_typed(& mut self, now: units::Ticks, events: units::Events) -> bool {
+            self.admits(now.0, events.0)
+        }
+
+        /// admit_typed is admit in typed ticks.
+        fn admit_typed(& mut self, now: units::Ticks) -> bool {
+            self.admit(now.0)
+        }
+
+        /// update_typed is update in typed ticks.
+        fn update_typed(& mut self, now: units::Ticks) -> bool {
+            self.update(now.0)
+        }
+
+        /// get_expected_typed is get_expected in typed ticks.
+        fn get_expected_typed(& self) -> units::Ticks {
+            units::Ticks(self.get_expected())
+        }
+
+        /// peek_typed is peek in typed ticks and events.
+        fn peek_typed(& self, now: units::Ticks, events: units::Events) -> units::Ticks {
+            units::Ticks(self.peek(now.0, events.0))
+        }
    
     }
 
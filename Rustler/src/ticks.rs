@@ -14,6 +14,9 @@ pub mod ticks {
     use std::option;
     use std::time;
     use std::thread;
+    use std::hint;
+    use libc;
+    use clock::clock;
 
     /// ticks is a type big enough to contain a monotonic elapsed time value.
     pub type Ticks = i64;
@@ -29,9 +32,39 @@ pub mod ticks {
         FREQUENCY
     }
  
+    /// to_nanoseconds returns the specified Ticks in nanoseconds, saturating at
+    /// the limits of an i64 instead of overflowing.
+    pub fn to_nanoseconds(ticks: Ticks) -> i64 {
+        let nanoseconds: i128 = (ticks as i128) * 1_000_000_000 / (frequency() as i128);
+
+        nanoseconds.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+    }
+
+    /// from_nanoseconds returns the specified nanoseconds in Ticks, saturating
+    /// at the limits of Ticks instead of overflowing.
+    pub fn from_nanoseconds(nanoseconds: i64) -> Ticks {
+        let ticks: i128 = (nanoseconds as i128) * (frequency() as i128) / 1_000_000_000;
+
+        ticks.clamp(Ticks::MIN as i128, Ticks::MAX as i128) as Ticks
+    }
+
     /// now returns the current value of Ticks for the monotonically increasing time
-    /// that is now.
+    /// that is now, read from the clock source chosen by select.
     pub fn now() -> Ticks {
+        clock::read()
+    }
+
+    /// select makes now read the specified clock source, scaled to Ticks, and
+    /// returns the previously selected source, or None if the source is not
+    /// available on this platform. The default is clock::Source::Instant.
+    pub fn select(source: clock::Source) -> Option<clock::Source> {
+        clock::select(source)
+    }
+
+    /// instant returns the current value of Ticks for the monotonically
+    /// increasing time that is now, read from the standard library Instant
+    /// regardless of the selected clock source.
+    pub fn instant() -> Ticks {
         unsafe {
             INIT.call_once(|| { EPOCH = option::Option::Some(time::Instant::now()); } );
             let then: time::Instant = EPOCH.unwrap();
@@ -49,6 +82,25 @@ pub mod ticks {
         }
     }
 
+    /// monotonic returns the current value of Ticks for the monotonically
+    /// increasing time that is now, measured from an epoch (typically boot)
+    /// that is shared by every process on the host, so that values can be
+    /// compared between processes.
+    pub fn monotonic() -> Ticks {
+        let mut timespec: libc::timespec = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        let mut ticks: Ticks;
+
+        unsafe {
+            libc::clock_gettime(libc::CLOCK_MONOTONIC, & mut timespec);
+        }
+
+        ticks = timespec.tv_sec as i64;
+        ticks *= frequency();
+        ticks += timespec.tv_nsec as i64;
+
+        ticks
+    }
+
     /// sleep delays the caller for at least as many ticks as specified. If zero
     /// ticks are specified, the caller yields the processor.
     pub fn sleep(ticks: Ticks) {
@@ -61,4 +113,77 @@ pub mod ticks {
         }
     }
 
+    /// sleep_until delays the caller until at least the specified absolute
+    /// time in ticks, as returned by now. Because the deadline is absolute,
+    /// time lost to scheduling latency or to the caller's own work does not
+    /// accumulate from one sleep to the next as it would with sleep. On Linux
+    /// this uses clock_nanosleep with TIMER_ABSTIME. If the deadline has
+    /// already passed, the caller yields the processor.
+    pub fn sleep_until(deadline: Ticks) {
+        match target(deadline, now(), monotonic()) {
+            Some(target) => absolute(target),
+            None => thread::yield_now(),
+        }
+    }
+
+    /// target returns the time in ticks as returned by monotonic at which the
+    /// specified deadline in ticks as returned by now falls, given the values
+    /// of now and monotonic at the same instant, or None if the deadline has
+    /// already passed.
+    pub fn target(deadline: Ticks, now: Ticks, monotonic: Ticks) -> Option<Ticks> {
+        let delay: Ticks = deadline - now;
+
+        if delay <= 0 {
+            None
+        } else {
+            Some(monotonic + delay)
+        }
+    }
+
+    /// timespec returns the POSIX timespec equivalent to the specified
+    /// non-negative number of ticks.
+    pub fn timespec(ticks: Ticks) -> libc::timespec {
+        libc::timespec { tv_sec: (ticks / frequency()) as libc::time_t, tv_nsec: ((ticks % frequency()) * 1_000_000_000 / frequency()) as libc::c_long }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn absolute(target: Ticks) {
+        use std::ptr;
+        let timespec: libc::timespec = timespec(target);
+        let mut rc: libc::c_int;
+
+        loop {
+            rc = unsafe { libc::clock_nanosleep(libc::CLOCK_MONOTONIC, libc::TIMER_ABSTIME, &timespec, ptr::null_mut()) };
+            if rc != libc::EINTR {
+                break;
+            }
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn absolute(target: Ticks) {
+        sleep(target - monotonic());
+    }
+
+    /// spin_until delays the caller until at least the specified absolute
+    /// time in ticks, as returned by now, by sleeping until the specified
+    /// window before the deadline and then spinning on the processor for the
+    /// remainder. This trades processor time for a wake up that is typically
+    /// late by much less than the scheduling latency of sleep_until. A window
+    /// of zero ticks is the same as sleep_until.
+    pub fn spin_until(deadline: Ticks, window: Ticks) {
+        assert!(window >= 0);
+
+        if window == 0 {
+            sleep_until(deadline);
+        } else {
+            if (deadline - window) > now() {
+                sleep_until(deadline - window);
+            }
+            while now() < deadline {
+                hint::spin_loop();
+            }
+        }
+    }
+
 }
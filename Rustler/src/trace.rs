@@ -0,0 +1,373 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a recorder that wraps any throttle and writes a trace of every
+/// call that alters it, so that the behavior of a traffic contract in
+/// production can be reproduced later, and a function that replays such a
+/// trace through another throttle. The trace is a CSV file with a header line
+/// followed by one line per call giving the method, the time in ticks passed
+/// to (or, for commits and commit, last passed to) the throttle, the number of
+/// events, the result (a delay in ticks for request, one or zero for true or
+/// false for the others), and a snapshot of the state of the throttle after
+/// the call: its expected ticks, and whether it is empty, full, and alarmed.
+///
+pub mod trace {
+
+    use std::io;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+
+    /// HEADER is the first line of a trace.
+    pub const HEADER: &str = "method,now,events,result,expected,empty,full,alarmed";
+
+    /// Method identifies the throttle method that was called.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Method {
+        Reset,
+        Request,
+        Commits,
+        Commit,
+        Admits,
+        Admit,
+        Update,
+    }
+
+    impl Method {
+
+        /// name returns the name of the method as it appears in a trace.
+        pub fn name(& self) -> &'static str {
+            match *self {
+                Method::Reset => "reset",
+                Method::Request => "request",
+                Method::Commits => "commits",
+                Method::Commit => "commit",
+                Method::Admits => "admits",
+                Method::Admit => "admit",
+                Method::Update => "update",
+            }
+        }
+
+        /// parse returns the method having the specified name, if any.
+        pub fn parse(name: & str) -> Option<Method> {
+            match name {
+                "reset" => Some(Method::Reset),
+                "request" => Some(Method::Request),
+                "commits" => Some(Method::Commits),
+                "commit" => Some(Method::Commit),
+                "admits" => Some(Method::Admits),
+                "admit" => Some(Method::Admit),
+                "update" => Some(Method::Update),
+                _ => None,
+            }
+        }
+
+    }
+
+    /// Record is a single call in a trace.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Record {
+        pub method:     Method,                 // Method called.
+        pub now:        ticks::Ticks,           // Time in ticks.
+        pub events:     throttle::Events,       // Events emitted.
+        pub result:     i64,                    // Delay or boolean result.
+        pub expected:   ticks::Ticks,           // Expected ticks after.
+        pub empty:      bool,                   // Empty after.
+        pub full:       bool,                   // Full after.
+        pub alarmed:    bool,                   // Alarmed after.
+    }
+
+    impl string::ToString for Record {
+
+        fn to_string(& self) -> string::String {
+            format!("{},{},{},{},{},{},{},{}",
+                self.method.name(), self.now, self.events, self.result,
+                self.expected, self.empty as i32, self.full as i32, self.alarmed as i32)
+        }
+
+    }
+
+    impl Record {
+
+        /// parse returns the record on a line of a trace, or None if the line
+        /// is the header or is not valid.
+        pub fn parse(line: & str) -> Option<Record> {
+            let fields: Vec<& str> = line.trim().split(',').collect();
+
+            if fields.len() != 8 {
+                return None;
+            }
+
+            Some(Record {
+                method:     Method::parse(fields[0])?,
+                now:        fields[1].parse().ok()?,
+                events:     fields[2].parse().ok()?,
+                result:     fields[3].parse().ok()?,
+                expected:   fields[4].parse().ok()?,
+                empty:      fields[5] == "1",
+                full:       fields[6] == "1",
+                alarmed:    fields[7] == "1",
+            })
+        }
+
+        /// is_decision returns true if the records agree on the result of the
+        /// call, regardless of the state of the throttle.
+        pub fn is_decision(& self, that: & Record) -> bool {
+            (self.method == that.method) && (self.result == that.result)
+        }
+
+    }
+
+    fn record(throttle: & throttle::Throttle, method: Method, now: ticks::Ticks, events: throttle::Events, result: i64) -> Record {
+        Record {
+            method,
+            now,
+            events,
+            result,
+            expected:   throttle.get_expected(),
+            empty:      throttle.is_empty(),
+            full:       throttle.is_full(),
+            alarmed:    throttle.is_alarmed(),
+        }
+    }
+
+    /// replay performs the call in the record on the specified throttle and
+    /// returns the record of the replayed call.
+    pub fn replay(throttle: & mut throttle::Throttle, record: & Record) -> Record {
+        let result: i64 = match record.method {
+            Method::Reset => { throttle.reset(record.now); 0 },
+            Method::Request => throttle.request(record.now),
+            Method::Commits => throttle.commits(record.events) as i64,
+            Method::Commit => throttle.commit() as i64,
+            Method::Admits => throttle.admits(record.now, record.events) as i64,
+            Method::Admit => throttle.admit(record.now) as i64,
+            Method::Update => throttle.update(record.now) as i64,
+        };
+
+        self::record(throttle, record.method, record.now, record.events, result)
+    }
+
+    pub struct Recorder<T: throttle::Throttle, W: io::Write> {
+        throttle:   T,                          // Throttle recorded.
+        output:     W,                          // Trace written.
+        now:        ticks::Ticks,               // Last time passed.
+        failures:   u64,                        // Records not written.
+    }
+
+    impl<T: throttle::Throttle, W: io::Write> string::ToString for Recorder<T, W> {
+
+        fn to_string(& self) -> string::String {
+            format!("Recorder@{:p}:{{n:{},f:{},t:{}}}",
+                self,
+                self.now, self.failures,
+                self.throttle.as_string())
+        }
+
+    }
+
+    impl<T: throttle::Throttle, W: io::Write> Recorder<T, W> {
+
+        /// Allocate a new Recorder object that wraps the specified throttle and
+        /// writes its trace, starting with the header, to the specified output.
+        pub fn new(throttle: T, mut output: W) -> Recorder<T, W> {
+            let mut failures: u64 = 0;
+
+            if writeln!(output, "{}", HEADER).is_err() {
+                failures += 1;
+            }
+
+            Recorder {
+                throttle,
+                output,
+                now:        0,
+                failures,
+            }
+        }
+
+        /// throttle returns a reference to the throttle being recorded.
+        pub fn throttle(& self) -> & T {
+            &self.throttle
+        }
+
+        /// failures returns the number of records that could not be written.
+        pub fn failures(& self) -> u64 {
+            self.failures
+        }
+
+        /// into_inner returns the throttle and the output after flushing it.
+        pub fn into_inner(mut self) -> (T, W) {
+            if self.output.flush().is_err() {
+                self.failures += 1;
+            }
+            (self.throttle, self.output)
+        }
+
+        fn log(& mut self, method: Method, events: throttle::Events, result: i64) {
+            let record: Record = record(&self.throttle, method, self.now, events, result);
+
+            if writeln!(self.output, "{}", record.to_string()).is_err() {
+                self.failures += 1;
+            }
+        }
+
+    }
+
+    impl<T: throttle::Throttle, W: io::Write> throttle::Throttle for Recorder<T, W> {
+
+        /***************************************************************************
+         * INSPECTORS
+         **************************************************************************/
+
+        /// size_of returns the size of the recorded throttle in bytes.
+        fn size_of(& self) -> usize {
+            self.throttle.size_of()
+        }
+
+        /***************************************************************************
+         * CONVERTORS
+         **************************************************************************/
+
+        /// as_string returns the underlying object as a printable string.
+        fn as_string(& self) -> string::String {
+            string::ToString::to_string(self)
+        }
+
+        /***************************************************************************
+         * SETTERS
+         **************************************************************************/
+
+        /// reset the recorded throttle back to its initial state.
+        fn reset(& mut self, now: ticks::Ticks) {
+            self.now = now;
+            self.throttle.reset(now);
+            self.log(Method::Reset, 0, 0);
+        }
+
+        /***************************************************************************
+         * GETTERS
+         **************************************************************************/
+
+        /// get_expected returns the expected ticks of the recorded throttle.
+        fn get_expected(& self) -> ticks::Ticks {
+            self.throttle.get_expected()
+        }
+
+        /// peek computes the delay of the recorded throttle without altering it.
+        /// Since peek does not alter the recorder either, it is not recorded.
+        fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+            self.throttle.peek(now, events)
+        }
+
+        /// is_empty returns true if the recorded throttle is empty.
+        fn is_empty(& self) -> bool {
+            self.throttle.is_empty()
+        }
+
+        /// is_full returns true if the recorded throttle is full.
+        fn is_full(& self) -> bool {
+            self.throttle.is_full()
+        }
+
+        /// is_alarmed returns true if the recorded throttle is alarmed.
+        fn is_alarmed(& self) -> bool {
+            self.throttle.is_alarmed()
+        }
+
+        /***************************************************************************
+         * SENSORS
+         **************************************************************************/
+
+        /// emptied returns true if the recorded throttle just emptied.
+        fn emptied(& self) -> bool {
+            self.throttle.emptied()
+        }
+
+        /// filled returns true if the recorded throttle just filled.
+        fn filled(& self) -> bool {
+            self.throttle.filled()
+        }
+
+        /// alarmed returns true if the recorded throttle just alarmed.
+        fn alarmed(& self) -> bool {
+            self.throttle.alarmed()
+        }
+
+        /// cleared returns true if the recorded throttle just unalarmed.
+        fn cleared(& self) -> bool {
+            self.throttle.cleared()
+        }
+
+        /***************************************************************************
+         * MUTATORS
+         **************************************************************************/
+
+        /// request computes the delay of the recorded throttle.
+        fn request(& mut self, now: ticks::Ticks) -> ticks::Ticks {
+            let delay: ticks::Ticks;
+
+            self.now = now;
+            delay = self.throttle.request(now);
+            self.log(Method::Request, 0, delay);
+
+            delay
+        }
+
+        /// commits updates the recorded throttle with the number of events.
+        fn commits(& mut self, events: throttle::Events) -> bool {
+            let result: bool = self.throttle.commits(events);
+
+            self.log(Method::Commits, events, result as i64);
+
+            result
+        }
+
+        /// commit is equivalent to calling commits with one event.
+        fn commit(& mut self) -> bool {
+            let result: bool = self.throttle.commit();
+
+            self.log(Method::Commit, 1, result as i64);
+
+            result
+        }
+
+        /// admits requests and commits on the recorded throttle.
+        fn admits(& mut self, now: ticks::Ticks, events: throttle::Events) -> bool {
+            let result: bool;
+
+            self.now = now;
+            result = self.throttle.admits(now, events);
+            self.log(Method::Admits, events, result as i64);
+
+            result
+        }
+
+        /// admit is equivalent to calling admits with one event.
+        fn admit(& mut self, now: ticks::Ticks) -> bool {
+            let result: bool;
+
+            self.now = now;
+            result = self.throttle.admit(now);
+            self.log(Method::Admit, 1, result as i64);
+
+            result
+        }
+
+        /// update is equivalent to calling admits with zero events.
+        fn update(& mut self, now: ticks::Ticks) -> bool {
+            let result: bool;
+
+            self.now = now;
+            result = self.throttle.update(now);
+            self.log(Method::Update, 0, result as i64);
+
+            result
+        }
+
+    }
+
+}
@@ -0,0 +1,176 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements a traffic generator that produces a schedule of emissions (each
+/// being some number of events, for example the length of a data block, at
+/// some offset in ticks from the start of the stream) following one of several
+/// patterns: constant bit rate, Poisson arrivals, on/off bursts, or the
+/// emissions recorded in a trace. The schedule depends only on the pattern,
+/// the rate, the block size, and the seed, so the same parameters always
+/// produce the same stream. The generator does not itself wait for the time of
+/// each emission; it is an iterator of offsets and sizes, which the caller may
+/// use in real time or in simulation.
+///
+pub mod trafgen {
+
+    use std::io;
+    use std::io::BufRead;
+    use std::string;
+    use ticks::ticks;
+    use throttle::throttle;
+    use trace::trace;
+    use random::random;
+
+    /// Pattern describes the shape of the generated traffic.
+    #[derive(Clone, Debug, PartialEq)]
+    pub enum Pattern {
+        /// Blocks at a constant interval.
+        Constant,
+        /// Blocks with exponentially distributed intervals.
+        Poisson,
+        /// Blocks at a higher rate during on periods, and none during off
+        /// periods, the periods having exponentially distributed lengths with
+        /// the specified means in ticks, so that the mean rate is the target.
+        OnOff { on: ticks::Ticks, off: ticks::Ticks },
+        /// Blocks of the specified sizes at the specified offsets in ticks.
+        Trace(Vec<(ticks::Ticks, throttle::Events)>),
+    }
+
+    /// load the emissions from a trace of a throttle, as written by a
+    /// trace::Recorder, for use as a Trace pattern. Each admitted or committed
+    /// record of more than zero events is an emission at an offset relative
+    /// to the first record.
+    pub fn load<R: io::Read>(input: R) -> io::Result<Vec<(ticks::Ticks, throttle::Events)>> {
+        let mut emissions: Vec<(ticks::Ticks, throttle::Events)> = Vec::new();
+        let mut start: Option<ticks::Ticks> = None;
+
+        for line in io::BufReader::new(input).lines() {
+            let record: trace::Record = match trace::Record::parse(&line?) {
+                Some(record) => record,
+                None => continue,
+            };
+            let origin: ticks::Ticks = *start.get_or_insert(record.now);
+            match record.method {
+                trace::Method::Reset | trace::Method::Request | trace::Method::Update => { },
+                _ => { if record.events > 0 { emissions.push((record.now - origin, record.events)); } },
+            }
+        }
+
+        Ok(emissions)
+    }
+
+    pub struct Generator {
+        pattern:    Pattern,                    // Shape of the traffic.
+        rate:       f64,                        // Mean events per second.
+        blocksize:  throttle::Events,           // Events per emission.
+        random:     random::Random,             // Random state.
+        offset:     ticks::Ticks,               // Offset of next emission.
+        until:      ticks::Ticks,               // OnOff: end of on period.
+        index:      usize,                      // Trace: next emission.
+    }
+
+    impl string::ToString for Generator {
+
+        fn to_string(& self) -> string::String {
+            format!("Generator@{:p}:{{r:{},b:{},o:{},s:{}}}",
+                self,
+                self.rate, self.blocksize, self.offset, self.random.get_seed())
+        }
+
+    }
+
+    impl Generator {
+
+        /// Allocate a new Generator object given the pattern, the mean rate in
+        /// events per second, the number of events in each emission, and the
+        /// seed. The rate and block size are ignored by the Trace pattern.
+        pub fn new(pattern: Pattern, rate: f64, blocksize: throttle::Events, seed: u64) -> Generator {
+            match pattern {
+                Pattern::Trace(_) => { },
+                Pattern::OnOff { on, off } => { assert!((rate > 0.0) && (blocksize > 0) && (on > 0) && (off >= 0)); },
+                _ => { assert!((rate > 0.0) && (blocksize > 0)); },
+            }
+
+            let mut that: Generator = Generator {
+                pattern,
+                rate,
+                blocksize,
+                random:     random::Random::new(seed),
+                offset:     0,
+                until:      0,
+                index:      0,
+            };
+
+            if let Pattern::OnOff { on, .. } = that.pattern {
+                that.until = that.exponential(on as f64);
+            }
+
+            that
+        }
+
+        fn exponential(& mut self, mean: f64) -> ticks::Ticks {
+            self.random.exponential(mean) as ticks::Ticks
+        }
+
+        fn interval(& self, rate: f64) -> f64 {
+            (self.blocksize as f64) * (ticks::frequency() as f64) / rate
+        }
+
+        /// fill the buffer with printable ASCII characters drawn from the
+        /// random state of the generator.
+        pub fn fill(& mut self, buffer: & mut [u8]) {
+            self.random.fill(buffer);
+        }
+
+    }
+
+    impl Iterator for Generator {
+
+        type Item = (ticks::Ticks, throttle::Events);
+
+        /// next returns the offset in ticks from the start of the stream and the
+        /// number of events of the next emission, or None if a Trace pattern
+        /// is exhausted.
+        fn next(& mut self) -> Option<(ticks::Ticks, throttle::Events)> {
+            let emission: (ticks::Ticks, throttle::Events);
+
+            match self.pattern {
+                Pattern::Constant => {
+                    emission = (self.offset, self.blocksize);
+                    self.offset = (((self.index + 1) as f64) * self.interval(self.rate)) as ticks::Ticks;
+                    self.index += 1;
+                },
+                Pattern::Poisson => {
+                    emission = (self.offset, self.blocksize);
+                    let mean: f64 = self.interval(self.rate);
+                    self.offset += self.exponential(mean);
+                },
+                Pattern::OnOff { on, off } => {
+                    emission = (self.offset, self.blocksize);
+                    let peak: f64 = self.rate * ((on + off) as f64) / (on as f64);
+                    self.offset += self.interval(peak) as ticks::Ticks;
+                    if self.offset >= self.until {
+                        self.offset += self.exponential(off as f64);
+                        self.until = self.offset + self.exponential(on as f64);
+                    }
+                },
+                Pattern::Trace(ref emissions) => {
+                    if self.index >= emissions.len() {
+                        return None;
+                    }
+                    emission = emissions[self.index];
+                    self.index += 1;
+                },
+            }
+
+            Some(emission)
+        }
+
+    }
+
+}
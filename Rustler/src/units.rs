@@ -0,0 +1,295 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+/// Implements strongly typed wrappers for times, counts, and rates, so that
+/// the compiler rather than the reader catches a time passed where a count
+/// was meant. Ticks is a time or duration in ticks, Events is a count of
+/// events, and Rate is some number of events per some number of ticks, from
+/// which the increment of a throttle is computed. Arithmetic on the wrappers
+/// is checked: the operators panic on overflow, and checked methods return
+/// None instead. The Throttle trait provides a counterpart of each of its
+/// methods in terms of the wrappers, whose name ends in _typed, and every
+/// throttle has a typed constructor named with alongside those taking bare
+/// ticks::Ticks and throttle::Events.
+///
+pub mod units {
+
+    use std::fmt;
+    use std::ops;
+    use std::time;
+    use ticks::ticks;
+    use throttle::throttle;
+    use gcra::gcra;
+
+    /***************************************************************************
+     * TICKS
+     **************************************************************************/
+
+    /// Ticks is a time, or a duration, in ticks.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Ticks(pub ticks::Ticks);
+
+    impl Ticks {
+
+        /// ZERO is no time at all.
+        pub const ZERO: Ticks = Ticks(0);
+
+        /// now returns the current time from ticks::now.
+        pub fn now() -> Ticks {
+            Ticks(ticks::now())
+        }
+
+        /// seconds returns the duration of the specified number of seconds.
+        pub fn seconds(seconds: i64) -> Ticks {
+            Ticks(seconds.checked_mul(ticks::frequency()).expect("Ticks overflow"))
+        }
+
+        /// from_duration returns the duration equivalent to a
+        /// std::time::Duration, or None if it is too long to be represented.
+        pub fn from_duration(duration: time::Duration) -> Option<Ticks> {
+            let seconds: ticks::Ticks = if duration.as_secs() > (i64::max_value() as u64) { return None; } else { duration.as_secs() as ticks::Ticks };
+            let fraction: ticks::Ticks = (duration.subsec_nanos() as ticks::Ticks) * ticks::frequency() / 1_000_000_000;
+
+            seconds.checked_mul(ticks::frequency()).and_then(|ticks| ticks.checked_add(fraction)).map(Ticks)
+        }
+
+        /// to_duration returns the equivalent std::time::Duration, or None if
+        /// the value is negative.
+        pub fn to_duration(& self) -> Option<time::Duration> {
+            if self.0 < 0 {
+                None
+            } else {
+                let seconds: ticks::Ticks = self.0 / ticks::frequency();
+                let fraction: ticks::Ticks = self.0 % ticks::frequency();
+                Some(time::Duration::new(seconds as u64, (fraction * 1_000_000_000 / ticks::frequency()) as u32))
+            }
+        }
+
+        /// as_secs_f64 returns the value in seconds.
+        pub fn as_secs_f64(& self) -> f64 {
+            (self.0 as f64) / (ticks::frequency() as f64)
+        }
+
+        /// checked_add returns the sum, or None on overflow.
+        pub fn checked_add(self, other: Ticks) -> Option<Ticks> {
+            self.0.checked_add(other.0).map(Ticks)
+        }
+
+        /// checked_sub returns the difference, or None on overflow.
+        pub fn checked_sub(self, other: Ticks) -> Option<Ticks> {
+            self.0.checked_sub(other.0).map(Ticks)
+        }
+
+        /// checked_mul returns the product with a scalar, or None on overflow.
+        pub fn checked_mul(self, factor: i64) -> Option<Ticks> {
+            self.0.checked_mul(factor).map(Ticks)
+        }
+
+        /// checked_div returns the quotient by a scalar, or None if the
+        /// divisor is zero.
+        pub fn checked_div(self, divisor: i64) -> Option<Ticks> {
+            self.0.checked_div(divisor).map(Ticks)
+        }
+
+    }
+
+    impl From<ticks::Ticks> for Ticks {
+        fn from(ticks: ticks::Ticks) -> Ticks { Ticks(ticks) }
+    }
+
+    impl From<Ticks> for ticks::Ticks {
+        fn from(ticks: Ticks) -> ticks::Ticks { ticks.0 }
+    }
+
+    impl ops::Add for Ticks {
+        type Output = Ticks;
+        fn add(self, other: Ticks) -> Ticks { self.checked_add(other).expect("Ticks overflow") }
+    }
+
+    impl ops::Sub for Ticks {
+        type Output = Ticks;
+        fn sub(self, other: Ticks) -> Ticks { self.checked_sub(other).expect("Ticks overflow") }
+    }
+
+    impl ops::Mul<i64> for Ticks {
+        type Output = Ticks;
+        fn mul(self, factor: i64) -> Ticks { self.checked_mul(factor).expect("Ticks overflow") }
+    }
+
+    impl ops::Div<i64> for Ticks {
+        type Output = Ticks;
+        fn div(self, divisor: i64) -> Ticks { self.checked_div(divisor).expect("Ticks division") }
+    }
+
+    impl ops::AddAssign for Ticks {
+        fn add_assign(& mut self, other: Ticks) { *self = *self + other; }
+    }
+
+    impl ops::SubAssign for Ticks {
+        fn sub_assign(& mut self, other: Ticks) { *self = *self - other; }
+    }
+
+    impl fmt::Display for Ticks {
+
+        /// fmt displays the value in the largest of seconds, milliseconds,
+        /// microseconds, or nanoseconds in which its magnitude is at least one.
+        fn fmt(& self, f: & mut fmt::Formatter) -> fmt::Result {
+            let seconds: f64 = self.as_secs_f64();
+            let magnitude: f64 = seconds.abs();
+
+            if magnitude >= 1.0 {
+                write!(f, "{}s", seconds)
+            } else if magnitude >= 0.001 {
+                write!(f, "{}ms", seconds * 1_000.0)
+            } else if magnitude >= 0.000_001 {
+                write!(f, "{}us", seconds * 1_000_000.0)
+            } else {
+                write!(f, "{}ns", seconds * 1_000_000_000.0)
+            }
+        }
+
+    }
+
+    /***************************************************************************
+     * EVENTS
+     **************************************************************************/
+
+    /// Events is a count of events.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Events(pub throttle::Events);
+
+    impl Events {
+
+        /// checked_add returns the sum, or None on overflow.
+        pub fn checked_add(self, other: Events) -> Option<Events> {
+            self.0.checked_add(other.0).map(Events)
+        }
+
+        /// checked_sub returns the difference, or None on overflow.
+        pub fn checked_sub(self, other: Events) -> Option<Events> {
+            self.0.checked_sub(other.0).map(Events)
+        }
+
+        /// checked_mul returns the product with a scalar, or None on overflow.
+        pub fn checked_mul(self, factor: i64) -> Option<Events> {
+            self.0.checked_mul(factor).map(Events)
+        }
+
+    }
+
+    impl From<throttle::Events> for Events {
+        fn from(events: throttle::Events) -> Events { Events(events) }
+    }
+
+    impl From<Events> for throttle::Events {
+        fn from(events: Events) -> throttle::Events { events.0 }
+    }
+
+    impl ops::Add for Events {
+        type Output = Events;
+        fn add(self, other: Events) -> Events { self.checked_add(other).expect("Events overflow") }
+    }
+
+    impl ops::Sub for Events {
+        type Output = Events;
+        fn sub(self, other: Events) -> Events { self.checked_sub(other).expect("Events overflow") }
+    }
+
+    impl ops::Mul<i64> for Events {
+        type Output = Events;
+        fn mul(self, factor: i64) -> Events { self.checked_mul(factor).expect("Events overflow") }
+    }
+
+    impl ops::AddAssign for Events {
+        fn add_assign(& mut self, other: Events) { *self = *self + other; }
+    }
+
+    impl ops::SubAssign for Events {
+        fn sub_assign(& mut self, other: Events) { *self = *self - other; }
+    }
+
+    impl fmt::Display for Events {
+
+        fn fmt(& self, f: & mut fmt::Formatter) -> fmt::Result {
+            if self.0 == 1 {
+                write!(f, "1 event")
+            } else {
+                write!(f, "{} events", self.0)
+            }
+        }
+
+    }
+
+    /***************************************************************************
+     * RATE
+     **************************************************************************/
+
+    /// Rate is some number of events per some duration.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct Rate {
+        events:     Events,                     // Numerator.
+        per:        Ticks,                      // Denominator.
+    }
+
+    impl Rate {
+
+        /// Allocate a new Rate object given the number of events and the
+        /// duration over which they occur, or None unless both are positive.
+        pub fn new(events: Events, per: Ticks) -> Option<Rate> {
+            if (events.0 > 0) && (per.0 > 0) {
+                Some(Rate { events, per })
+            } else {
+                None
+            }
+        }
+
+        /// per_second returns a rate of the specified number of events per
+        /// second, or None unless the number of events is positive.
+        pub fn per_second(events: throttle::Events) -> Option<Rate> {
+            Rate::new(Events(events), Ticks::seconds(1))
+        }
+
+        /// per_duration returns a rate of the specified number of events per
+        /// std::time::Duration, or None unless the number of events is
+        /// positive and the duration is at least a tick and fits in Ticks.
+        pub fn per_duration(events: throttle::Events, duration: time::Duration) -> Option<Rate> {
+            Rate::new(Events(events), Ticks::from_duration(duration)?)
+        }
+
+        /// get_events returns the numerator of the rate.
+        pub fn get_events(& self) -> Events {
+            self.events
+        }
+
+        /// get_per returns the denominator of the rate.
+        pub fn get_per(& self) -> Ticks {
+            self.per
+        }
+
+        /// increment returns the number of ticks between events at this rate,
+        /// rounded up, as used by a Gcra.
+        pub fn increment(& self) -> Ticks {
+            Ticks(gcra::increment(self.events.0, self.per.0, 1))
+        }
+
+        /// per_second_f64 returns the rate in events per second.
+        pub fn per_second_f64(& self) -> f64 {
+            (self.events.0 as f64) / self.per.as_secs_f64()
+        }
+
+    }
+
+    impl fmt::Display for Rate {
+
+        fn fmt(& self, f: & mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} events/s", self.per_second_f64())
+        }
+
+    }
+
+}
@@ -6,41 +6,56 @@
 // mailto:coverclock@diag.com
 // https://github.com/coverclock/com-diag-rustler
 
-extern crate rustler;
+#![allow(dead_code)]
 
-use std::os::raw;
+extern crate rustler;
 
-extern {
-    /// rand(3) <stdlib.h> libc.
-    fn rand() -> raw::c_int;
-}
+use std::cell;
+use std::sync;
+use rustler::random::random;
 
 /*******************************************************************************
  * HELPERS
  ******************************************************************************/
 
+static SEED: sync::OnceLock<u64> = sync::OnceLock::new();
+
+/// Return the seed shared by every thread in this run of the tests, taken from
+/// the RUSTLER_SEED environment variable if it is set.
+pub fn seed() -> u64 {
+    *SEED.get_or_init(random::seed)
+}
+
+thread_local! {
+    /// Each thread has its own generator seeded with the seed of the run, so
+    /// that each test and each thread it spawns draws a reproducible sequence.
+    /// The seed is printed when a thread first draws from its generator; the
+    /// test framework displays it only if the test fails.
+    static GENERATOR: cell::RefCell<random::Random> = {
+        let seed: u64 = seed();
+        eprintln!("harness: seed={} (replay with {}={}).", seed, random::SEED, seed);
+        cell::RefCell::new(random::Random::new(seed))
+    };
+}
+
 /// Compute a random blocksize between the values 1 and maximum inclusive.
 pub fn blocksize(maximum: usize) -> usize {
-    unsafe {
-        let size: usize = ((rand() % (maximum as raw::c_int)) + 1) as usize;
+    let size: usize = GENERATOR.with(|generator| generator.borrow_mut().range(1, maximum as u64)) as usize;
 
-        assert!(size >= 1);
-        assert!(size <= maximum);
+    assert!(size >= 1);
+    assert!(size <= maximum);
 
-        return size;
-    }
+    size
 }
 
 /// Compute a eight-bit datum between the values 1 and maximum inclusive.
 pub fn payload(maximum: u8) -> u8 {
-    unsafe {
-        let byte: u8 = ((rand() % (maximum as raw::c_int)) + 1) as u8;
+    let byte: u8 = GENERATOR.with(|generator| generator.borrow_mut().range(1, maximum as u64)) as u8;
 
-        assert!(byte >= 1);
-        assert!(byte <= maximum);
+    assert!(byte >= 1);
+    assert!(byte <= maximum);
 
-        return byte;
-    }
+    byte
 }
 
 /// Return the absolute value of a 64-bit float.
@@ -131,17 +146,150 @@ pub fn simulate(shape: & mut throttle::Throttle, police: & mut throttle::Throttl
     (peak, sustained)
 }
 
+/*******************************************************************************
+ * CHECKPOINTED EVENT STREAM
+ ******************************************************************************/
+
+/// Churn a throttle through a stream of one to four events at a time arriving
+/// at intervals of up to the specified spread in ticks, starting at the
+/// specified time in ticks, so that its state is something other than the
+/// initial one. Returns the time of the last admission.
+pub fn churn(throttle: & mut throttle::Throttle, mut now: ticks::Ticks, spread: ticks::Ticks, iterations: usize) -> ticks::Ticks {
+    for ii in 0..iterations {
+        now += ((ii as ticks::Ticks) * 53) % spread;
+        throttle.admits(now, ((ii % 4) + 1) as throttle::Events);
+    }
+    now
+}
+
+/// Drive two throttles whose clocks have different epochs through the same
+/// event stream, arriving at intervals of up to the specified spread in ticks,
+/// and verify that they make the same decisions.
+pub fn lockstep(one: & mut throttle::Throttle, mut now1: ticks::Ticks, two: & mut throttle::Throttle, mut now2: ticks::Ticks, spread: ticks::Ticks, iterations: usize) {
+    let mut interval: ticks::Ticks;
+    let mut events: throttle::Events;
+    for ii in 0..iterations {
+        interval = ((ii as ticks::Ticks) * 37) % spread;
+        events = ((ii % 3) + 1) as throttle::Events;
+        now1 += interval;
+        now2 += interval;
+        assert!(one.peek(now1, events) == two.peek(now2, events));
+        assert!(one.request(now1) == two.request(now2));
+        assert!(one.commits(events) == two.commits(events));
+        assert!(one.get_expected() == two.get_expected());
+        assert!(one.is_empty() == two.is_empty());
+        assert!(one.is_full() == two.is_full());
+        assert!(one.is_alarmed() == two.is_alarmed());
+        assert!(one.emptied() == two.emptied());
+        assert!(one.filled() == two.filled());
+        assert!(one.alarmed() == two.alarmed());
+        assert!(one.cleared() == two.cleared());
+    }
+}
+
 /*******************************************************************************
  * ACTUAL EVENT STREAM
  ******************************************************************************/
 
-use std::sync::mpsc;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::marker;
 use std::net;
+use std::os::unix::net as unix;
+use std::sync::mpsc;
 use std::thread;
 use rustler::fletcher::fletcher;
 
 const DEBUG: bool = false;
 
+/// Transport selects how the shaper thread sends each burst to the policer
+/// thread. Every transport uses addresses chosen by the operating system, so
+/// exercised tests may run in parallel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Transport {
+    /// UDP datagrams over the loopback interface.
+    Udp,
+    /// A TCP stream over the loopback interface, each burst preceded by its
+    /// length.
+    Tcp,
+    /// An unnamed pair of Unix domain datagram sockets.
+    Unix,
+}
+
+/// Sends one burst at a time.
+pub trait Outbound: marker::Send {
+    fn send(& mut self, buffer: & [u8]) -> io::Result<()>;
+}
+
+/// Receives one burst at a time, returning its length.
+pub trait Inbound: marker::Send {
+    fn receive(& mut self, buffer: & mut [u8]) -> io::Result<usize>;
+}
+
+impl Outbound for net::UdpSocket {
+    fn send(& mut self, buffer: & [u8]) -> io::Result<()> {
+        net::UdpSocket::send(self, buffer).map(|_| ())
+    }
+}
+
+impl Inbound for net::UdpSocket {
+    fn receive(& mut self, buffer: & mut [u8]) -> io::Result<usize> {
+        self.recv(buffer)
+    }
+}
+
+impl Outbound for unix::UnixDatagram {
+    fn send(& mut self, buffer: & [u8]) -> io::Result<()> {
+        unix::UnixDatagram::send(self, buffer).map(|_| ())
+    }
+}
+
+impl Inbound for unix::UnixDatagram {
+    fn receive(& mut self, buffer: & mut [u8]) -> io::Result<usize> {
+        self.recv(buffer)
+    }
+}
+
+impl Outbound for net::TcpStream {
+    fn send(& mut self, buffer: & [u8]) -> io::Result<()> {
+        self.write_all(&(buffer.len() as u32).to_be_bytes())?;
+        self.write_all(buffer)
+    }
+}
+
+impl Inbound for net::TcpStream {
+    fn receive(& mut self, buffer: & mut [u8]) -> io::Result<usize> {
+        let mut length = [0u8; 4];
+        self.read_exact(& mut length)?;
+        let size: usize = u32::from_be_bytes(length) as usize;
+        self.read_exact(& mut buffer[..size])?;
+        Ok(size)
+    }
+}
+
+/// Return a connected sender and receiver using the specified transport.
+pub fn connect(transport: Transport) -> (Box<Outbound>, Box<Inbound>) {
+    match transport {
+        Transport::Udp => {
+            let source = net::UdpSocket::bind("127.0.0.1:0").expect("couldn't bind to address");
+            let sink = net::UdpSocket::bind("127.0.0.1:0").expect("couldn't bind to address");
+            sink.connect(source.local_addr().unwrap()).expect("couldn't connect to address");
+            (Box::new(sink), Box::new(source))
+        },
+        Transport::Tcp => {
+            let listener = net::TcpListener::bind("127.0.0.1:0").expect("couldn't bind to address");
+            let sink = net::TcpStream::connect(listener.local_addr().unwrap()).expect("couldn't connect to address");
+            let (source, _) = listener.accept().expect("couldn't accept connection");
+            (Box::new(sink), Box::new(source))
+        },
+        Transport::Unix => {
+            let (sink, source) = unix::UnixDatagram::pair().expect("couldn't create pair");
+            (Box::new(sink), Box::new(source))
+        },
+    }
+}
+
 fn producer(maximum: usize, mut limit: u64, output: & mpsc::SyncSender<u8>, results: & mpsc::Sender<(u64, u16)>) {
     let mut count: usize = 0;
     let mut largest: usize = 0;
@@ -196,7 +344,7 @@ fn producer(maximum: usize, mut limit: u64, output: & mpsc::SyncSender<u8>, resu
     eprintln!("producer: end total={}B mean={}B/burst maximum={}B/burst.", total, (total as f64) / (count as f64), largest);
 }
 
-fn shaper(input: & mpsc::Receiver<u8>, shape: & mut throttle::Throttle, output: & net::UdpSocket, address: & net::SocketAddrV4) {
+fn shaper(input: & mpsc::Receiver<u8>, shape: & mut throttle::Throttle, output: & mut Outbound) {
     let frequency: f64 = ticks::frequency() as f64;
     let mut buffer = [0u8; 65536];
     let before: ticks::Ticks;
@@ -257,7 +405,7 @@ fn shaper(input: & mpsc::Receiver<u8>, shape: & mut throttle::Throttle, output:
         if size > largest { largest = size; }
         total += size as u64;
         
-        match output.send_to(&buffer[..size], address) {
+        match output.send(&buffer[..size]) {
             Ok(_) => { },
             Err(error) => { panic!(error); }
         }
@@ -283,7 +431,7 @@ fn shaper(input: & mpsc::Receiver<u8>, shape: & mut throttle::Throttle, output:
     
     buffer[0] = 0x00;
     size = 1;
-    match output.send_to(&buffer[..size], address) {
+    match output.send(&buffer[..size]) {
         Ok(_) => { },
         Err(error) => { panic!(error); }
     }
@@ -295,7 +443,7 @@ fn shaper(input: & mpsc::Receiver<u8>, shape: & mut throttle::Throttle, output:
     eprintln!("shaper: end total={}B mean={}B/burst maximum={}B/burst delay={}s/burst peak={}B/s sustained={}B/s.", total, mean, largest, average, peak, sustained);    
 }
 
-fn policer(input: & net::UdpSocket, police: & mut throttle::Throttle, output: & mpsc::Sender<u8>) {
+fn policer(input: & mut Inbound, police: & mut throttle::Throttle, output: & mpsc::Sender<u8>) {
     let mut eof: bool = false;
     let mut buffer = [0u8; 65536];
     let frequency: f64 = ticks::frequency() as f64;
@@ -320,8 +468,8 @@ fn policer(input: & net::UdpSocket, police: & mut throttle::Throttle, output: &
     
     while !eof {       
         
-        match input.recv_from(& mut buffer) {
-            Ok(value) => { size = value.0; }
+        match input.receive(& mut buffer) {
+            Ok(value) => { size = value; }
             Err(error) => { panic!(error); }
         }
         assert!(size > 0);
@@ -426,98 +574,12 @@ fn consumer(maximum: usize, input: & mpsc::Receiver<u8>, results: & mpsc::Sender
     eprintln!("consumer: end total={}B.", total);
 }
 
-use std::sync;
-use rustler::gcra::gcra;
-
-/// Exercise a shaping gcra and a policing gcra by producing an
-/// actual event stream, shaping it, policing it, and consuming it four threads.
-/// Returns the difference in the total byte counts and the checksums between
-/// the producer and the consumer threads.
-pub fn exercise_gcra(shape: sync::Arc<sync::Mutex<gcra::Gcra>>, police: sync::Arc<sync::Mutex<gcra::Gcra>>, maximum: usize, total: u64) -> (i64, i64) {
-    let producertotal: u64;
-    let producerchecksum: u16;
-    let consumertotal: u64;
-    let consumerchecksum: u16;
-    
-    eprintln!("exercise: maximum={}.", maximum);
-    eprintln!("exercise: total={}.", total);
-
-    let (supply_tx, supply_rx) = mpsc::sync_channel::<u8>(maximum + 1);
-    let (demand_tx, demand_rx) = mpsc::channel::<u8>();
-
-    let (consumer_tx, consumer_rx) = mpsc::channel::<(u64, u16)>();
-    let (producer_tx, producer_rx) = mpsc::channel::<(u64, u16)>();
-
-    let source = net::UdpSocket::bind("127.0.0.1:5555").expect("couldn't bind to address");
-    let sink = net::UdpSocket::bind("127.0.0.1:0").expect("couldn't bind to address");
-    let destination = net::SocketAddrV4::new(net::Ipv4Addr::new(127, 0, 0, 1), 5555);
-       
-    eprintln!("exercise: Spawning.");
-   
-    let consuming = thread::spawn( move || {
-        consumer(maximum, & demand_rx, & consumer_tx)
-    } );
-
-    let policing  = thread::spawn( move || {
-        let mut throttle: gcra::Gcra = *police.lock().unwrap();
-        policer(& source, & mut throttle, & demand_tx)
-    } );
-
-    let shaping   = thread::spawn( move || {
-        let mut throttle: gcra::Gcra = *shape.lock().unwrap();
-        shaper(& supply_rx, & mut throttle, & sink, & destination)
-    } );
-
-    let producing = thread::spawn( move || {
-        producer(maximum, total, & supply_tx, & producer_tx)
-    } );
-    
-    eprintln!("exercise: Joining.");
-
-    match consuming.join() {
-        Ok(_) => { },
-        Err(error) => { panic!(error); }
-    }
-
-    match policing.join() {
-        Ok(_) => { },
-        Err(error) => { panic!(error); }
-    }
-
-    match shaping.join() {
-        Ok(_) => { },
-        Err(error) => { panic!(error); }
-    }
- 
-    match producing.join() {
-        Ok(_) => { },
-        Err(error) => { panic!(error); }
-    }
-
-    eprintln!("exercise: Checking.");
-    
-    match producer_rx.recv() {
-        Ok(value) => { producertotal = value.0; producerchecksum = value.1; },
-        Err(error) => { panic!(error); }
-    }
-    eprintln!("exercise: produced={}:{:04x}.", producertotal, producerchecksum);
- 
-    match consumer_rx.recv() {
-        Ok(value) => { consumertotal = value.0; consumerchecksum = value.1; },
-        Err(error) => { panic!(error); }
-    }
-    eprintln!("exercise: consumer={}:{:04x}.", consumertotal, consumerchecksum);
-    
-    (((consumertotal- producertotal) as i64), ((consumerchecksum - producerchecksum) as i64))
-}
-
-use rustler::contract::contract;
-
-/// Exercise a shaping contract and a policing contract by producing an
-/// actual event stream, shaping it, policing it, and consuming it four threads.
-/// Returns the difference in the total byte counts and the checksums between
-/// the producer and the consumer threads.
-pub fn exercise_contract(shape: sync::Arc<sync::Mutex<contract::Contract>>, police: sync::Arc<sync::Mutex<contract::Contract>>, maximum: usize, total: u64) -> (i64, i64) {
+/// Exercise a shaping throttle and a policing throttle of any type by producing
+/// an actual event stream, shaping it, sending it over the specified transport,
+/// policing it, and consuming it in four threads. Returns the difference in the
+/// total byte counts and the checksums between the producer and the consumer
+/// threads.
+pub fn exercise<T: throttle::Throttle + marker::Send + 'static>(shape: T, police: T, maximum: usize, total: u64, transport: Transport) -> (i64, i64) {
     let producertotal: u64;
     let producerchecksum: u16;
     let consumertotal: u64;
@@ -525,6 +587,7 @@ pub fn exercise_contract(shape: sync::Arc<sync::Mutex<contract::Contract>>, poli
     
     eprintln!("exercise: maximum={}.", maximum);
     eprintln!("exercise: total={}.", total);
+    eprintln!("exercise: transport={:?}.", transport);
 
     let (supply_tx, supply_rx) = mpsc::sync_channel::<u8>(maximum + 1);
     let (demand_tx, demand_rx) = mpsc::channel::<u8>();
@@ -532,9 +595,7 @@ pub fn exercise_contract(shape: sync::Arc<sync::Mutex<contract::Contract>>, poli
     let (consumer_tx, consumer_rx) = mpsc::channel::<(u64, u16)>();
     let (producer_tx, producer_rx) = mpsc::channel::<(u64, u16)>();
 
-    let source = net::UdpSocket::bind("127.0.0.1:5555").expect("couldn't bind to address");
-    let sink = net::UdpSocket::bind("127.0.0.1:0").expect("couldn't bind to address");
-    let destination = net::SocketAddrV4::new(net::Ipv4Addr::new(127, 0, 0, 1), 5555);
+    let (mut sink, mut source) = connect(transport);
        
     eprintln!("exercise: Spawning.");
    
@@ -543,13 +604,13 @@ pub fn exercise_contract(shape: sync::Arc<sync::Mutex<contract::Contract>>, poli
     } );
 
     let policing  = thread::spawn( move || {
-        let mut throttle: contract::Contract = *police.lock().unwrap();
-        policer(& source, & mut throttle, & demand_tx)
+        let mut throttle: T = police;
+        policer(& mut *source, & mut throttle, & demand_tx)
     } );
 
     let shaping   = thread::spawn( move || {
-        let mut throttle: contract::Contract = *shape.lock().unwrap();
-        shaper(& supply_rx, & mut throttle, & sink, & destination)
+        let mut throttle: T = shape;
+        shaper(& supply_rx, & mut throttle, & mut *sink)
     } );
 
     let producing = thread::spawn( move || {
@@ -0,0 +1,278 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::net;
+use std::option;
+use std::os::unix::net as unix;
+use std::path;
+use std::process;
+use std::sync;
+use std::thread;
+use rustler::ticks::ticks;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::daemon::daemon;
+use rustler::client::client;
+
+/// Return a socket path in the temporary directory unique to this process and test.
+fn pathname(name: & str) -> path::PathBuf {
+    let mut path: path::PathBuf = env::temp_dir();
+    path.push(format!("rustler-{}-{}.sock", process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Return a registry whose template admits one event per millisecond with no
+/// burst.
+fn registry() -> daemon::Shared<gcra::Gcra> {
+    let increment: ticks::Ticks = ticks::frequency() / 1000;
+    sync::Arc::new(sync::Mutex::new(daemon::Registry::new(gcra::Gcra::new().init(increment, 0, ticks::now()))))
+}
+
+/// Exercise a connected client against a registry as returned by registry().
+fn exercise(client: & mut client::Client) {
+    let increment: ticks::Ticks = ticks::frequency() / 1000;
+    assert!(client.admit("alpha", 1).unwrap());
+    assert!(!client.admit("alpha", 1).unwrap());
+    /* Keys are independent. */
+    assert!(client.admit("beta", 1).unwrap());
+    /* A reset key starts over. */
+    assert!(client.request("beta").unwrap() > 0);
+    client.reset("beta").unwrap();
+    assert!(client.request("beta").unwrap() == 0);
+    assert!(client.admit("beta", 0).unwrap());
+    let delay: ticks::Ticks = client.request("alpha").unwrap();
+    eprintln!("delay={}", delay);
+    assert!(delay > 0);
+    assert!(delay <= (2 * increment));
+    ticks::sleep(delay);
+    assert!(client.request("alpha").unwrap() == 0);
+    assert!(client.admit("alpha", 1).unwrap());
+    assert!(client.admit("bad key", 1).is_err());
+    assert!(client.admit("gamma", -1).is_err());
+}
+
+#[test]
+fn test_daemon_050_sanity() {
+    let now: ticks::Ticks = ticks::now();
+    let mut registry: daemon::Registry<contract::Contract> = daemon::Registry::new(contract::Contract::new().init(1000, 0, 2000, 4000, now));
+    println!("registry={}", registry.to_string());
+    assert!(registry.is_empty());
+    assert!(registry.handle("admit alpha 1\n", now) == "OK 1");
+    assert!(registry.handle("admit alpha 1\n", now) == "OK 0");
+    assert!(registry.handle("  admit   beta   1  \n", now) == "OK 1");
+    assert!(registry.len() == 2);
+    assert!(registry.handle("request alpha\n", now) == "OK 2000");
+    assert!(registry.handle("request alpha\n", now + 2000) == "OK 0");
+    assert!(registry.handle("reset alpha\n", now) == "OK 0");
+    assert!(registry.len() == 1);
+    assert!(registry.handle("reset alpha\n", now) == "OK 0");
+    assert!(registry.handle("request alpha\n", now) == "OK 0");
+    assert!(registry.handle("admit alpha\n", now) == "ERR command");
+    assert!(registry.handle("admit alpha -1\n", now) == "ERR events");
+    assert!(registry.handle("admit alpha one\n", now) == "ERR events");
+    assert!(registry.handle("admit alpha 9223372036854775807\n", now) == "ERR events");
+    assert!(registry.handle(&format!("admit alpha {}\n", daemon::EVENTS + 1), now) == "ERR events");
+    assert!(registry.handle(&format!("admit alpha {}\n", daemon::EVENTS), now).starts_with("OK "));
+    assert!(registry.handle("request alpha\n", now) != "ERR capacity");
+    assert!(registry.handle("commit alpha\n", now) == "ERR command");
+    assert!(registry.handle("\n", now) == "ERR empty");
+}
+
+#[test]
+fn test_daemon_055_long() {
+    /* One event per minute: the second waits a full minute, reported exactly in nanoseconds. */
+    let frequency: ticks::Ticks = ticks::frequency();
+    let now: ticks::Ticks = ticks::now();
+    let mut registry: daemon::Registry<gcra::Gcra> = daemon::Registry::new(gcra::Gcra::new().init(60 * frequency, 0, now));
+    assert!(registry.handle("admit alpha 1\n", now) == "OK 1");
+    assert!(registry.handle("request alpha\n", now) == "OK 60000000000");
+    assert!(registry.handle("request alpha\n", now + (30 * frequency)) == "OK 30000000000");
+    /* A backlog of a million events is about eleven and a half days. */
+    assert!(registry.handle("admit beta 1000000\n", now) == "OK 1");
+    assert!(registry.handle("request beta\n", now) == format!("OK {}", 1_000_000 * 60 * 1_000_000_000i64));
+    assert!(ticks::to_nanoseconds(i64::max_value()) == (i64::max_value() / frequency) * 1_000_000_000 + (i64::max_value() % frequency) * 1_000_000_000 / frequency);
+    assert!(ticks::from_nanoseconds(i64::max_value()) == i64::max_value() / 1_000_000_000 * frequency + (i64::max_value() % 1_000_000_000) * frequency / 1_000_000_000);
+}
+
+#[test]
+fn test_daemon_060_capacity() {
+    let now: ticks::Ticks = 0;
+    let mut registry: daemon::Registry<gcra::Gcra> = daemon::Registry::with_capacity(gcra::Gcra::new().init(1000, 0, now), 2);
+    assert!(daemon::Registry::new(gcra::Gcra::new()).get_capacity() == daemon::CAPACITY);
+    assert!(registry.get_capacity() == 2);
+    assert!(registry.handle("admit alpha 5\n", now) == "OK 1");
+    assert!(registry.handle("admit beta 2\n", now) == "OK 1");
+    assert!(registry.handle("admit gamma 1\n", now) == "ERR capacity");
+    assert!(registry.handle("request gamma\n", now) == "ERR capacity");
+    /* Known keys are still served. */
+    assert!(registry.handle("admit alpha 1\n", now) == "OK 0");
+    assert!(registry.len() == 2);
+    /* Only beta has emptied, so only beta is evicted. */
+    assert!(registry.handle("admit gamma 1\n", now + 2000) == "OK 1");
+    assert!(registry.len() == 2);
+    assert!(registry.handle("admit alpha 1\n", now + 2000) == "OK 0");
+    /* Nothing idle, nothing evicted. */
+    assert!(registry.evict(now + 2000) == 0);
+    assert!(registry.evict(now + 10000) == 2);
+    assert!(registry.is_empty());
+}
+
+#[test]
+fn test_daemon_070_poisoned() {
+    let shared: daemon::Shared<gcra::Gcra> = registry();
+    let poisoner: daemon::Shared<gcra::Gcra> = shared.clone();
+    assert!(thread::spawn(move || { let _guard = poisoner.lock().unwrap(); panic!("poisoning"); }).join().is_err());
+    assert!(shared.is_poisoned());
+    let mut output: Vec<u8> = Vec::new();
+    daemon::session(&shared, &b"admit alpha 1\nrequest alpha\n"[..], & mut output).unwrap();
+    let response: String = String::from_utf8(output).unwrap();
+    eprintln!("response={:?}", response);
+    assert!(response.starts_with("OK 1\nOK "));
+    assert!(daemon::lock(&shared).len() == 1);
+}
+
+#[test]
+fn test_daemon_100_unix() {
+    let path: path::PathBuf = pathname("100");
+    let listener: unix::UnixListener = unix::UnixListener::bind(&path).unwrap();
+    let shared: daemon::Shared<gcra::Gcra> = registry();
+    let server: daemon::Shared<gcra::Gcra> = shared.clone();
+    thread::spawn(move || { daemon::serve_unix(server, listener) });
+    let mut client: client::Client = client::Client::unix(&path).unwrap();
+    exercise(& mut client);
+    /* Many clients share the same throttles. */
+    let mut other: client::Client = client::Client::unix(&path).unwrap();
+    assert!(!other.admit("alpha", 1).unwrap());
+    eprintln!("registry={}", shared.lock().unwrap().to_string());
+    assert!(shared.lock().unwrap().len() == 2);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_daemon_200_tcp() {
+    let listener: net::TcpListener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address: net::SocketAddr = listener.local_addr().unwrap();
+    let shared: daemon::Shared<gcra::Gcra> = registry();
+    let server: daemon::Shared<gcra::Gcra> = shared.clone();
+    thread::spawn(move || { daemon::serve_tcp(server, listener) });
+    let mut client: client::Client = client::Client::tcp(address).unwrap();
+    exercise(& mut client);
+    eprintln!("registry={}", shared.lock().unwrap().to_string());
+    assert!(shared.lock().unwrap().len() == 2);
+}
+
+#[test]
+fn test_daemon_250_long() {
+    /* A delay of a minute survives the round trip to the client. */
+    let frequency: ticks::Ticks = ticks::frequency();
+    let listener: net::TcpListener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address: net::SocketAddr = listener.local_addr().unwrap();
+    let shared: daemon::Shared<gcra::Gcra> = sync::Arc::new(sync::Mutex::new(daemon::Registry::new(gcra::Gcra::new().init(60 * frequency, 0, ticks::now()))));
+    thread::spawn(move || { daemon::serve_tcp(shared, listener) });
+    let mut client: client::Client = client::Client::tcp(address).unwrap();
+    assert!(client.admit("alpha", 1).unwrap());
+    let delay: ticks::Ticks = client.request("alpha").unwrap();
+    eprintln!("delay={}", delay);
+    assert!(delay > (59 * frequency));
+    assert!(delay <= (60 * frequency));
+    /* The largest reply converts without overflowing. */
+    let listener: net::TcpListener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address: net::SocketAddr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut writer: net::TcpStream = stream.try_clone().unwrap();
+        for line in io::BufReader::new(stream).lines() {
+            if line.is_err() {
+                break;
+            }
+            writer.write_all(format!("OK {}\n", i64::max_value()).as_bytes()).unwrap();
+        }
+    });
+    let mut client: client::Client = client::Client::tcp(address).unwrap();
+    let delay: ticks::Ticks = client.request("alpha").unwrap();
+    eprintln!("delay={}", delay);
+    assert!(delay == ticks::from_nanoseconds(i64::max_value()));
+    assert!(delay > 0);
+}
+
+#[test]
+fn test_daemon_400_throttled() {
+    let path: path::PathBuf = pathname("400");
+    let mut child: process::Child = process::Command::new(env!("CARGO_BIN_EXE_throttled"))
+        .args(["-p", "1000", "-s", "100", "-b", "10", "-u"])
+        .arg(&path)
+        .spawn().unwrap();
+    let mut client: option::Option<client::Client> = None;
+    for _ in 0..100 {
+        if let Ok(value) = client::Client::unix(&path) {
+            client = Some(value);
+            break;
+        }
+        ticks::sleep(ticks::frequency() / 100);
+    }
+    let mut client: client::Client = client.unwrap();
+    let mut admitted: i32 = 0;
+    for _ in 0..20 {
+        if client.admit("alpha", 1).unwrap() {
+            admitted += 1;
+        }
+    }
+    eprintln!("admitted={}", admitted);
+    /* At most the burst is admitted, and the peak rate limits it further. */
+    assert!(admitted >= 1);
+    assert!(admitted <= 10);
+    assert!(client.request("alpha").unwrap() > 0);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_daemon_500_stale() {
+    let path: path::PathBuf = pathname("500");
+    /* A stale socket left behind is replaced. */
+    let stale: unix::UnixListener = unix::UnixListener::bind(&path).unwrap();
+    drop(stale);
+    let mut child: process::Child = process::Command::new(env!("CARGO_BIN_EXE_throttled"))
+        .args(["-u"])
+        .arg(&path)
+        .spawn().unwrap();
+    let mut client: option::Option<client::Client> = None;
+    for _ in 0..100 {
+        if let Ok(value) = client::Client::unix(&path) {
+            client = Some(value);
+            break;
+        }
+        ticks::sleep(ticks::frequency() / 100);
+    }
+    assert!(client.unwrap().admit("alpha", 1).unwrap());
+    child.kill().unwrap();
+    child.wait().unwrap();
+    /* Anything else is left alone and is an error. */
+    fs::remove_file(&path).unwrap();
+    fs::write(&path, "precious\n").unwrap();
+    let output: process::Output = process::Command::new(env!("CARGO_BIN_EXE_throttled"))
+        .args(["-u"])
+        .arg(&path)
+        .stdin(process::Stdio::null())
+        .output().unwrap();
+    let error: String = String::from_utf8_lossy(&output.stderr).to_string();
+    eprintln!("error={}", error);
+    assert!(output.status.code() == Some(1));
+    assert!(error.contains("not a socket"));
+    assert!(fs::read_to_string(&path).unwrap() == "precious\n");
+    let _ = fs::remove_file(&path);
+}
@@ -45,3 +45,123 @@ fn test_ticks_400_sleep() {
     assert!((after - before) >= frequency);
     assert!((after - before) <= (2 * frequency));
 }
+
+#[test]
+fn test_ticks_500_monotonic() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let before: ticks::Ticks = ticks::monotonic();
+    eprintln!("before={}", before);
+    ticks::sleep(frequency / 10);
+    let after: ticks::Ticks = ticks::monotonic();
+    eprintln!("after={}", after);
+    assert!(before <= after);
+    assert!((after - before) >= (frequency / 10));
+    assert!((after - before) <= (2 * frequency / 10));
+}
+
+/// sleeps sleeps until each of the specified number of successive deadlines
+/// separated by the specified interval, checking that it never wakes early,
+/// and returns the total elapsed ticks.
+fn sleeps(iterations: ticks::Ticks, interval: ticks::Ticks) -> ticks::Ticks {
+    let before: ticks::Ticks = ticks::now();
+    let mut deadline: ticks::Ticks = before;
+    let mut now: ticks::Ticks;
+    for _ in 0..iterations {
+        deadline += interval;
+        ticks::sleep_until(deadline);
+        now = ticks::now();
+        assert!(now >= deadline);
+    }
+    let after: ticks::Ticks = ticks::now();
+    eprintln!("elapsed={} drift={}", after - before, (after - before) - (iterations * interval));
+    after - before
+}
+
+/// spins spins until each of the specified number of successive deadlines
+/// separated by the specified interval, checking that it never wakes early,
+/// and returns the median lateness in ticks.
+fn spins(iterations: ticks::Ticks, interval: ticks::Ticks, window: ticks::Ticks) -> ticks::Ticks {
+    let mut deadline: ticks::Ticks = ticks::now();
+    let mut now: ticks::Ticks;
+    let mut lateness: Vec<ticks::Ticks> = Vec::new();
+    for _ in 0..iterations {
+        deadline += interval;
+        ticks::spin_until(deadline, window);
+        now = ticks::now();
+        assert!(now >= deadline);
+        lateness.push(now - deadline);
+    }
+    lateness.sort();
+    eprintln!("median={} maximum={}", lateness[lateness.len() / 2], lateness[lateness.len() - 1]);
+    lateness[lateness.len() / 2]
+}
+
+#[test]
+fn test_ticks_600_sleep_until() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let interval: ticks::Ticks = frequency / 1000;
+    /* Never early, and not wildly late even on a loaded host. */
+    let elapsed: ticks::Ticks = sleeps(200, interval);
+    assert!(elapsed >= (200 * interval));
+    assert!(elapsed <= (200 * interval + frequency));
+    /* A deadline in the past only yields. */
+    let before: ticks::Ticks = ticks::now();
+    ticks::sleep_until(before - frequency);
+    let after: ticks::Ticks = ticks::now();
+    assert!((after - before) < frequency);
+}
+
+#[test]
+fn test_ticks_650_target() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    /* The deadline is mapped from the now time base to the monotonic one. */
+    assert!(ticks::target(1000, 400, 7000) == Some(7600));
+    assert!(ticks::target(401, 400, 7000) == Some(7001));
+    /* A deadline at or before now has already passed. */
+    assert!(ticks::target(400, 400, 7000).is_none());
+    assert!(ticks::target(400 - frequency, 400, 7000).is_none());
+    /* Successive deadlines map to successive targets without drift. */
+    let interval: ticks::Ticks = frequency / 1000;
+    let mut deadline: ticks::Ticks = 400;
+    for ii in 1..=200 {
+        deadline += interval;
+        assert!(ticks::target(deadline, 400, 7000) == Some(7000 + (ii * interval)));
+    }
+    /* The conversion to a timespec splits seconds and nanoseconds. */
+    let timespec = ticks::timespec((3 * frequency) + (frequency / 4));
+    assert!(timespec.tv_sec == 3);
+    assert!(timespec.tv_nsec == 250_000_000);
+    let timespec = ticks::timespec(frequency - 1);
+    assert!(timespec.tv_sec == 0);
+    assert!(timespec.tv_nsec == 999_999_999);
+}
+
+#[test]
+#[ignore]
+fn test_ticks_660_sleep_until_precision() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let interval: ticks::Ticks = frequency / 1000;
+    /* No more than 20ms of drift over 200 sleeps on a quiet host. */
+    let elapsed: ticks::Ticks = sleeps(200, interval);
+    assert!(elapsed >= (200 * interval));
+    assert!(elapsed <= (200 * interval + frequency / 50));
+}
+
+#[test]
+fn test_ticks_700_spin_until() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let interval: ticks::Ticks = frequency / 1000;
+    let window: ticks::Ticks = frequency / 5000;
+    /* Never early, and typically within the interval even on a loaded host. */
+    assert!(spins(200, interval, window) < interval);
+}
+
+#[test]
+#[ignore]
+fn test_ticks_760_spin_until_precision() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let interval: ticks::Ticks = frequency / 1000;
+    let window: ticks::Ticks = frequency / 5000;
+    /* A median lateness under 100us on a quiet host. */
+    assert!(spins(200, interval, window) < (frequency / 10000));
+}
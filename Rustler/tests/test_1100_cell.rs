@@ -0,0 +1,120 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::gcra::gcra;
+use rustler::cell::cell;
+
+#[test]
+fn test_cell_050_sanity() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    assert!(cell::increment(30, 60) == (2 * frequency));
+    assert!(cell::limit(2 * frequency, 15) == (30 * frequency));
+    assert!(cell::limit(2 * frequency, 0) == 0);
+}
+
+#[test]
+fn test_cell_100_readme() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let now: ticks::Ticks = ticks::now();
+    let mut gcra: gcra::Gcra = gcra::Gcra::new();
+    let mut reply: cell::Reply;
+    /* CL.THROTTLE user123 15 30 60 1 */
+    reply = cell::throttle(& mut gcra, 15, 30, 60, 1, now).unwrap();
+    eprintln!("reply={:?}", reply);
+    assert!(reply == (false, 16, 15, -1, 2));
+    /* The rest of the burst at the same instant. */
+    for remaining in (0..15).rev() {
+        reply = cell::throttle(& mut gcra, 15, 30, 60, 1, now).unwrap();
+        assert!(reply == (false, 16, remaining, -1, 2 * (16 - remaining)));
+    }
+    reply = cell::throttle(& mut gcra, 15, 30, 60, 1, now).unwrap();
+    eprintln!("reply={:?}", reply);
+    assert!(reply == (true, 16, 0, 2, 32));
+    /* Limited requests do not alter the throttle. */
+    reply = cell::throttle(& mut gcra, 15, 30, 60, 1, now + frequency).unwrap();
+    assert!(reply == (true, 16, 0, 1, 31));
+    reply = cell::throttle(& mut gcra, 15, 30, 60, 1, now + (2 * frequency)).unwrap();
+    assert!(reply == (false, 16, 0, -1, 32));
+}
+
+#[test]
+fn test_cell_200_cases() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let start: ticks::Ticks = ticks::now();
+    let milliseconds: ticks::Ticks = frequency / 1000;
+    let mut gcra: gcra::Gcra = gcra::Gcra::new();
+    let mut reply: cell::Reply;
+    /* (now, quantity, remaining, reset after, retry after, limited) from the redis-cell test suite */
+    let cases: [(ticks::Ticks, throttle::Events, throttle::Events, i64, i64, bool); 15] = [
+        /* A request can never be larger than the maximum. */
+        (0, 6, 5, 0, -1, true),
+        /* Normal requests are limited appropriately. */
+        (0, 1, 4, 1, -1, false),
+        (0, 1, 3, 2, -1, false),
+        (0, 1, 2, 3, -1, false),
+        (0, 1, 1, 4, -1, false),
+        (0, 1, 0, 5, -1, false),
+        (0, 1, 0, 5, 1, true),
+        (3000, 1, 2, 3, -1, false),
+        (3100, 1, 1, 4, -1, false),
+        (4000, 1, 1, 4, -1, false),
+        (8000, 1, 4, 1, -1, false),
+        (9500, 1, 4, 1, -1, false),
+        /* A request of zero events just peeks at the state. */
+        (9500, 0, 4, 1, -1, false),
+        /* A request of many events uses up more of the limit. */
+        (9500, 2, 2, 3, -1, false),
+        /* A request of many events cannot exceed the limit. */
+        (9500, 5, 2, 3, 3, true),
+    ];
+    for (index, case) in cases.iter().enumerate() {
+        reply = cell::throttle(& mut gcra, 4, 1, 1, case.1, start + (case.0 * milliseconds)).unwrap();
+        eprintln!("case={} reply={:?}", index, reply);
+        assert!(reply.0 == case.5);
+        assert!(reply.1 == 5);
+        assert!(reply.2 == case.2);
+        assert!(reply.3 == case.4);
+        assert!(reply.4 == case.3);
+    }
+}
+
+#[test]
+fn test_cell_300_reconfigure() {
+    let now: ticks::Ticks = ticks::now();
+    let mut gcra: gcra::Gcra = gcra::Gcra::new();
+    let mut reply: cell::Reply;
+    reply = cell::throttle(& mut gcra, 0, 1, 10, 1, now).unwrap();
+    assert!(reply == (false, 1, 0, -1, 10));
+    reply = cell::throttle(& mut gcra, 0, 1, 10, 1, now).unwrap();
+    assert!(reply == (true, 1, 0, 10, 10));
+    /* A larger burst admits more without losing what was admitted. */
+    reply = cell::throttle(& mut gcra, 1, 1, 10, 1, now).unwrap();
+    assert!(reply == (false, 2, 0, -1, 20));
+}
+
+#[test]
+fn test_cell_400_invalid() {
+    let now: ticks::Ticks = ticks::now();
+    let mut gcra: gcra::Gcra = gcra::Gcra::new();
+    assert!(cell::throttle(& mut gcra, -1, 1, 10, 1, now).is_err());
+    assert!(cell::throttle(& mut gcra, 0, 0, 10, 1, now).is_err());
+    assert!(cell::throttle(& mut gcra, 0, 1, 0, 1, now).is_err());
+    assert!(cell::throttle(& mut gcra, 0, 1, 10, -1, now).is_err());
+    assert!(cell::throttle(& mut gcra, 0, 1, i64::max_value(), 1, now).is_err());
+    assert!(cell::throttle(& mut gcra, i64::max_value(), 1, 10, 1, now).is_err());
+    assert!(cell::throttle(& mut gcra, 0, 1, 10, i64::max_value(), now).is_err());
+    /* None of the errors altered the throttle. */
+    assert!(gcra.checkpoint(now).increment == 0);
+    assert!(cell::throttle(& mut gcra, 0, 1, 10, 1, now).unwrap() == (false, 1, 0, -1, 10));
+    let error: std::io::Error = cell::throttle(& mut gcra, 0, 1, 10, i64::max_value(), now).unwrap_err();
+    assert!(error.to_string() == "quantity not valid");
+}
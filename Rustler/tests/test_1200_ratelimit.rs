@@ -0,0 +1,59 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::ratelimit::ratelimit;
+
+#[test]
+fn test_ratelimit_050_sanity() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    assert!(ratelimit::seconds(-1) == 0);
+    assert!(ratelimit::seconds(0) == 0);
+    assert!(ratelimit::seconds(1) == 1);
+    assert!(ratelimit::seconds(frequency) == 1);
+    assert!(ratelimit::seconds(frequency + 1) == 2);
+}
+
+#[test]
+fn test_ratelimit_100_gcra() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(1, 2, frequency);
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(increment, gcra::jittertolerance(increment, 3), 0);
+    let mut headers: String;
+    headers = ratelimit::headers(throttle.get_burstsize(), throttle.get_remaining(0), throttle.get_retry(0), throttle.get_reset(0));
+    print!("{}", headers);
+    assert!(headers == "RateLimit-Limit: 3\r\nRateLimit-Remaining: 3\r\nRateLimit-Reset: 0\r\n");
+    assert!(throttle.admits(0, 3));
+    headers = ratelimit::headers(throttle.get_burstsize(), throttle.get_remaining(0), throttle.get_retry(0), throttle.get_reset(0));
+    print!("{}", headers);
+    assert!(headers == "RateLimit-Limit: 3\r\nRateLimit-Remaining: 0\r\nRateLimit-Reset: 6\r\nRetry-After: 2\r\n");
+    let fields = ratelimit::fields(throttle.get_burstsize(), throttle.get_remaining(1), throttle.get_retry(1), throttle.get_reset(1));
+    assert!(fields.len() == 4);
+    assert!(fields[0] == (ratelimit::LIMIT, "3".to_string()));
+    assert!(fields[1] == (ratelimit::REMAINING, "0".to_string()));
+    assert!(fields[2] == (ratelimit::RESET, "6".to_string()));
+    assert!(fields[3] == (ratelimit::RETRY, "2".to_string()));
+}
+
+#[test]
+fn test_ratelimit_200_contract() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(10, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(1, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, 10);
+    let mut throttle: contract::Contract = contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, 0);
+    assert!(throttle.admit(0));
+    let headers: String = ratelimit::headers(throttle.get_burstsize(), throttle.get_remaining(0), throttle.get_retry(0), throttle.get_reset(0));
+    print!("{}", headers);
+    assert!(headers == "RateLimit-Limit: 10\r\nRateLimit-Remaining: 9\r\nRateLimit-Reset: 1\r\n");
+}
@@ -0,0 +1,126 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::env;
+use std::fs;
+use std::path;
+use std::process;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::trace::trace;
+
+/// Record a trace of a policed event stream with the specified contract
+/// parameters, returning the trace as text.
+fn record(peakrate: throttle::Events, sustainedrate: throttle::Events, burstsize: throttle::Events) -> String {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize);
+    let mut now: ticks::Ticks = 1_000_000_000;
+    let throttle: contract::Contract = contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, now);
+    let mut recorder: trace::Recorder<contract::Contract, Vec<u8>> = trace::Recorder::new(throttle, Vec::new());
+    for ii in 0..1000 {
+        now += ((ii * 7919) % (2 * peakincrement)) as ticks::Ticks;
+        match ii % 5 {
+            0 => { if recorder.request(now) == 0 { recorder.commit(); } },
+            1 => { recorder.request(now); recorder.commits(2); },
+            2 => { recorder.admit(now); },
+            3 => { recorder.admits(now, 3); },
+            _ => { recorder.update(now); },
+        }
+    }
+    recorder.reset(now);
+    recorder.admit(now);
+    assert!(recorder.failures() == 0);
+    let (_, output) = recorder.into_inner();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_trace_050_sanity() {
+    let record: trace::Record = trace::Record { method: trace::Method::Admits, now: 12345, events: 3, result: 1, expected: 678, empty: false, full: true, alarmed: false };
+    let line: String = record.to_string();
+    println!("line={}", line);
+    assert!(line == "admits,12345,3,1,678,0,1,0");
+    assert!(trace::Record::parse(&line) == Some(record));
+    assert!(trace::Record::parse(trace::HEADER).is_none());
+    assert!(trace::Record::parse("admits,12345,3,1,678,0,1").is_none());
+    assert!(trace::Record::parse("emits,12345,3,1,678,0,1,0").is_none());
+    for name in ["reset", "request", "commits", "commit", "admits", "admit", "update"].iter() {
+        assert!(trace::Method::parse(name).unwrap().name() == *name);
+    }
+}
+
+#[test]
+fn test_trace_100_identical() {
+    let text: String = record(1000, 500, 10);
+    let mut lines = text.lines();
+    assert!(lines.next() == Some(trace::HEADER));
+    let mut throttle: Option<contract::Contract> = None;
+    let mut count: usize = 0;
+    for line in lines {
+        let record: trace::Record = trace::Record::parse(line).unwrap();
+        if throttle.is_none() {
+            let frequency: ticks::Ticks = ticks::frequency();
+            let peakincrement: ticks::Ticks = gcra::increment(1000, 1, frequency);
+            let sustainedincrement: ticks::Ticks = gcra::increment(500, 1, frequency);
+            let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, 10);
+            throttle = Some(contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, 1_000_000_000));
+        }
+        let replayed: trace::Record = trace::replay(throttle.as_mut().unwrap(), &record);
+        assert!(replayed == record);
+        count += 1;
+    }
+    eprintln!("count={}", count);
+    assert!(count > 1000);
+}
+
+#[test]
+fn test_trace_200_different() {
+    let text: String = record(1000, 500, 10);
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(500, 1, frequency);
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(increment, 0, 1_000_000_000);
+    let mut differences: usize = 0;
+    for line in text.lines().skip(1) {
+        let record: trace::Record = trace::Record::parse(line).unwrap();
+        let replayed: trace::Record = trace::replay(& mut throttle, &record);
+        if !record.is_decision(&replayed) {
+            differences += 1;
+        }
+    }
+    eprintln!("differences={}", differences);
+    assert!(differences > 0);
+}
+
+#[test]
+fn test_trace_300_replay() {
+    let mut path: path::PathBuf = env::temp_dir();
+    path.push(format!("rustler-{}-300.csv", process::id()));
+    fs::write(&path, record(1000, 500, 10)).unwrap();
+    let same = process::Command::new(env!("CARGO_BIN_EXE_replay"))
+        .args(["-p", "1000", "-s", "500", "-b", "10"])
+        .stdin(fs::File::open(&path).unwrap())
+        .output().unwrap();
+    assert!(same.status.success());
+    assert!(same.stdout.is_empty());
+    let different = process::Command::new(env!("CARGO_BIN_EXE_replay"))
+        .args(["-p", "500", "-b", "1"])
+        .stdin(fs::File::open(&path).unwrap())
+        .output().unwrap();
+    let text: String = String::from_utf8_lossy(&different.stdout).into_owned();
+    eprintln!("differences={}", text.lines().count());
+    assert!(!different.status.success());
+    assert!(text.lines().count() > 0);
+    let _ = fs::remove_file(&path);
+}
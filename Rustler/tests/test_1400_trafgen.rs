@@ -0,0 +1,126 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::process;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::trace::trace;
+use rustler::trafgen::trafgen;
+
+mod harness;
+
+/// Generate the specified number of emissions and return the mean rate in
+/// events per second.
+fn rate(generator: & mut trafgen::Generator, emissions: usize) -> f64 {
+    let mut total: throttle::Events = 0;
+    let mut last: ticks::Ticks = 0;
+    for (offset, events) in generator.take(emissions) {
+        assert!(offset >= last);
+        last = offset;
+        total += events;
+    }
+    (total as f64) * (ticks::frequency() as f64) / (last as f64)
+}
+
+#[test]
+fn test_trafgen_050_sanity() {
+    let one: Vec<(ticks::Ticks, throttle::Events)> = trafgen::Generator::new(trafgen::Pattern::Poisson, 1000.0, 100, 42).take(100).collect();
+    let two: Vec<(ticks::Ticks, throttle::Events)> = trafgen::Generator::new(trafgen::Pattern::Poisson, 1000.0, 100, 42).take(100).collect();
+    let three: Vec<(ticks::Ticks, throttle::Events)> = trafgen::Generator::new(trafgen::Pattern::Poisson, 1000.0, 100, 43).take(100).collect();
+    assert!(one == two);
+    assert!(one != three);
+    let mut generator: trafgen::Generator = trafgen::Generator::new(trafgen::Pattern::Constant, 1000.0, 100, 42);
+    println!("generator={}", generator.to_string());
+    let mut buffer = [0u8; 256];
+    generator.fill(& mut buffer);
+    for byte in buffer.iter() {
+        assert!((b' ' <= *byte) && (*byte <= b'~'));
+    }
+}
+
+#[test]
+fn test_trafgen_100_constant() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut generator: trafgen::Generator = trafgen::Generator::new(trafgen::Pattern::Constant, 1000.0, 100, 1);
+    assert!(generator.next() == Some((0, 100)));
+    assert!(generator.next() == Some((frequency / 10, 100)));
+    assert!(generator.next() == Some((2 * frequency / 10, 100)));
+    let rate: f64 = rate(& mut trafgen::Generator::new(trafgen::Pattern::Constant, 3000.0, 7, 1), 10001);
+    eprintln!("constant: rate={}", rate);
+    assert!(harness::fabs(rate - 3000.0) < 1.0);
+}
+
+#[test]
+fn test_trafgen_200_poisson() {
+    let rate: f64 = rate(& mut trafgen::Generator::new(trafgen::Pattern::Poisson, 2048.0, 64, 1), 100000);
+    eprintln!("poisson: rate={}", rate);
+    assert!(harness::fabs(rate - 2048.0) < (2048.0 / 50.0));
+}
+
+#[test]
+fn test_trafgen_300_onoff() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let pattern: trafgen::Pattern = trafgen::Pattern::OnOff { on: frequency / 10, off: frequency / 5 };
+    let rate: f64 = rate(& mut trafgen::Generator::new(pattern.clone(), 2048.0, 64, 1), 100000);
+    eprintln!("onoff: rate={}", rate);
+    assert!(harness::fabs(rate - 2048.0) < (2048.0 / 20.0));
+    /* During an on period blocks are at the peak rate, three times the mean. */
+    let emissions: Vec<(ticks::Ticks, throttle::Events)> = trafgen::Generator::new(pattern, 2048.0, 64, 1).take(1000).collect();
+    let peak: ticks::Ticks = gcra::increment(3 * 2048, 64, frequency);
+    let mut gaps: usize = 0;
+    for pair in emissions.windows(2) {
+        let interval: ticks::Ticks = pair[1].0 - pair[0].0;
+        assert!(interval >= (peak - 1));
+        if interval > peak { gaps += 1; }
+    }
+    eprintln!("onoff: gaps={}", gaps);
+    assert!(gaps > 0);
+}
+
+#[test]
+fn test_trafgen_400_trace() {
+    let throttle: gcra::Gcra = gcra::Gcra::new().init(100, 0, 1000);
+    let mut recorder: trace::Recorder<gcra::Gcra, Vec<u8>> = trace::Recorder::new(throttle, Vec::new());
+    recorder.admits(1000, 3);
+    recorder.update(1100);
+    recorder.admit(1200);
+    recorder.request(1500);
+    recorder.commits(5);
+    let (_, output) = recorder.into_inner();
+    let emissions: Vec<(ticks::Ticks, throttle::Events)> = trafgen::load(&output[..]).unwrap();
+    eprintln!("trace: emissions={:?}", emissions);
+    assert!(emissions == vec![(0, 3), (200, 1), (500, 5)]);
+    let generated: Vec<(ticks::Ticks, throttle::Events)> = trafgen::Generator::new(trafgen::Pattern::Trace(emissions.clone()), 0.0, 0, 1).collect();
+    assert!(generated == emissions);
+}
+
+#[test]
+fn test_trafgen_500_binary() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let before: ticks::Ticks = ticks::now();
+    let one = process::Command::new(env!("CARGO_BIN_EXE_trafgen"))
+        .args(["-r", "100000", "-b", "100", "-n", "10050", "-m", "poisson", "-x", "7"])
+        .output().unwrap();
+    let after: ticks::Ticks = ticks::now();
+    assert!(one.status.success());
+    assert!(one.stdout.len() == 10050);
+    eprintln!("binary: duration={}s", ((after - before) as f64) / (frequency as f64));
+    assert!((after - before) >= (frequency / 20));
+    let two = process::Command::new(env!("CARGO_BIN_EXE_trafgen"))
+        .args(["-r", "100000", "-b", "100", "-n", "10050", "-m", "poisson", "-x", "7"])
+        .output().unwrap();
+    assert!(one.stdout == two.stdout);
+    let three = process::Command::new(env!("CARGO_BIN_EXE_trafgen"))
+        .args(["-r", "100000", "-b", "100", "-n", "10050", "-m", "poisson", "-x", "8"])
+        .output().unwrap();
+    assert!(one.stdout != three.stdout);
+}
@@ -0,0 +1,89 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::env;
+use rustler::random::random;
+
+mod harness;
+
+#[test]
+fn test_random_050_sanity() {
+    let mut one: random::Random = random::Random::new(42);
+    let mut two: random::Random = random::Random::new(42);
+    let mut three: random::Random = random::Random::new(43);
+    println!("one={}", one.to_string());
+    assert!(one.get_seed() == 42);
+    let mut same: bool = true;
+    for _ in 0..1000 {
+        let value: u64 = one.generate();
+        assert!(value == two.generate());
+        if value != three.generate() { same = false; }
+    }
+    assert!(!same);
+    let mut zero: random::Random = random::Random::new(0);
+    assert!(zero.get_seed() == 0);
+    assert!(zero.generate() != 0);
+}
+
+#[test]
+fn test_random_100_range() {
+    let mut random: random::Random = random::Random::new(1);
+    let mut seen = [false; 10];
+    for _ in 0..10000 {
+        let value: u64 = random.range(5, 14);
+        assert!((5..=14).contains(&value));
+        seen[(value - 5) as usize] = true;
+    }
+    assert!(seen.iter().all(|seen| *seen));
+    for _ in 0..100 {
+        assert!(random.range(7, 7) == 7);
+    }
+    random.range(0, u64::max_value());
+    let mut buffer = [0u8; 1024];
+    random.fill(& mut buffer);
+    for byte in buffer.iter() {
+        assert!((b' ' <= *byte) && (*byte <= b'~'));
+    }
+}
+
+#[test]
+fn test_random_200_distributions() {
+    let mut random: random::Random = random::Random::new(1);
+    let mut uniform: f64 = 0.0;
+    let mut exponential: f64 = 0.0;
+    for _ in 0..100000 {
+        let value: f64 = random.uniform();
+        assert!((0.0..1.0).contains(&value));
+        uniform += value;
+        let value: f64 = random.exponential(10.0);
+        assert!(value >= 0.0);
+        exponential += value;
+    }
+    uniform /= 100000.0;
+    exponential /= 100000.0;
+    eprintln!("uniform={} exponential={}", uniform, exponential);
+    assert!(harness::fabs(uniform - 0.5) < 0.01);
+    assert!(harness::fabs(exponential - 10.0) < 0.2);
+}
+
+#[test]
+fn test_random_300_seed() {
+    env::set_var(random::SEED, "12345");
+    assert!(random::seed() == 12345);
+    env::set_var(random::SEED, "invalid");
+    let seed: u64 = random::seed();
+    eprintln!("seed={}", seed);
+    env::remove_var(random::SEED);
+    assert!(harness::seed() == harness::seed());
+    let size: usize = harness::blocksize(512);
+    assert!((1..=512).contains(&size));
+    let byte: u8 = harness::payload(10);
+    assert!((1..=10).contains(&byte));
+}
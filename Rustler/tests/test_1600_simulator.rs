@@ -0,0 +1,169 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::shaper::shaper;
+use rustler::trafgen::trafgen;
+use rustler::simulator::simulator;
+
+mod harness;
+
+const START: ticks::Ticks = 1_000_000_000;
+
+fn gcra(rate: throttle::Events, burstsize: throttle::Events) -> gcra::Gcra {
+    let increment: ticks::Ticks = gcra::increment(rate, 1, ticks::frequency());
+    gcra::Gcra::new().init(increment, gcra::jittertolerance(increment, burstsize), START)
+}
+
+fn contract(peakrate: throttle::Events, sustainedrate: throttle::Events, burstsize: throttle::Events) -> contract::Contract {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize);
+    contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, START)
+}
+
+#[test]
+fn test_simulator_050_sanity() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut simulator: simulator::Simulator = simulator::Simulator::new(START);
+    let source: simulator::Node = simulator.add_source(trafgen::Generator::new(trafgen::Pattern::Constant, 1000.0, 100, 1), 10 * frequency);
+    let sink: simulator::Node = simulator.add_sink();
+    simulator.connect(source, sink);
+    println!("simulator={}", simulator.to_string());
+    assert!(simulator.nodes() == 2);
+    assert!(simulator.throttle(source).is_none());
+    let before: ticks::Ticks = ticks::now();
+    let actions: u64 = simulator.run(START + 20 * frequency);
+    let after: ticks::Ticks = ticks::now();
+    assert!(simulator.is_idle());
+    assert!(simulator.now() == (START + 20 * frequency));
+    assert!(simulator.elapsed() == (20 * frequency));
+    assert!((after - before) < frequency);
+    let statistics: simulator::Statistics = *simulator.statistics(source);
+    eprintln!("actions={} source={:?}", actions, statistics);
+    assert!(statistics.departures == 101);
+    assert!(statistics.sent == 10100);
+    let statistics: simulator::Statistics = *simulator.statistics(sink);
+    eprintln!("sink={:?}", statistics);
+    assert!(statistics.arrivals == 101);
+    assert!(statistics.departures == 101);
+    assert!(statistics.drops == 0);
+    assert!(statistics.delay == 0);
+    assert!(harness::fabs(statistics.throughput(10 * frequency) - 1010.0) < 1.0);
+}
+
+#[test]
+fn test_simulator_100_shaper() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut simulator: simulator::Simulator = simulator::Simulator::new(START);
+    let source: simulator::Node = simulator.add_source(trafgen::Generator::new(trafgen::Pattern::Poisson, 4000.0, 100, 1), 100 * frequency);
+    let shaper: simulator::Node = simulator.add_shaper(Box::new(gcra(2000, 100)), 1000, shaper::Policy::TailDrop);
+    let sink: simulator::Node = simulator.add_sink();
+    simulator.connect(source, shaper);
+    simulator.connect(shaper, sink);
+    simulator.run(START + 101 * frequency);
+    let offered: simulator::Statistics = *simulator.statistics(source);
+    let shaped: simulator::Statistics = *simulator.statistics(shaper);
+    let consumed: simulator::Statistics = *simulator.statistics(sink);
+    eprintln!("source={:?}", offered);
+    eprintln!("shaper={:?}", shaped);
+    eprintln!("sink={:?}", consumed);
+    assert!(shaped.arrivals == offered.departures);
+    assert!(shaped.received >= (shaped.sent + shaped.dropped));
+    assert!(shaped.drops > 0);
+    assert!(consumed.arrivals == shaped.departures);
+    let throughput: f64 = consumed.throughput(100 * frequency);
+    eprintln!("throughput={} mean={}s maximum={}s", throughput, shaped.mean() / (frequency as f64), (shaped.maximum as f64) / (frequency as f64));
+    assert!(harness::fabs(throughput - 2000.0) < 20.0);
+    assert!(shaped.maximum <= (frequency / 2));
+    assert!(simulator.throttle(shaper).is_some());
+}
+
+#[test]
+fn test_simulator_200_policer() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut simulator: simulator::Simulator = simulator::Simulator::new(START);
+    let source: simulator::Node = simulator.add_source(trafgen::Generator::new(trafgen::Pattern::Poisson, 800.0, 100, 1), 100 * frequency);
+    let policer: simulator::Node = simulator.add_policer(Box::new(gcra(1000, 100)));
+    let sink: simulator::Node = simulator.add_sink();
+    simulator.connect(source, policer);
+    simulator.connect(policer, sink);
+    simulator.run(START + 100 * frequency);
+    let policed: simulator::Statistics = *simulator.statistics(policer);
+    let consumed: simulator::Statistics = *simulator.statistics(sink);
+    eprintln!("policer={:?}", policed);
+    eprintln!("sink={:?}", consumed);
+    assert!(policed.received == (policed.sent + policed.dropped));
+    assert!(policed.drops > 0);
+    assert!(policed.delay == 0);
+    assert!(consumed.throughput(100 * frequency) < 800.0);
+}
+
+#[test]
+fn test_simulator_300_link() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut simulator: simulator::Simulator = simulator::Simulator::new(START);
+    let source: simulator::Node = simulator.add_source(trafgen::Generator::new(trafgen::Pattern::Constant, 1000.0, 100, 1), 10 * frequency);
+    let link: simulator::Node = simulator.add_link(10000.0, frequency / 200);
+    let sink: simulator::Node = simulator.add_sink();
+    simulator.connect(source, link);
+    simulator.connect(link, sink);
+    simulator.run(START + 11 * frequency);
+    let linked: simulator::Statistics = *simulator.statistics(link);
+    let consumed: simulator::Statistics = *simulator.statistics(sink);
+    eprintln!("link={:?}", linked);
+    eprintln!("sink={:?}", consumed);
+    assert!(linked.departures == 101);
+    assert!(linked.maximum == (frequency / 100 + frequency / 200));
+    assert!(consumed.maximum == linked.maximum);
+    /* Two sources sharing the link queue behind one another. */
+    let mut simulator: simulator::Simulator = simulator::Simulator::new(START);
+    let one: simulator::Node = simulator.add_source(trafgen::Generator::new(trafgen::Pattern::Constant, 1000.0, 100, 1), 10 * frequency);
+    let two: simulator::Node = simulator.add_source(trafgen::Generator::new(trafgen::Pattern::Constant, 1000.0, 100, 2), 10 * frequency);
+    let link: simulator::Node = simulator.add_link(10000.0, 0);
+    simulator.connect(one, link);
+    simulator.connect(two, link);
+    simulator.run(START + 11 * frequency);
+    let linked: simulator::Statistics = *simulator.statistics(link);
+    eprintln!("link={:?}", linked);
+    assert!(linked.departures == 202);
+    assert!(linked.maximum == (2 * frequency / 100));
+}
+
+#[test]
+fn test_simulator_400_contract() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut simulator: simulator::Simulator = simulator::Simulator::new(START);
+    let pattern: trafgen::Pattern = trafgen::Pattern::OnOff { on: frequency / 10, off: frequency / 5 };
+    let one: simulator::Node = simulator.add_source(trafgen::Generator::new(pattern.clone(), 400.0, 64, 1), 60 * frequency);
+    let two: simulator::Node = simulator.add_source(trafgen::Generator::new(pattern, 400.0, 64, 2), 60 * frequency);
+    let shaper: simulator::Node = simulator.add_shaper(Box::new(contract(4096, 1024, 512)), 65536, shaper::Policy::TailDrop);
+    let policer: simulator::Node = simulator.add_policer(Box::new(contract(4096, 1024, 512)));
+    let sink: simulator::Node = simulator.add_sink();
+    simulator.connect(one, shaper);
+    simulator.connect(two, shaper);
+    simulator.connect(shaper, policer);
+    simulator.connect(policer, sink);
+    simulator.run(START + 120 * frequency);
+    let shaped: simulator::Statistics = *simulator.statistics(shaper);
+    let policed: simulator::Statistics = *simulator.statistics(policer);
+    let consumed: simulator::Statistics = *simulator.statistics(sink);
+    eprintln!("shaper={:?}", shaped);
+    eprintln!("policer={:?}", policed);
+    eprintln!("sink={:?}", consumed);
+    assert!(shaped.drops == 0);
+    assert!(policed.drops == 0);
+    assert!(consumed.received == (simulator.statistics(one).sent + simulator.statistics(two).sent));
+    assert!(consumed.throughput(simulator.elapsed()) <= 1024.0);
+}
@@ -0,0 +1,75 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+#[cfg(feature = "conformance")]
+use rustler::ticks::ticks;
+#[cfg(feature = "conformance")]
+use rustler::gcra::gcra;
+#[cfg(feature = "conformance")]
+use rustler::contract::contract;
+#[cfg(feature = "conformance")]
+use rustler::bucket::bucket;
+#[cfg(feature = "conformance")]
+use rustler::conformance::conformance;
+
+#[cfg(feature = "conformance")]
+#[test]
+fn test_conformance_100_gcra() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let limit: ticks::Ticks = gcra::jittertolerance(increment, 8);
+    let factory = |now: ticks::Ticks| gcra::Gcra::new().init(increment, limit, now);
+    for seed in 1..4 {
+        let failures: Vec<String> = conformance::conforms(&factory, 1000.0, seed);
+        eprintln!("gcra: seed={} failures={:?}", seed, failures);
+        assert!(failures.is_empty());
+    }
+}
+
+#[cfg(feature = "conformance")]
+#[test]
+fn test_conformance_200_contract() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(2000, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, 16);
+    let factory = |now: ticks::Ticks| contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, now);
+    for seed in 1..4 {
+        let failures: Vec<String> = conformance::conforms(&factory, 1000.0, seed);
+        eprintln!("contract: seed={} failures={:?}", seed, failures);
+        assert!(failures.is_empty());
+    }
+}
+
+#[cfg(feature = "conformance")]
+#[test]
+fn test_conformance_300_violation() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let factory = |now: ticks::Ticks| gcra::Gcra::new().init(increment, 0, now);
+    assert!(conformance::sustained(&factory, 1000.0, 1, 10000, 0.01).is_ok());
+    let failure = conformance::sustained(&factory, 2000.0, 1, 10000, 0.01);
+    eprintln!("violation: {:?}", failure);
+    assert!(failure.is_err());
+}
+
+#[cfg(feature = "conformance")]
+#[test]
+fn test_conformance_400_bucket() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let limit: ticks::Ticks = gcra::jittertolerance(increment, 8);
+    let factory = |now: ticks::Ticks| bucket::Bucket::new(increment, limit, now);
+    for seed in 1..4 {
+        let failures: Vec<String> = conformance::conforms(&factory, 1000.0, seed);
+        eprintln!("bucket: seed={} failures={:?}", seed, failures);
+        assert!(failures.is_empty());
+    }
+}
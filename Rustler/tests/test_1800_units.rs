@@ -0,0 +1,227 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::time;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::bucket::bucket;
+use rustler::units::units;
+
+#[test]
+fn test_units_050_ticks() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let one: units::Ticks = units::Ticks::seconds(1);
+    assert!(one == units::Ticks(frequency));
+    assert!((one + one) == units::Ticks(2 * frequency));
+    assert!((one - one) == units::Ticks::ZERO);
+    assert!((one * 3) == units::Ticks(3 * frequency));
+    assert!((one / 4) == units::Ticks(frequency / 4));
+    let mut sum: units::Ticks = units::Ticks::ZERO;
+    sum += one;
+    sum -= one / 2;
+    assert!(sum == units::Ticks(frequency / 2));
+    assert!(units::Ticks(i64::max_value()).checked_add(units::Ticks(1)).is_none());
+    assert!(units::Ticks(i64::min_value()).checked_sub(units::Ticks(1)).is_none());
+    assert!(units::Ticks(i64::max_value()).checked_mul(2).is_none());
+    assert!(one.checked_div(0).is_none());
+    let raw: ticks::Ticks = one.into();
+    assert!(raw == frequency);
+    assert!(units::Ticks::from(raw) == one);
+    assert!(units::Ticks::now() >= units::Ticks::ZERO);
+}
+
+#[test]
+#[should_panic(expected = "Ticks overflow")]
+fn test_units_060_overflow() {
+    let _ = units::Ticks(i64::max_value()) + units::Ticks(1);
+}
+
+#[test]
+fn test_units_100_duration() {
+    let duration: time::Duration = time::Duration::new(2, 500_000_000);
+    let ticks: units::Ticks = units::Ticks::from_duration(duration).unwrap();
+    assert!(ticks == units::Ticks(2 * ticks::frequency() + ticks::frequency() / 2));
+    assert!(ticks.to_duration() == Some(duration));
+    assert!(units::Ticks(-1).to_duration().is_none());
+    assert!(units::Ticks::from_duration(time::Duration::new(u64::max_value(), 0)).is_none());
+    assert!((ticks.as_secs_f64() - 2.5).abs() < 0.000_000_001);
+}
+
+#[test]
+fn test_units_150_display() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    assert!(units::Ticks(3 * frequency / 2).to_string() == "1.5s");
+    assert!(units::Ticks(frequency / 4).to_string() == "250ms");
+    assert!(units::Ticks(frequency / 100_000).to_string() == "10us");
+    assert!(units::Ticks(frequency / 1_000_000_000 * 7).to_string() == "7ns");
+    assert!(units::Ticks(-2 * frequency).to_string() == "-2s");
+    assert!(units::Ticks::ZERO.to_string() == "0ns");
+    assert!(units::Events(1).to_string() == "1 event");
+    assert!(units::Events(512).to_string() == "512 events");
+    assert!(units::Rate::per_second(1024).unwrap().to_string() == "1024 events/s");
+    assert!(units::Rate::new(units::Events(1), units::Ticks(frequency / 2)).unwrap().to_string() == "2 events/s");
+}
+
+#[test]
+fn test_units_200_events() {
+    let mut events: units::Events = units::Events(3) + units::Events(4);
+    assert!(events == units::Events(7));
+    events -= units::Events(2);
+    events += units::Events(1);
+    assert!(events * 2 == units::Events(12));
+    assert!(units::Events(i64::max_value()).checked_add(units::Events(1)).is_none());
+    let raw: throttle::Events = events.into();
+    assert!(raw == 6);
+}
+
+#[test]
+fn test_units_300_rate() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let rate: units::Rate = units::Rate::per_second(1024).unwrap();
+    assert!(rate.get_events() == units::Events(1024));
+    assert!(rate.get_per() == units::Ticks::seconds(1));
+    assert!(rate.increment() == units::Ticks(gcra::increment(1024, 1, frequency)));
+    let rate: units::Rate = units::Rate::per_duration(3, time::Duration::from_millis(500)).unwrap();
+    assert!(rate.increment() == units::Ticks(gcra::increment(6, 1, frequency)));
+    assert!((rate.per_second_f64() - 6.0).abs() < 0.000_001);
+    assert!(units::Rate::per_second(0).is_none());
+    assert!(units::Rate::per_second(-1).is_none());
+    assert!(units::Rate::new(units::Events(1), units::Ticks::ZERO).is_none());
+    assert!(units::Rate::new(units::Events(1), units::Ticks(-1)).is_none());
+    assert!(units::Rate::new(units::Events(0), units::Ticks(1)).is_none());
+    assert!(units::Rate::per_duration(1, time::Duration::from_secs(0)).is_none());
+    assert!(units::Rate::per_duration(1, time::Duration::from_secs(u64::max_value())).is_none());
+    assert!(units::Rate::per_duration(0, time::Duration::from_secs(1)).is_none());
+}
+
+#[test]
+fn test_units_400_constructors() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let now: ticks::Ticks = ticks::now();
+    let typed: gcra::Gcra = gcra::Gcra::with(units::Rate::per_second(1000).unwrap(), units::Events(8), units::Ticks(now));
+    let increment: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let untyped: gcra::Gcra = gcra::Gcra::new().init(increment, gcra::jittertolerance(increment, 8), now);
+    assert!(typed.get_increment() == untyped.get_increment());
+    assert!(typed.get_limit() == untyped.get_limit());
+    assert!(typed.peek(now, 10) == untyped.peek(now, 10));
+    let typed: contract::Contract = contract::Contract::with(units::Rate::per_second(2000).unwrap(), units::Ticks::ZERO, units::Rate::per_second(1000).unwrap(), units::Events(16), units::Ticks(now));
+    let peakincrement: ticks::Ticks = gcra::increment(2000, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let untyped: contract::Contract = contract::Contract::new().init(peakincrement, 0, sustainedincrement, contract::bursttolerance(peakincrement, 0, sustainedincrement, 16), now);
+    assert!(typed.get_burstsize() == untyped.get_burstsize());
+    assert!(typed.peek(now, 10) == untyped.peek(now, 10));
+}
+
+#[test]
+fn test_units_500_typed() {
+    let mut now: units::Ticks = units::Ticks(ticks::now());
+    let mut gcra: gcra::Gcra = gcra::Gcra::with(units::Rate::per_second(1000).unwrap(), units::Events(1), now);
+    let increment: units::Ticks = units::Rate::per_second(1000).unwrap().increment();
+    assert!(gcra.request_typed(now) == units::Ticks::ZERO);
+    assert!(gcra.commits_typed(units::Events(2)));
+    assert!(gcra.get_expected_typed() == (increment * 2));
+    assert!(gcra.peek_typed(now, units::Events(1)) == (increment * 2));
+    assert!(gcra.request_typed(now) == (increment * 2));
+    now += gcra.request_typed(now);
+    assert!(gcra.request_typed(now) == units::Ticks::ZERO);
+    assert!(gcra.commit());
+    assert!(gcra.admit_typed(now + increment));
+    assert!(gcra.admits_typed(now + increment * 2, units::Events(1)));
+    assert!(gcra.update_typed(now + increment * 10));
+    assert!(gcra.is_empty() || !gcra.is_full());
+    assert!(!gcra.is_alarmed());
+    gcra.reset_typed(now);
+    assert!(gcra.get_expected() == 0);
+}
+
+/// Drive one throttle through the typed methods of the Throttle trait and an
+/// identical one through the compatibility methods, and verify that they make
+/// the same decisions.
+fn typed(one: & mut throttle::Throttle, two: & mut throttle::Throttle) {
+    let mut now: units::Ticks = units::Ticks(0);
+    one.reset_typed(now);
+    two.reset(now.0);
+    for ii in 0..1000 {
+        now += units::Ticks(((ii * 37) % 250) as ticks::Ticks);
+        let events: units::Events = units::Events((ii % 3) + 1);
+        assert!(one.peek_typed(now, events).0 == two.peek(now.0, events.0));
+        if (ii % 4) == 0 {
+            assert!(one.admits_typed(now, events) == two.admits(now.0, events.0));
+        } else if (ii % 4) == 1 {
+            assert!(one.admit_typed(now) == two.admit(now.0));
+        } else if (ii % 4) == 2 {
+            assert!(one.update_typed(now) == two.update(now.0));
+        } else {
+            assert!(one.request_typed(now).0 == two.request(now.0));
+            assert!(one.commits_typed(events) == two.commits(events.0));
+        }
+        assert!(one.get_expected_typed().0 == two.get_expected());
+        assert!(one.is_alarmed() == two.is_alarmed());
+    }
+}
+
+#[test]
+fn test_units_600_trait() {
+    let rate: units::Rate = units::Rate::new(units::Events(1), units::Ticks(100)).unwrap();
+    let now: units::Ticks = units::Ticks::ZERO;
+    typed(& mut gcra::Gcra::with(rate, units::Events(4), now), & mut gcra::Gcra::new().init(100, 300, 0));
+    typed(& mut gcra::PoliceGcra::with(rate, units::Events(4), now), & mut gcra::PoliceGcra::new().init(100, 300, 0));
+    typed(& mut bucket::Bucket::with(rate, units::Events(4), now), & mut bucket::Bucket::new(100, 300, 0));
+    typed(& mut contract::Contract::with(units::Rate::new(units::Events(1), units::Ticks(50)).unwrap(), units::Ticks::ZERO, rate, units::Events(4), now), & mut contract::Contract::new().init(50, 0, 100, contract::bursttolerance(50, 0, 100, 4), 0));
+}
+
+/// Counter is a throttle as a crate outside of Rustler would implement it,
+/// providing only the required methods, which take bare ticks and events.
+struct Counter {
+    now:        ticks::Ticks,               // Time of the last request.
+    events:     throttle::Events,           // Events committed.
+}
+
+impl throttle::Throttle for Counter {
+    fn size_of(& self) -> usize { std::mem::size_of::<Counter>() }
+    fn as_string(& self) -> String { format!("Counter@{:p}:{{n:{},e:{}}}", self, self.now, self.events) }
+    fn reset(& mut self, now: ticks::Ticks) { self.now = now; self.events = 0; }
+    fn request(& mut self, now: ticks::Ticks) -> ticks::Ticks { self.now = now; self.events }
+    fn commits(& mut self, events: throttle::Events) -> bool { self.events += events; true }
+    fn commit(& mut self) -> bool { self.commits(1) }
+    fn admits(& mut self, now: ticks::Ticks, events: throttle::Events) -> bool { self.request(now); self.commits(events) }
+    fn admit(& mut self, now: ticks::Ticks) -> bool { self.admits(now, 1) }
+    fn update(& mut self, now: ticks::Ticks) -> bool { self.admits(now, 0) }
+    fn get_expected(& self) -> ticks::Ticks { self.events }
+    fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks { now + self.events + events }
+    fn is_empty(& self) -> bool { self.events == 0 }
+    fn is_full(& self) -> bool { false }
+    fn is_alarmed(& self) -> bool { false }
+    fn emptied(& self) -> bool { false }
+    fn filled(& self) -> bool { false }
+    fn alarmed(& self) -> bool { false }
+    fn cleared(& self) -> bool { false }
+}
+
+#[test]
+fn test_units_650_external() {
+    /* An implementation of only the bare methods gets the typed ones, which agree with it. */
+    let mut counter: Counter = Counter { now: 0, events: 0 };
+    counter.reset_typed(units::Ticks(5));
+    assert!(counter.now == 5);
+    assert!(counter.admits_typed(units::Ticks(7), units::Events(3)));
+    assert!(counter.now == 7);
+    assert!(counter.events == 3);
+    assert!(counter.admit_typed(units::Ticks(8)));
+    assert!(counter.update_typed(units::Ticks(9)));
+    assert!(counter.commits_typed(units::Events(2)));
+    assert!(counter.request_typed(units::Ticks(10)) == units::Ticks(counter.request(10)));
+    assert!(counter.get_expected_typed() == units::Ticks(6));
+    assert!(counter.peek_typed(units::Ticks(10), units::Events(1)) == units::Ticks(counter.peek(10, 1)));
+    assert!(counter.peek_typed(units::Ticks(10), units::Events(1)) == units::Ticks(17));
+}
@@ -0,0 +1,193 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate libc;
+extern crate rustler;
+
+use std::env;
+use std::fs;
+use std::process;
+use std::thread;
+use std::io::Write;
+use rustler::ticks::ticks;
+
+/// RATE is the contracted rate in bytes per second. The peak and sustained
+/// rates are the same so that no burst tolerance can make up for lateness.
+const RATE: f64 = 100000.0;
+
+/// shape runs the shape executable with the specified arguments over the
+/// specified number of bytes and returns the achieved rate in bytes per second.
+fn shape(arguments: &[&str], total: usize) -> f64 {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut child: process::Child = process::Command::new(env!("CARGO_BIN_EXE_shape"))
+        .args(arguments)
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .spawn().unwrap();
+    let mut input = child.stdin.take().unwrap();
+    let before: ticks::Ticks = ticks::now();
+    let producer = thread::spawn(move || { input.write_all(&vec![0x55u8; total][..]).unwrap(); });
+    let output: process::Output = child.wait_with_output().unwrap();
+    let after: ticks::Ticks = ticks::now();
+    producer.join().unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.len() == total);
+    (total as f64) * (frequency as f64) / ((after - before) as f64)
+}
+
+/// achieved checks that the achieved rate is never above the expected rate,
+/// and not so far below it that the shaper is plainly broken, allowing for the
+/// wake ups later than a whole emission interval that a loaded host produces.
+fn achieved(rate: f64, expected: f64) -> bool {
+    ((expected * 0.5)..=(expected * 1.01)).contains(&rate)
+}
+
+/// precise checks that the achieved rate is close to but not above the
+/// expected rate, as it is on a quiet host.
+fn precise(rate: f64, expected: f64) -> bool {
+    ((expected * 0.92)..=(expected * 1.01)).contains(&rate)
+}
+
+#[test]
+fn test_shape_100_sleep() {
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100"], 200000);
+    eprintln!("sleep: rate={}", rate);
+    assert!(achieved(rate, RATE));
+}
+
+#[test]
+fn test_shape_200_spin() {
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100", "-S", "100"], 200000);
+    eprintln!("spin: rate={}", rate);
+    assert!(achieved(rate, RATE));
+}
+
+#[test]
+fn test_shape_220_clock() {
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100", "-c", "monotonic"], 200000);
+    eprintln!("clock: rate={}", rate);
+    assert!(achieved(rate, RATE));
+}
+
+#[test]
+#[ignore]
+fn test_shape_250_precision() {
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100"], 200000);
+    eprintln!("sleep: rate={}", rate);
+    assert!(precise(rate, RATE));
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100", "-S", "100"], 200000);
+    eprintln!("spin: rate={}", rate);
+    assert!(precise(rate, RATE));
+}
+
+#[test]
+fn test_shape_300_queued() {
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100", "-q", "262144"], 200000);
+    eprintln!("queued: rate={}", rate);
+    assert!(achieved(rate, RATE));
+}
+
+#[test]
+fn test_shape_350_overhead() {
+    /* Each 100B block costs 200B with its overhead, so half the rate is data. */
+    let rate: f64 = shape(&["-p", "100000", "-s", "100000", "-b", "100", "-o", "100"], 100000);
+    eprintln!("overhead: rate={}", rate);
+    assert!(achieved(rate, RATE / 2.0));
+    /* Charged in bits, the rates are in bits per second. */
+    let rate: f64 = shape(&["-p", "800000", "-s", "800000", "-b", "100", "-o", "100", "-u", "bits"], 100000);
+    eprintln!("bits: rate={}", rate);
+    assert!(achieved(rate, RATE / 2.0));
+    /* Each 184B block and its 8B AAL5 trailer fill four 53B cells. */
+    let rate: f64 = shape(&["-p", "106000", "-s", "106000", "-b", "184", "-A"], 184000);
+    eprintln!("cells: rate={}", rate);
+    assert!(achieved(rate, 92000.0));
+}
+
+/// hangup runs the shape executable with the specified arguments and a
+/// contract file of 20000B/s, at which the data would take five seconds.
+/// After one second the file is rewritten to 200000B/s and shape is sent
+/// SIGHUP, so the remaining 80000B take less than half a second more. Anything
+/// well short of five seconds shows the reload took effect. Returns the
+/// elapsed time in seconds.
+fn hangup(arguments: &[&str]) -> f64 {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let total: usize = 100000;
+    let path = env::temp_dir().join(format!("test_shape_hangup.{}.{}.txt", process::id(), arguments.len()));
+    fs::write(&path, "# Contract.\npeakrate=20000\nsustainedrate=20000\nburstsize=100\n").unwrap();
+    let mut child: process::Child = process::Command::new(env!("CARGO_BIN_EXE_shape"))
+        .args(["-f", path.to_str().unwrap()])
+        .args(arguments)
+        .stdin(process::Stdio::piped())
+        .stdout(process::Stdio::piped())
+        .spawn().unwrap();
+    let mut input = child.stdin.take().unwrap();
+    let before: ticks::Ticks = ticks::now();
+    let producer = thread::spawn(move || { input.write_all(&vec![0x55u8; total][..]).unwrap(); });
+    ticks::sleep(frequency);
+    fs::write(&path, "peakrate=200000\nsustainedrate=200000\nburstsize=100\n").unwrap();
+    unsafe {
+        assert!(libc::kill(child.id() as libc::pid_t, libc::SIGHUP) == 0);
+    }
+    let output: process::Output = child.wait_with_output().unwrap();
+    let after: ticks::Ticks = ticks::now();
+    producer.join().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.len() == total);
+    ((after - before) as f64) / (frequency as f64)
+}
+
+#[test]
+fn test_shape_400_hangup() {
+    let elapsed: f64 = hangup(&["-V"]);
+    eprintln!("hangup: elapsed={}s", elapsed);
+    assert!((1.3..=4.0).contains(&elapsed));
+}
+
+#[test]
+fn test_shape_500_hangup_queued() {
+    let elapsed: f64 = hangup(&["-V", "-q", "262144"]);
+    eprintln!("hangup queued: elapsed={}s", elapsed);
+    assert!((1.3..=4.0).contains(&elapsed));
+}
+
+/// usage runs the shape executable with the specified arguments, expecting it
+/// to exit with an error instead of panicking, and returns its standard error.
+fn usage(arguments: &[&str]) -> String {
+    let output: process::Output = process::Command::new(env!("CARGO_BIN_EXE_shape"))
+        .args(arguments)
+        .stdin(process::Stdio::null())
+        .output().unwrap();
+    let error: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    eprintln!("usage: status={:?} stderr={}", output.status.code(), error);
+    assert!(output.status.code() == Some(1));
+    assert!(!error.contains("panicked"));
+    error
+}
+
+#[test]
+fn test_shape_600_queuesize() {
+    let error: String = usage(&["-b", "1024", "-q", "512"]);
+    assert!(error.contains("queuesize"));
+    assert!(error.contains("USAGE"));
+}
+
+#[test]
+fn test_shape_700_contract() {
+    /* A missing contract file. */
+    let path = env::temp_dir().join(format!("test_shape_contract.{}.txt", process::id()));
+    let error: String = usage(&["-f", path.to_str().unwrap()]);
+    assert!(error.contains("contract"));
+    assert!(error.contains(path.to_str().unwrap()));
+    /* A malformed contract file. */
+    fs::write(&path, "peakrate=fast\n").unwrap();
+    let error: String = usage(&["-f", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert!(error.contains("contract"));
+    assert!(error.contains("value not valid"));
+}
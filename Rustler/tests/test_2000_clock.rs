@@ -0,0 +1,177 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::clock::clock;
+use rustler::clock::clock::Clock;
+use std::sync;
+use std::sync::atomic;
+use std::thread;
+
+/// SELECTION serializes the tests that change the source read by ticks::now.
+static SELECTION: sync::Mutex<()> = sync::Mutex::new(());
+
+#[test]
+fn test_clock_100_sources() {
+    for source in clock::SOURCES.iter() {
+        assert!(clock::Source::parse(source.name()) == Some(*source));
+    }
+    assert!(clock::Source::parse("sundial").is_none());
+    assert!(clock::clock(clock::Source::Instant).is_some());
+    assert!(clock::clock(clock::Source::Monotonic).is_some());
+}
+
+#[test]
+fn test_clock_200_elapsed() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    for source in clock::SOURCES.iter() {
+        let clock: Box<clock::Clock + Send> = match clock::clock(*source) {
+            Some(value) => value,
+            None => { eprintln!("{}: unavailable", source.name()); continue; },
+        };
+        assert!(clock.name() == source.name());
+        assert!(clock.frequency() > 0);
+        assert!(clock.resolution() > 0);
+        let mut previous: ticks::Ticks = clock.now();
+        for _ in 0..1000 {
+            let now: ticks::Ticks = clock.now();
+            assert!(now >= previous);
+            previous = now;
+        }
+        let before: ticks::Ticks = clock.now();
+        ticks::sleep(frequency / 10);
+        let after: ticks::Ticks = clock.now();
+        let elapsed: f64 = ((after - before) as f64) / (clock.frequency() as f64);
+        eprintln!("{}: frequency={} resolution={} elapsed={}s", source.name(), clock.frequency(), clock.resolution(), elapsed);
+        assert!(elapsed >= (0.1 - ((clock.resolution() as f64) / (clock.frequency() as f64))));
+        assert!(elapsed < 0.2);
+    }
+}
+
+#[test]
+fn test_clock_300_posix() {
+    let monotonic: clock::Posix = clock::Posix::monotonic();
+    let before: ticks::Ticks = ticks::monotonic();
+    let now: ticks::Ticks = monotonic.now();
+    let after: ticks::Ticks = ticks::monotonic();
+    assert!((before <= now) && (now <= after));
+    eprintln!("monotonic={}", monotonic.to_string());
+    if let Some(coarse) = clock::Posix::coarse() {
+        assert!(coarse.resolution() >= monotonic.resolution());
+    }
+}
+
+#[test]
+fn test_clock_400_tsc() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let one: ticks::Ticks = match clock::calibrate(frequency / 10) {
+        Some(value) => value,
+        None => { eprintln!("tsc: unavailable"); return; },
+    };
+    let two: ticks::Ticks = clock::calibrate(frequency / 10).unwrap();
+    eprintln!("tsc: one={}Hz two={}Hz", one, two);
+    assert!(one > 0);
+    assert!((one - two).abs() < (one / 100));
+    let tsc: clock::Tsc = clock::Tsc::new(one).unwrap();
+    eprintln!("tsc={}", tsc.to_string());
+    assert!(tsc.now() >= 0);
+    assert!(tsc.frequency() == one);
+}
+
+#[test]
+fn test_clock_500_throttle() {
+    /* A throttle runs in the units of whatever clock drives it. */
+    let clock: Box<clock::Clock + Send> = clock::clock(clock::Source::Tsc).unwrap_or_else(|| clock::clock(clock::Source::Monotonic).unwrap());
+    let increment: ticks::Ticks = gcra::increment(100, 1, clock.frequency());
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(increment, 0, clock.now());
+    let mut count: u64 = 0;
+    let before: ticks::Ticks = clock.now();
+    while count < 50 {
+        let now: ticks::Ticks = clock.now();
+        let delay: ticks::Ticks = throttle.request(now);
+        if delay > 0 {
+            ticks::sleep(delay * ticks::frequency() / clock.frequency());
+        } else {
+            throttle.commit();
+            count += 1;
+        }
+    }
+    let after: ticks::Ticks = clock.now();
+    let rate: f64 = ((count - 1) as f64) * (clock.frequency() as f64) / ((after - before) as f64);
+    eprintln!("{}: rate={}", clock.name(), rate);
+    assert!((85.0..=101.0).contains(&rate));
+}
+
+#[test]
+fn test_clock_600_select() {
+    let _selection: sync::MutexGuard<()> = SELECTION.lock().unwrap_or_else(|error| error.into_inner());
+    let frequency: ticks::Ticks = ticks::frequency();
+    assert!(clock::selected() == clock::Source::Instant);
+    for source in clock::SOURCES.iter() {
+        let before: ticks::Ticks = ticks::now();
+        let previous: clock::Source = match ticks::select(*source) {
+            Some(previous) => previous,
+            None => { eprintln!("{}: unavailable", source.name()); continue; },
+        };
+        assert!(clock::selected() == *source);
+        /* Time continues across the change, and still counts nanoseconds. */
+        let during: ticks::Ticks = ticks::now();
+        ticks::sleep(frequency / 10);
+        let after: ticks::Ticks = ticks::now();
+        eprintln!("{}: previous={} elapsed={}", source.name(), previous.name(), after - during);
+        assert!(before <= during);
+        assert!(during <= after);
+        assert!((after - during) >= (frequency / 20));
+        assert!((after - during) <= frequency);
+    }
+    assert!(ticks::select(clock::Source::Instant).is_some());
+    assert!(clock::selected() == clock::Source::Instant);
+}
+
+#[test]
+fn test_clock_700_concurrent() {
+    let _selection: sync::MutexGuard<()> = SELECTION.lock().unwrap_or_else(|error| error.into_inner());
+    let done: sync::Arc<atomic::AtomicBool> = sync::Arc::new(atomic::AtomicBool::new(false));
+    let mut readers: Vec<thread::JoinHandle<u64>> = Vec::new();
+    for _ in 0..4 {
+        let done: sync::Arc<atomic::AtomicBool> = done.clone();
+        readers.push(thread::spawn(move || {
+            let mut reads: u64 = 0;
+            let mut before: ticks::Ticks = ticks::now();
+            while !done.load(atomic::Ordering::Relaxed) {
+                let after: ticks::Ticks = ticks::now();
+                assert!(before <= after, "before={} after={}", before, after);
+                before = after;
+                reads += 1;
+            }
+            reads
+        }));
+    }
+    /* Switch among every available source (but the slow to calibrate Tsc) while the readers read. */
+    for _ in 0..200 {
+        for source in clock::SOURCES.iter() {
+            if *source != clock::Source::Tsc {
+                ticks::select(*source);
+            }
+        }
+    }
+    if ticks::select(clock::Source::Tsc).is_some() {
+        ticks::sleep(ticks::frequency() / 100);
+    }
+    assert!(ticks::select(clock::Source::Instant).is_some());
+    done.store(true, atomic::Ordering::Relaxed);
+    for reader in readers {
+        let reads: u64 = reader.join().unwrap();
+        assert!(reads > 0);
+    }
+    assert!(clock::selected() == clock::Source::Instant);
+}
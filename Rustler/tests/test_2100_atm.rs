@@ -0,0 +1,130 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::contract::contract;
+use rustler::atm::atm;
+use rustler::atm::atm::Category;
+use rustler::atm::atm::Clp::Zero;
+use rustler::atm::atm::Clp::One;
+use rustler::atm::atm::Verdict::Pass;
+use rustler::atm::atm::Verdict::Tag;
+use rustler::atm::atm::Verdict::Discard;
+
+/// Check a conformance definition against a table of cell arrival times,
+/// CLPs, and expected verdicts.
+fn check(category: & mut atm::Category, table: &[(ticks::Ticks, atm::Clp, atm::Verdict)]) {
+    for (ii, &(now, clp, expected)) in table.iter().enumerate() {
+        let verdict: atm::Verdict = category.cell(now, clp);
+        eprintln!("{}: {}: now={} clp={:?} verdict={:?}", category.name(), ii, now, clp, verdict);
+        assert!(verdict == expected);
+    }
+}
+
+#[test]
+fn test_atm_100_cbr1() {
+    /* T=10 tau=2: cells may arrive up to tau early, and a discarded cell does not advance TAT. */
+    let mut cbr: atm::Cbr1 = atm::Cbr1::new(10, 2, 0);
+    eprintln!("cbr={}", cbr.to_string());
+    assert!(cbr.name() == "CBR.1");
+    check(& mut cbr, &[
+        (0, Zero, Pass), (10, Zero, Pass), (20, One, Pass),
+        (27, Zero, Discard), (28, One, Pass),
+        (36, Zero, Discard), (38, Zero, Pass),
+        (50, One, Pass), (100, Zero, Pass), (100, Zero, Discard),
+    ]);
+    cbr.reset(100);
+    check(& mut cbr, &[ (100, Zero, Pass), (108, One, Pass), (115, One, Discard) ]);
+}
+
+#[test]
+fn test_atm_200_vbr1() {
+    /* PCR T=10 tau=0, SCR Ts=30 with MBS=3: BT = (MBS - 1) * (Ts - T) = 40. */
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(10, 0, 30, 3);
+    assert!(bursttolerance == 40);
+    let mut vbr: atm::Vbr1 = atm::Vbr1::new(10, 0, 30, bursttolerance, 0);
+    eprintln!("vbr={}", vbr.to_string());
+    assert!(vbr.name() == "VBR.1");
+    /* Both CLPs count against both rates. */
+    check(& mut vbr, &[
+        (0, Zero, Pass), (10, One, Pass), (20, Zero, Pass),
+        (30, One, Discard), (40, Zero, Discard),
+        (50, One, Pass), (55, Zero, Discard), (80, Zero, Pass),
+    ]);
+}
+
+#[test]
+fn test_atm_300_vbr2() {
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(10, 0, 30, 3);
+    let mut vbr: atm::Vbr2 = atm::Vbr2::new(10, 0, 30, bursttolerance, 0);
+    eprintln!("vbr={}", vbr.to_string());
+    assert!(vbr.name() == "VBR.2");
+    /* CLP=1 cells are held only to the PCR; excess CLP=0 cells are discarded. */
+    check(& mut vbr, &[
+        (0, Zero, Pass), (10, Zero, Pass), (20, Zero, Pass),
+        (30, One, Pass), (40, Zero, Discard), (40, One, Pass),
+        (45, One, Discard), (50, Zero, Pass), (55, Zero, Discard),
+    ]);
+}
+
+#[test]
+fn test_atm_400_vbr3() {
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(10, 0, 30, 3);
+    let mut vbr: atm::Vbr3 = atm::Vbr3::new(10, 0, 30, bursttolerance, 0);
+    eprintln!("vbr={}", vbr.to_string());
+    assert!(vbr.name() == "VBR.3");
+    /* Excess CLP=0 cells are tagged instead, counting against the PCR but not the SCR. */
+    check(& mut vbr, &[
+        (0, Zero, Pass), (10, Zero, Pass), (20, Zero, Pass),
+        (30, One, Pass), (40, Zero, Tag), (40, One, Discard),
+        (50, Zero, Pass), (55, Zero, Discard), (60, Zero, Tag),
+    ]);
+    vbr.reset(1000);
+    check(& mut vbr, &[ (1000, Zero, Pass), (1010, Zero, Pass), (1020, Zero, Pass), (1030, Zero, Tag) ]);
+}
+
+#[test]
+fn test_atm_500_gfr() {
+    /* PCR T=10 tau=0, MCR T=40 with tau + BT = 80, and MFS of four cells. */
+    let mut gfr1: atm::Gfr = atm::Gfr::new(10, 0, 40, 80, 4, false, 0);
+    let mut gfr2: atm::Gfr = atm::Gfr::new(10, 0, 40, 80, 4, true, 0);
+    assert!(gfr1.name() == "GFR.1");
+    assert!(gfr2.name() == "GFR.2");
+    eprintln!("gfr={}", gfr2.to_string());
+    /* TIME, CLP, LAST, GFR.1, GFR.2, ELIGIBLE */
+    let table: [(ticks::Ticks, atm::Clp, bool, atm::Verdict, atm::Verdict, bool); 24] = [
+        /* An eligible frame is charged to the F-GCRA. */
+        (0, Zero, false, Pass, Pass, true), (10, Zero, false, Pass, Pass, true), (20, Zero, true, Pass, Pass, true),
+        /* Not eligible: passed by GFR.1, tagged by GFR.2. */
+        (30, Zero, false, Pass, Tag, false), (40, Zero, false, Pass, Tag, false), (50, Zero, true, Pass, Tag, false),
+        /* Eligible again once the F-GCRA has drained. */
+        (60, Zero, false, Pass, Pass, true), (70, Zero, true, Pass, Pass, true),
+        /* A change of CLP within a frame discards the rest but the last cell. */
+        (80, Zero, false, Pass, Tag, false), (90, One, false, Discard, Discard, false), (100, Zero, false, Discard, Discard, false), (110, Zero, true, Pass, Pass, false),
+        /* A frame longer than the MFS. */
+        (120, One, false, Pass, Pass, false), (130, One, false, Pass, Pass, false), (140, One, false, Pass, Pass, false), (150, One, false, Pass, Pass, false), (160, One, false, Discard, Discard, false), (170, One, true, Pass, Pass, false),
+        /* A cell that violates the PCR. */
+        (180, One, false, Pass, Pass, false), (185, One, false, Discard, Discard, false), (190, One, false, Discard, Discard, false), (195, One, true, Pass, Pass, false),
+        /* A single cell frame. */
+        (210, Zero, true, Pass, Pass, true), (210, Zero, true, Discard, Discard, true),
+    ];
+    for (ii, &(now, clp, last, one, two, eligible)) in table.iter().enumerate() {
+        let verdict1: atm::Verdict = gfr1.cell(now, clp, last);
+        let verdict2: atm::Verdict = gfr2.cell(now, clp, last);
+        eprintln!("gfr: {}: now={} clp={:?} last={} verdicts={:?},{:?} eligible={}", ii, now, clp, last, verdict1, verdict2, gfr2.is_eligible());
+        assert!(verdict1 == one);
+        assert!(verdict2 == two);
+        assert!(gfr1.is_eligible() == eligible);
+        assert!(gfr2.is_eligible() == eligible);
+    }
+    gfr2.reset(1000);
+    assert!(gfr2.cell(1000, Zero, true) == Pass);
+    assert!(gfr2.is_eligible());
+}
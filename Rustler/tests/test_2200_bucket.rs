@@ -0,0 +1,180 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::bucket::bucket;
+use rustler::random::random;
+
+mod harness;
+
+/// Scheduler is the virtual scheduling algorithm, the first form of the GCRA in
+/// the ATM Forum Traffic Management Specification 4.1, kept here as an
+/// absolute theoretical arrival time (TAT) so that it shares no arithmetic with
+/// either the relative deficit of gcra or the content of bucket. An arrival
+/// at time ta is non-conforming if TAT > ta + L; each event committed at ta
+/// advances TAT to max(ta, TAT) + I.
+struct Scheduler {
+    increment:  ticks::Ticks,               // I: increment per event.
+    limit:      ticks::Ticks,               // L: limit.
+    tat:        ticks::Ticks,               // TAT: theoretical arrival time.
+    arrival:    ticks::Ticks,               // ta: time of arrival.
+}
+
+impl Scheduler {
+
+    fn new(increment: ticks::Ticks, limit: ticks::Ticks, now: ticks::Ticks) -> Scheduler {
+        Scheduler { increment, limit, tat: now, arrival: now }
+    }
+
+    /// request returns how long past ta + L the TAT lies, if at all.
+    fn request(& mut self, now: ticks::Ticks) -> ticks::Ticks {
+        self.arrival = now;
+        (self.tat - (now + self.limit)).max(0)
+    }
+
+    /// peek returns how long past ta + L the TAT of the last of the events
+    /// would lie, each committed as soon as it conforms.
+    fn peek(& self, now: ticks::Ticks, events: throttle::Events) -> ticks::Ticks {
+        (self.tat.max(now) + (self.increment * (events - 1).max(0)) - (now + self.limit)).max(0)
+    }
+
+    /// commits advances the TAT by the events at the time of the request.
+    fn commits(& mut self, events: throttle::Events) {
+        self.tat = self.tat.max(self.arrival) + (self.increment * events);
+    }
+
+    /// is_empty returns true if the TAT is not in the future.
+    fn is_empty(& self) -> bool {
+        self.tat <= self.arrival
+    }
+
+    /// is_full returns true if the arrival was non-conforming.
+    fn is_full(& self) -> bool {
+        self.tat > (self.arrival + self.limit)
+    }
+
+    /// get_expected returns how far the TAT lies beyond the arrival, which is
+    /// the content of the equivalent continuous-state leaky bucket.
+    fn get_expected(& self) -> ticks::Ticks {
+        (self.tat - self.arrival).max(0)
+    }
+
+}
+
+#[test]
+fn test_bucket_100_sanity() {
+    /* I=10 L=15: two back to back arrivals conform, the third does not. */
+    let mut bucket: bucket::Bucket = bucket::Bucket::new(10, 15, 0);
+    eprintln!("bucket={}", bucket.to_string());
+    assert!(bucket.get_increment() == 10);
+    assert!(bucket.get_limit() == 15);
+    assert!(bucket.is_empty());
+    assert!(bucket.request(0) == 0);
+    assert!(bucket.commit());
+    assert!(bucket.get_content() == 10);
+    assert!(bucket.request(0) == 0);
+    assert!(bucket.commit());
+    assert!(bucket.get_content() == 20);
+    assert!(bucket.peek(0, 1) == 5);
+    assert!(bucket.peek(0, 3) == 25);
+    assert!(bucket.request(2) == 3);
+    assert!(!bucket.commit());
+    assert!(bucket.is_full());
+    assert!(bucket.alarmed());
+    assert!(bucket.get_content() == 28);
+    assert!(bucket.update(100));
+    assert!(bucket.is_empty());
+    assert!(bucket.cleared());
+    eprintln!("bucket={}", bucket.to_string());
+    bucket.reset(200);
+    assert!(bucket.get_expected() == 0);
+    assert!(bucket.size_of() > 0);
+}
+
+#[test]
+fn test_bucket_150_peek_overflow() {
+    let increment: ticks::Ticks = 100;
+    let limit: ticks::Ticks = 250;
+    let mut bucket: bucket::Bucket = bucket::Bucket::new(increment, limit, 0);
+    let mut gcra: gcra::Gcra = gcra::Gcra::new().init(increment, limit, 0);
+    bucket.admits(0, 3);
+    gcra.admits(0, 3);
+    /* So many events saturate the delay instead of overflowing it, as in a Gcra. */
+    assert!(bucket.peek(0, i64::max_value()) == (i64::max_value() - limit));
+    assert!(bucket.peek(0, i64::max_value() / 2) == (i64::max_value() - limit));
+    assert!(bucket.peek(0, (i64::max_value() / increment) - 3) > 0);
+    assert!(bucket.peek(0, i64::max_value()) == gcra.peek(0, i64::max_value()));
+    assert!(bucket.peek(0, (i64::max_value() / increment) - 3) == gcra.peek(0, (i64::max_value() / increment) - 3));
+}
+
+/// Return the levels and sensors of a throttle.
+fn state(throttle: & throttle::Throttle) -> [bool; 7] {
+    [
+        throttle.is_empty(), throttle.is_full(), throttle.is_alarmed(),
+        throttle.emptied(), throttle.filled(), throttle.alarmed(), throttle.cleared(),
+    ]
+}
+
+#[test]
+fn test_bucket_200_differential() {
+    let seed: u64 = harness::seed();
+    let mut random: random::Random = random::Random::new(seed);
+    let mut arrivals: u64 = 0;
+    let mut conforming: u64 = 0;
+    eprintln!("differential: seed={}", seed);
+    for contract in 0..40 {
+        let increment: ticks::Ticks = random.range(1, 1000) as ticks::Ticks;
+        let limit: ticks::Ticks = random.range(0, 10) as ticks::Ticks * increment + random.range(0, increment as u64 - 1) as ticks::Ticks;
+        /* Even contracts are charged for every emission, as a shaper would be; odd ones only for conforming emissions, as a policer would be. */
+        let policing: bool = (contract % 2) == 1;
+        let mut now: ticks::Ticks = random.range(0, 1_000_000_000) as ticks::Ticks;
+        let mut scheduler: Scheduler = Scheduler::new(increment, limit, now);
+        let mut gcra: gcra::Gcra = gcra::Gcra::new().init(increment, limit, now);
+        let mut bucket: bucket::Bucket = bucket::Bucket::new(increment, limit, now);
+        for ii in 0..50000 {
+            let events: throttle::Events = random.range(0, 4) as throttle::Events;
+            now += match random.range(0, 3) {
+                0 => 0,
+                1 => random.range(0, (increment * events.max(1)) as u64) as ticks::Ticks,
+                2 => random.range(0, (2 * increment * events.max(1)) as u64) as ticks::Ticks,
+                _ => random.range(0, (increment + limit) as u64 * 2) as ticks::Ticks,
+            };
+            let peek: throttle::Events = random.range(0, 4) as throttle::Events;
+            assert!(scheduler.peek(now, peek) == gcra.peek(now, peek), "seed={} contract={} ii={}", seed, contract, ii);
+            assert!(scheduler.peek(now, peek) == bucket.peek(now, peek), "seed={} contract={} ii={}", seed, contract, ii);
+            let delay: ticks::Ticks = scheduler.request(now);
+            assert!(delay == gcra.request(now), "seed={} contract={} ii={} tat={} gcra={}", seed, contract, ii, scheduler.tat, gcra.to_string());
+            assert!(delay == bucket.request(now), "seed={} contract={} ii={} tat={} bucket={}", seed, contract, ii, scheduler.tat, bucket.to_string());
+            arrivals += 1;
+            if delay == 0 {
+                conforming += 1;
+            }
+            if !policing || (delay == 0) {
+                /* The levels of a throttle are those at the arrival it committed. */
+                let empty: bool = scheduler.is_empty();
+                let full: bool = scheduler.is_full();
+                scheduler.commits(events);
+                assert!(gcra.commits(events) == bucket.commits(events), "seed={} contract={} ii={}", seed, contract, ii);
+                assert!(empty == gcra.is_empty(), "seed={} contract={} ii={}", seed, contract, ii);
+                assert!(full == gcra.is_full(), "seed={} contract={} ii={}", seed, contract, ii);
+                assert!(state(& gcra) == state(& bucket), "seed={} contract={} ii={} gcra={} bucket={}", seed, contract, ii, gcra.to_string(), bucket.to_string());
+                assert!(scheduler.get_expected() == gcra.get_expected(), "seed={} contract={} ii={} tat={} gcra={}", seed, contract, ii, scheduler.tat, gcra.to_string());
+                assert!(scheduler.get_expected() == bucket.get_expected(), "seed={} contract={} ii={} tat={} bucket={}", seed, contract, ii, scheduler.tat, bucket.to_string());
+            }
+        }
+    }
+    eprintln!("differential: arrivals={} conforming={}", arrivals, conforming);
+    assert!(arrivals == 2_000_000);
+    assert!(conforming > (arrivals / 4));
+    assert!(conforming < arrivals);
+}
@@ -0,0 +1,166 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::io;
+use std::thread;
+use rustler::ticks::ticks;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::aal5::aal5;
+
+mod harness;
+
+/// Return a frame of the specified length with a random payload.
+fn frame(length: usize) -> Vec<u8> {
+    (0..length).map(|_| harness::payload(0xff)).collect()
+}
+
+#[test]
+fn test_aal5_100_crc() {
+    /* CRC-32/BZIP2 check value, and the HECs of an all zero header and of an idle cell. */
+    assert!(aal5::crc32(b"123456789") == 0xfc89_1918);
+    assert!(aal5::crc32(b"") == 0);
+    assert!(aal5::hec(&[0x00, 0x00, 0x00, 0x00]) == 0x55);
+    assert!(aal5::hec(&[0x00, 0x00, 0x00, 0x01]) == 0x52);
+}
+
+#[test]
+fn test_aal5_200_header() {
+    let header: aal5::Header = aal5::Header { gfc: 0x0a, vpi: 0xc3, vci: 0xbeef, pti: 0x05, clp: true };
+    eprintln!("header={}", header.to_string());
+    let bytes: [u8; aal5::HEADER] = header.encode();
+    eprintln!("bytes={:02x?}", bytes);
+    assert!(bytes[..4] == [0xac, 0x3b, 0xee, 0xfb]);
+    assert!(aal5::Header::decode(&bytes).unwrap() == header);
+    let header: aal5::Header = aal5::Header::new(1, 32, false);
+    assert!(aal5::Header::decode(&header.encode()).unwrap() == header);
+    for ii in 0..(aal5::HEADER * 8) {
+        let mut damaged: [u8; aal5::HEADER] = bytes;
+        damaged[ii / 8] ^= 1 << (ii % 8);
+        let error: io::Error = aal5::Header::decode(&damaged).unwrap_err();
+        assert!(error.kind() == io::ErrorKind::InvalidData);
+    }
+    assert!(aal5::Header::decode(&bytes[..4]).is_err());
+}
+
+#[test]
+fn test_aal5_300_segment() {
+    let header: aal5::Header = aal5::Header::new(0, 100, false);
+    let mut reassembler: aal5::Reassembler = aal5::Reassembler::new();
+    for &(length, count) in [(0, 1), (1, 1), (40, 1), (41, 2), (48, 2), (88, 2), (89, 3), (aal5::MAXIMUM, 1366)].iter() {
+        let data: Vec<u8> = frame(length);
+        let cells: Vec<aal5::Cell> = aal5::segment(header, 0x5a, &data);
+        assert!(aal5::cells(length) == count);
+        assert!(cells.len() == count);
+        for (ii, cell) in cells.iter().enumerate() {
+            let each: aal5::Header = aal5::Header::decode(cell).unwrap();
+            assert!(each.vci == 100);
+            assert!(((each.pti & aal5::AUU) != 0) == ((ii + 1) == count));
+            let result: Option<aal5::Frame> = reassembler.push(cell).unwrap();
+            if (ii + 1) < count {
+                assert!(result.is_none());
+            } else {
+                assert!(result == Some((0x5a, data.clone())));
+            }
+        }
+    }
+    eprintln!("reassembler={}", reassembler.to_string());
+    assert!(reassembler.get_frames() == 8);
+    assert!(reassembler.get_errors() == 0);
+}
+
+#[test]
+fn test_aal5_400_errors() {
+    let header: aal5::Header = aal5::Header::new(0, 100, false);
+    let mut reassembler: aal5::Reassembler = aal5::Reassembler::new();
+    let data: Vec<u8> = frame(500);
+    let cells: Vec<aal5::Cell> = aal5::segment(header, 0, &data);
+    /* A damaged payload fails the CRC. */
+    let mut damaged: Vec<aal5::Cell> = cells.clone();
+    damaged[3][20] ^= 0x10;
+    let results: Vec<io::Result<Option<aal5::Frame>>> = damaged.iter().map(|cell| reassembler.push(cell)).collect();
+    assert!(results[results.len() - 1].as_ref().unwrap_err().to_string() == "CRC-32 error");
+    /* A lost cell in the middle fails the CRC. */
+    for (ii, cell) in cells.iter().enumerate() {
+        if ii == 2 { continue; }
+        let result = reassembler.push(cell);
+        if (ii + 1) == cells.len() { assert!(result.is_err()); }
+    }
+    /* A lost last cell runs two frames together, failing the length check or the CRC. */
+    for cell in cells[..(cells.len() - 1)].iter().chain(cells.iter()) {
+        let result = reassembler.push(cell);
+        if (aal5::Header::decode(cell).unwrap().pti & aal5::AUU) != 0 { assert!(result.is_err()); }
+    }
+    /* A damaged header drops just that cell. */
+    let mut damaged: aal5::Cell = cells[0];
+    damaged[2] ^= 0x01;
+    assert!(reassembler.push(&damaged).is_err());
+    assert!(reassembler.push(&damaged[..10]).is_err());
+    /* And a good frame after all that is fine. */
+    for cell in cells[..(cells.len() - 1)].iter() {
+        assert!(reassembler.push(cell).unwrap().is_none());
+    }
+    assert!(reassembler.push(&cells[cells.len() - 1]).unwrap() == Some((0, data.clone())));
+    /* A frame longer than the maximum is dropped through its last cell. */
+    let mut middle: aal5::Cell = cells[0];
+    let mut errors: usize = 0;
+    middle[aal5::HEADER..].copy_from_slice(&[0u8; aal5::PAYLOAD]);
+    for _ in 0..2000 {
+        if reassembler.push(&middle).is_err() { errors += 1; }
+    }
+    assert!(errors == 1);
+    assert!(reassembler.push(&cells[cells.len() - 1]).unwrap().is_none());
+    for cell in cells.iter() {
+        let result = reassembler.push(cell).unwrap();
+        if (aal5::Header::decode(cell).unwrap().pti & aal5::AUU) != 0 { assert!(result == Some((0, data.clone()))); }
+    }
+    eprintln!("reassembler={}", reassembler.to_string());
+    assert!(reassembler.get_frames() == 2);
+    assert!(reassembler.get_errors() == 6);
+}
+
+#[test]
+fn test_aal5_500_udp() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(20000, 1, frequency);
+    let sustainedincrement: ticks::Ticks = gcra::increment(10000, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, 64);
+    let header: aal5::Header = aal5::Header::new(0, 42, false);
+    let frames: Vec<Vec<u8>> = (0..200).map(|_| frame(harness::blocksize(1000))).collect();
+    let total: usize = frames.iter().map(|data| aal5::cells(data.len())).sum();
+    let expected: Vec<Vec<u8>> = frames.clone();
+    let (mut sink, mut source) = harness::connect(harness::Transport::Udp);
+    let receiver = thread::spawn(move || {
+        let mut reassembler: aal5::Reassembler = aal5::Reassembler::new();
+        let mut received: Vec<Vec<u8>> = Vec::new();
+        let mut buffer: [u8; 128] = [0; 128];
+        while received.len() < 200 {
+            let length: usize = source.receive(& mut buffer).unwrap();
+            assert!(length == aal5::CELL);
+            if let Some((_, data)) = reassembler.push(&buffer[..length]).unwrap() {
+                received.push(data);
+            }
+        }
+        eprintln!("receiver: reassembler={}", reassembler.to_string());
+        received
+    });
+    let mut shape: contract::Contract = contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, ticks::now());
+    let before: ticks::Ticks = ticks::now();
+    for data in frames.iter() {
+        let cells: Vec<aal5::Cell> = aal5::segment(header, 0, data);
+        aal5::shape(& mut shape, &cells, |cell| sink.send(cell)).unwrap();
+    }
+    let after: ticks::Ticks = ticks::now();
+    let received: Vec<Vec<u8>> = receiver.join().unwrap();
+    let rate: f64 = (total as f64) * (frequency as f64) / ((after - before) as f64);
+    eprintln!("udp: cells={} rate={}cells/s", total, rate);
+    assert!(received == expected);
+    assert!(rate <= 10500.0);
+}
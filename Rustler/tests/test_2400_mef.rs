@@ -0,0 +1,265 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::random::random;
+use rustler::mef::mef;
+use rustler::mef::mef::Color::Green;
+use rustler::mef::mef::Color::Yellow;
+use rustler::mef::mef::Color::Red;
+
+mod harness;
+
+/// Reference is the bandwidth profile algorithm of MEF 10.2 7.11.1 written out
+/// literally, with the buckets in bits scaled by the frequency. For a frame of
+/// length L bytes arriving at time tj after one at time tj-1:
+///
+/// Bc(tj) = min{CBS, Bc(tj-1) + CIR/8 x (tj - tj-1)}
+///
+/// O(tj) = max{0, Bc(tj-1) + CIR/8 x (tj - tj-1) - CBS}
+///
+/// Be(tj) = min{EBS, Be(tj-1) + EIR/8 x (tj - tj-1) + CF x O(tj)}
+///
+/// If (CM is color blind or the frame is green) and L <= Bc(tj) the frame is
+/// green and Bc(tj) = Bc(tj) - L; otherwise if (CM is color blind or the frame
+/// is not red) and L <= Be(tj) the frame is yellow and Be(tj) = Be(tj) - L;
+/// otherwise the frame is red.
+struct Reference {
+    cir:        u128,                       // CIR in bits per second.
+    cbs:        u128,                       // CBS in scaled bits.
+    eir:        u128,                       // EIR in bits per second.
+    ebs:        u128,                       // EBS in scaled bits.
+    cf:         u128,                       // CF as zero or one.
+    blind:      bool,                       // CM is color blind.
+    bc:         u128,                       // Bc in scaled bits.
+    be:         u128,                       // Be in scaled bits.
+    tj:         ticks::Ticks,               // Time of the last arrival.
+}
+
+impl Reference {
+
+    fn new(cir: i64, cbs: i64, eir: i64, ebs: i64, coupled: bool, mode: mef::Mode, now: ticks::Ticks) -> Reference {
+        let frequency: u128 = ticks::frequency() as u128;
+        Reference {
+            cir:    cir as u128,
+            cbs:    (cbs as u128) * 8 * frequency,
+            eir:    eir as u128,
+            ebs:    (ebs as u128) * 8 * frequency,
+            cf:     coupled as u128,
+            blind:  mode == mef::Mode::Blind,
+            bc:     (cbs as u128) * 8 * frequency,
+            be:     (ebs as u128) * 8 * frequency,
+            tj:     now,
+        }
+    }
+
+    fn frame(& mut self, now: ticks::Ticks, length: usize, color: mef::Color) -> mef::Color {
+        let elapsed: u128 = (now - self.tj) as u128;
+        let filled: u128 = self.bc + (self.cir * elapsed);
+        let overflow: u128 = filled.saturating_sub(self.cbs);
+        let l: u128 = (length as u128) * 8 * (ticks::frequency() as u128);
+        self.bc = filled.min(self.cbs);
+        self.be = (self.be + (self.eir * elapsed) + (self.cf * overflow)).min(self.ebs);
+        self.tj = now;
+        if (self.blind || (color == Green)) && (l <= self.bc) {
+            self.bc -= l;
+            Green
+        } else if (self.blind || (color != Red)) && (l <= self.be) {
+            self.be -= l;
+            Yellow
+        } else {
+            Red
+        }
+    }
+
+}
+
+/// Offer the same frame to a profile and to the reference, and return the
+/// color both declare.
+fn offer(profile: & mut mef::Profile, reference: & mut Reference, now: ticks::Ticks, length: usize, color: mef::Color) -> mef::Color {
+    let declared: mef::Color = profile.frame(now, length, color);
+    eprintln!("now={} length={} color={:?} declared={:?} committed={} excess={}", now, length, color, declared, profile.get_committed(), profile.get_excess());
+    assert!(declared == reference.frame(now, length, color));
+    assert!((profile.get_committed() as u128) == (reference.bc / 8 / (ticks::frequency() as u128)));
+    assert!((profile.get_excess() as u128) == (reference.be / 8 / (ticks::frequency() as u128)));
+    declared
+}
+
+#[test]
+fn test_mef_100_algorithm() {
+    /* Each step of 7.11.1 for CIR=8000b/s (1000B/s) CBS=1500B EIR=8000b/s EBS=1500B. */
+    let frequency: ticks::Ticks = ticks::frequency();
+    for &coupled in [false, true].iter() {
+        for &mode in [mef::Mode::Blind, mef::Mode::Aware].iter() {
+            let mut profile: mef::Profile = mef::Profile::new(8000, 1500, 8000, 1500, coupled, mode, 0);
+            let mut reference: Reference = Reference::new(8000, 1500, 8000, 1500, coupled, mode, 0);
+            eprintln!("profile={}", profile.to_string());
+            assert!(profile.is_coupled() == coupled);
+            assert!(profile.get_mode() == mode);
+            /* Both buckets start full. */
+            assert!(profile.get_committed() == 1500);
+            assert!(profile.get_excess() == 1500);
+            /* L <= Bc: green. */
+            assert!(offer(& mut profile, & mut reference, 0, 1000, Green) == Green);
+            /* L > Bc but L <= Be: yellow. */
+            assert!(offer(& mut profile, & mut reference, 0, 1000, Green) == Yellow);
+            /* L > Bc and L > Be: red. */
+            assert!(offer(& mut profile, & mut reference, 0, 1000, Green) == Red);
+            /* A yellow frame is green only if color blind, a red one yellow only if color blind. */
+            offer(& mut profile, & mut reference, 0, 500, Yellow);
+            offer(& mut profile, & mut reference, 0, 500, Red);
+            /* Bc fills at CIR/8 and Be at EIR/8. */
+            offer(& mut profile, & mut reference, frequency / 2, 600, Green);
+            offer(& mut profile, & mut reference, frequency / 2, 500, Green);
+            offer(& mut profile, & mut reference, frequency / 2, 500, Green);
+            /* Bc and Be fill no further than CBS and EBS, and O fills Be only if CF is one. */
+            offer(& mut profile, & mut reference, 3 * frequency, 1500, Green);
+            offer(& mut profile, & mut reference, 3 * frequency, 1500, Green);
+            offer(& mut profile, & mut reference, 3 * frequency, 1, Green);
+            offer(& mut profile, & mut reference, 3 * frequency + frequency / 4, 200, Red);
+            offer(& mut profile, & mut reference, 3 * frequency + frequency / 4, 200, Yellow);
+            offer(& mut profile, & mut reference, 3 * frequency + frequency / 4, 200, Green);
+            profile.reset(4 * frequency);
+            assert!(profile.get_committed() == 1500);
+            assert!(profile.get_excess() == 1500);
+        }
+    }
+}
+
+#[test]
+fn test_mef_200_differential() {
+    let seed: u64 = harness::seed();
+    let mut random: random::Random = random::Random::new(seed);
+    let colors: [mef::Color; 3] = [Green, Yellow, Red];
+    let mut counts: [u64; 3] = [0; 3];
+    eprintln!("differential: seed={}", seed);
+    for contract in 0..40 {
+        let cir: i64 = random.range(0, 10_000_000) as i64;
+        let cbs: i64 = random.range(0, 100_000) as i64;
+        let eir: i64 = random.range(0, 10_000_000) as i64;
+        let ebs: i64 = random.range(0, 100_000) as i64;
+        let coupled: bool = random.range(0, 1) == 1;
+        let mode: mef::Mode = if random.range(0, 1) == 1 { mef::Mode::Aware } else { mef::Mode::Blind };
+        let mut now: ticks::Ticks = random.range(0, 1_000_000_000) as ticks::Ticks;
+        let mut profile: mef::Profile = mef::Profile::new(cir, cbs, eir, ebs, coupled, mode, now);
+        let mut reference: Reference = Reference::new(cir, cbs, eir, ebs, coupled, mode, now);
+        for ii in 0..20000 {
+            now += match random.range(0, 3) {
+                0 => 0,
+                1 => random.range(0, 100_000) as ticks::Ticks,
+                2 => random.range(0, 10_000_000) as ticks::Ticks,
+                _ => random.range(0, 1_000_000_000) as ticks::Ticks,
+            };
+            let length: usize = random.range(64, 1518) as usize;
+            let color: mef::Color = colors[random.range(0, 2) as usize];
+            let declared: mef::Color = profile.frame(now, length, color);
+            assert!(declared == reference.frame(now, length, color), "seed={} contract={} ii={} profile={}", seed, contract, ii, profile.to_string());
+            counts[declared as usize] += 1;
+        }
+    }
+    eprintln!("differential: green={} yellow={} red={}", counts[0], counts[1], counts[2]);
+    assert!(counts.iter().all(|count| *count > 0));
+}
+
+#[test]
+fn test_mef_300_backward() {
+    /* CIR=8000b/s (1000B/s) CBS=1000B EIR=0 EBS=0. */
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut profile: mef::Profile = mef::Profile::new(8000, 1000, 0, 0, false, mef::Mode::Blind, 0);
+    assert!(profile.frame(frequency, 1000, Green) == Green);
+    /* A frame from the past accumulates nothing. */
+    assert!(profile.frame(frequency / 2, 1, Green) == Red);
+    assert!(profile.get_committed() == 0);
+    /* Nor does it move the last arrival back: half a second later only 500B have accumulated. */
+    assert!(profile.frame(frequency + frequency / 2, 501, Green) == Red);
+    assert!(profile.frame(frequency + frequency / 2, 500, Green) == Green);
+    assert!(profile.get_committed() == 0);
+}
+
+#[test]
+fn test_mef_400_bursts() {
+    /*
+     * CIR=1.2Mb/s (150000B/s) CBS=15000B EIR=0 EBS=15000B CM=blind. Bursts of
+     * twenty 1500B frames every 200ms average exactly the CIR, but each burst
+     * is twice the CBS. Without coupling half of each burst after the first is
+     * red even though the flow never exceeds the CIR; with coupling the
+     * committed tokens that overflow during each gap carry it as yellow.
+     */
+    let frequency: ticks::Ticks = ticks::frequency();
+    let bursts: usize = 100;
+    let frames: usize = 20;
+    for &coupled in [false, true].iter() {
+        let mut profile: mef::Profile = mef::Profile::new(1_200_000, 15000, 0, 15000, coupled, mef::Mode::Blind, 0);
+        let mut counts: [usize; 3] = [0; 3];
+        for burst in 0..bursts {
+            let now: ticks::Ticks = (burst as ticks::Ticks) * frequency / 5;
+            for frame in 0..frames {
+                let color: mef::Color = profile.frame(now, 1500, Green);
+                let expected: mef::Color = if frame < (frames / 2) { Green } else if coupled || (burst == 0) { Yellow } else { Red };
+                assert!(color == expected);
+                counts[color as usize] += 1;
+            }
+        }
+        eprintln!("coupled={} green={} yellow={} red={}", coupled, counts[0], counts[1], counts[2]);
+        assert!(counts[0] == (bursts * frames / 2));
+        if coupled {
+            assert!(counts[1] == (bursts * frames / 2));
+            assert!(counts[2] == 0);
+        } else {
+            assert!(counts[1] == (frames / 2));
+            assert!(counts[2] == ((bursts - 1) * frames / 2));
+        }
+    }
+}
+
+#[test]
+fn test_mef_500_rates() {
+    /*
+     * CIR=4Mb/s (500000B/s) CBS=15000B EIR=2Mb/s (250000B/s) EBS=15000B CF=0
+     * CM=blind offered 1500B every millisecond (12Mb/s) for ten seconds. The
+     * green bytes are within one frame of the tokens supplied. So are the
+     * yellow bytes, less the excess tokens lost while the excess bucket sat
+     * full during the first 15ms, in which the CBS was drained at 1000B/ms.
+     */
+    let frequency: ticks::Ticks = ticks::frequency();
+    let frames: i64 = 10000;
+    let mut profile: mef::Profile = mef::Profile::new(4_000_000, 15000, 2_000_000, 15000, false, mef::Mode::Blind, 0);
+    let mut bytes: [i64; 3] = [0; 3];
+    for frame in 0..frames {
+        let color: mef::Color = profile.frame(frame * frequency / 1000, 1500, Green);
+        bytes[color as usize] += 1500;
+    }
+    let elapsed: i64 = frames - 1;
+    let committed: i64 = (500_000 * elapsed / 1000) + 15000;
+    let excess: i64 = (250_000 * elapsed / 1000) + 15000;
+    eprintln!("green={}/{} yellow={}/{} red={}", bytes[0], committed, bytes[1], excess, bytes[2]);
+    assert!(((committed - 1500)..=committed).contains(&bytes[0]));
+    assert!(((excess - 3750 - 1500)..=(excess - 3750)).contains(&bytes[1]));
+    assert!((bytes[0] + bytes[1] + bytes[2]) == (frames * 1500));
+}
+
+#[test]
+fn test_mef_600_exact() {
+    /* CIR=12b/s (1.5B/s) CBS=3B EIR=0 EBS=0: three bytes exactly every two seconds. */
+    let frequency: ticks::Ticks = ticks::frequency();
+    let mut profile: mef::Profile = mef::Profile::new(12, 3, 0, 0, false, mef::Mode::Blind, 0);
+    assert!(profile.frame(0, 3, Green) == Green);
+    for period in 1..1000 {
+        let now: ticks::Ticks = period * 2 * frequency;
+        assert!(profile.frame(now - 1, 3, Green) == Red);
+        assert!(profile.frame(now, 3, Green) == Green);
+        assert!(profile.get_committed() == 0);
+    }
+    /* A very long idle period saturates instead of overflowing. */
+    assert!(profile.frame(i64::max_value() / 2, 3, Green) == Green);
+    assert!(profile.frame(i64::max_value() / 2, 1, Green) == Red);
+    assert!(profile.frame(i64::max_value(), 4, Green) == Red);
+    assert!(profile.frame(i64::max_value(), 3, Green) == Green);
+}
@@ -0,0 +1,77 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::throttle::throttle;
+use rustler::gcra::gcra;
+use rustler::throttle::throttle::Throttle;
+use rustler::aal5::aal5;
+use rustler::cost::cost;
+
+#[test]
+fn test_cost_100_models() {
+    let identity: cost::Model = cost::Model::new(0, false, cost::Unit::Bytes);
+    eprintln!("identity={}", identity.to_string());
+    assert!(identity.get_overhead() == 0);
+    assert!(!identity.get_cells());
+    assert!(identity.get_unit() == cost::Unit::Bytes);
+    let ethernet: cost::Model = cost::Model::new(cost::ETHERNET, false, cost::Unit::Bits);
+    let udp: cost::Model = cost::Model::new(cost::UDP, false, cost::Unit::Bytes);
+    let atm: cost::Model = cost::Model::new(0, true, cost::Unit::Bytes);
+    let llc: cost::Model = cost::Model::new(8, true, cost::Unit::Bits);
+    /* Length, identity, Ethernet in bits, IPv4 and UDP, cell tax, LLC/SNAP and cell tax in bits. */
+    let table: [(usize, throttle::Events, throttle::Events, throttle::Events, throttle::Events, throttle::Events); 8] = [
+        (0,     0,      160,    28,     53,     424),
+        (1,     1,      168,    29,     53,     424),
+        (40,    40,     480,    68,     53,     848),
+        (41,    41,     488,    69,     106,    848),
+        (64,    64,     672,    92,     106,    848),
+        (88,    88,     864,    116,    106,    1272),
+        (89,    89,     872,    117,    159,    1272),
+        (1500,  1500,   12160,  1528,   1696,   13568),
+    ];
+    for &(length, bytes, bits, headers, cells, both) in table.iter() {
+        eprintln!("length={} identity={} ethernet={} udp={} atm={} llc={}", length, identity.cost(length), ethernet.cost(length), udp.cost(length), atm.cost(length), llc.cost(length));
+        assert!(identity.cost(length) == bytes);
+        assert!(ethernet.cost(length) == bits);
+        assert!(udp.cost(length) == headers);
+        assert!(atm.cost(length) == cells);
+        assert!(llc.cost(length) == both);
+        assert!(atm.cost(length) == ((aal5::cells(length) * aal5::CELL) as throttle::Events));
+    }
+}
+
+#[test]
+fn test_cost_200_line_rate() {
+    /*
+     * A 1Gb/s Ethernet line rate admits a minimum frame (64 bytes, or 672
+     * bits with its 20 bytes of framing) every 672ns, 1488096 of them in one
+     * second counting the first at time zero, but charging the frame length
+     * alone would admit one every 512ns.
+     */
+    let frequency: i64 = 1_000_000_000;
+    let increment: i64 = gcra::increment(1_000_000_000, 1, frequency);
+    let ethernet: cost::Model = cost::Model::new(cost::ETHERNET, false, cost::Unit::Bits);
+    let naive: cost::Model = cost::Model::new(0, false, cost::Unit::Bits);
+    for &(model, expected) in [(ethernet, 1_488_096), (naive, 1_953_125)].iter() {
+        let mut throttle: gcra::Gcra = gcra::Gcra::new().init(increment, 0, 0);
+        let mut now: i64 = 0;
+        let mut frames: i64 = 0;
+        while now < frequency {
+            now += throttle.request(now);
+            if now >= frequency {
+                break;
+            }
+            throttle.admits(now, model.cost(64));
+            frames += 1;
+        }
+        eprintln!("model={} frames={}", model.to_string(), frames);
+        assert!(frames == expected);
+    }
+}
@@ -7,6 +7,8 @@
 // https://github.com/coverclock/com-diag-rustler
 
 extern crate rustler;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 use rustler::ticks::ticks;
 use rustler::throttle::throttle;
@@ -67,6 +69,183 @@ fn test_gcra_240_clone() {
     println!("D5={}", duplicate.to_string());
 }
 
+#[test]
+fn test_gcra_260_peek() {
+    let increment: ticks::Ticks = 100;
+    let limit: ticks::Ticks = 250;
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(increment, limit, 0);
+    let mut now: ticks::Ticks = 0;
+    let mut before: String;
+    let mut copy: gcra::Gcra;
+    let mut delay: ticks::Ticks;
+    let mut then: ticks::Ticks;
+    /**/
+    for ii in 0..1000 {
+        now += ((ii * 37) % (2 * increment)) as ticks::Ticks;
+        for events in 0..8 {
+            before = throttle.to_string();
+            delay = throttle.peek(now, events);
+            assert!(throttle.to_string() == before);
+            assert!(delay >= 0);
+            copy = throttle;
+            if events <= 1 {
+                assert!(delay == copy.request(now));
+            }
+            then = now;
+            for _ in 0..events {
+                then += copy.request(then);
+                assert!(copy.request(then) == 0);
+                copy.commit();
+            }
+            if events > 0 {
+                assert!((then - now) == delay);
+            }
+        }
+        throttle.admits(now, ((ii % 4) + 1) as throttle::Events);
+    }
+}
+
+#[test]
+fn test_gcra_265_peek_overflow() {
+    let increment: ticks::Ticks = 100;
+    let limit: ticks::Ticks = 250;
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(increment, limit, 0);
+    throttle.admits(0, 3);
+    /* So many events saturate the delay instead of overflowing it. */
+    assert!(throttle.peek(0, i64::max_value()) == (i64::max_value() - limit));
+    assert!(throttle.peek(0, i64::max_value() / 2) == (i64::max_value() - limit));
+    assert!(throttle.peek(0, (i64::max_value() / increment) - 3) > 0);
+}
+
+#[test]
+fn test_gcra_280_checkpoint() {
+    let mut original: gcra::Gcra = gcra::Gcra::new().init(100, 250, 0);
+    let mut now: ticks::Ticks = harness::churn(& mut original, 0, 150, 1000);
+    now += 17;
+    let checkpoint: gcra::Checkpoint = original.checkpoint(now);
+    println!("checkpoint={:?}", checkpoint);
+    let epoch: ticks::Ticks = 1_234_567_890;
+    let mut restored: gcra::Gcra = gcra::Gcra::new().restore(&checkpoint, epoch).unwrap();
+    println!("O={}", original.to_string());
+    println!("R={}", restored.to_string());
+    assert!(restored.checkpoint(epoch) == checkpoint);
+    harness::lockstep(& mut original, now, & mut restored, epoch, 250, 10000);
+}
+
+#[test]
+fn test_gcra_285_overflow() {
+    let mut original: gcra::Gcra = gcra::Gcra::new().init(100, 250, 0);
+    let now: ticks::Ticks = harness::churn(& mut original, 0, 150, 1000);
+    let mut checkpoint: gcra::Checkpoint = original.checkpoint(now);
+    checkpoint.then = i64::min_value();
+    let mut restored: gcra::Gcra = gcra::Gcra::new().init(100, 250, 0);
+    let before: gcra::Checkpoint = restored.checkpoint(0);
+    assert!(restored.restore(&checkpoint, 1).is_none());
+    assert!(restored.checkpoint(0) == before);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_gcra_290_serde() {
+    let mut original: gcra::Gcra = gcra::Gcra::new().init(100, 250, 0);
+    let now: ticks::Ticks = harness::churn(& mut original, 0, 150, 1000);
+    let serialized: String = serde_json::to_string(&original.checkpoint(now)).unwrap();
+    println!("serialized={}", serialized);
+    let checkpoint: gcra::Checkpoint = serde_json::from_str(&serialized).unwrap();
+    assert!(checkpoint == original.checkpoint(now));
+    let epoch: ticks::Ticks = ticks::now();
+    let mut restored: gcra::Gcra = gcra::Gcra::new().restore(&checkpoint, epoch).unwrap();
+    harness::lockstep(& mut original, now, & mut restored, epoch, 250, 10000);
+}
+
+#[test]
+fn test_gcra_295_remaining() {
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(100, gcra::jittertolerance(100, 4), 0);
+    let mut now: ticks::Ticks = 0;
+    assert!(throttle.get_increment() == 100);
+    assert!(throttle.get_limit() == 300);
+    assert!(throttle.get_burstsize() == 4);
+    assert!(throttle.get_remaining(now) == 4);
+    assert!(throttle.get_retry(now) == 0);
+    assert!(throttle.get_reset(now) == 0);
+    assert!(throttle.admit(now));
+    assert!(throttle.get_remaining(now) == 3);
+    assert!(throttle.get_reset(now) == 100);
+    assert!(throttle.admits(now, 3));
+    assert!(throttle.get_remaining(now) == 0);
+    assert!(throttle.get_retry(now) == 100);
+    assert!(throttle.get_reset(now) == 400);
+    assert!(throttle.get_remaining(now + 100) == 1);
+    assert!(throttle.get_retry(now + 100) == 0);
+    assert!(throttle.get_remaining(now + 400) == 4);
+    assert!(throttle.get_reset(now + 400) == 0);
+    assert!(throttle.get_reset(now + 500) == 0);
+    /* Exactly the remaining events are admitted back to back. */
+    for ii in 0..1000 {
+        now += ((ii * 53) % 250) as ticks::Ticks;
+        let remaining: throttle::Events = throttle.get_remaining(now);
+        if remaining > 0 {
+            assert!(throttle.peek(now, remaining) == 0);
+        }
+        assert!(throttle.peek(now, remaining + 1) > 0);
+        assert!((throttle.get_retry(now) == 0) == (remaining > 0));
+        throttle.admits(now, ((ii % 3) + 1) as throttle::Events);
+    }
+    assert!(gcra::Gcra::new().get_burstsize() == i64::max_value());
+}
+
+#[test]
+fn test_gcra_297_reconfigure() {
+    let mut throttle: gcra::Gcra = gcra::Gcra::new().init(100, 300, 0);
+    assert!(throttle.admits(0, 10));
+    assert!(throttle.get_reset(200) == 800);
+    /* Halving the increment halves the deficit instead of forgetting it. */
+    let mut faster: gcra::Gcra = throttle;
+    faster.reconfigure(50, 150, 200);
+    println!("faster={}", faster.to_string());
+    assert!(faster.get_increment() == 50);
+    assert!(faster.get_limit() == 150);
+    assert!(faster.get_reset(200) == 400);
+    assert!(faster.peek(200, 1) == 250);
+    assert!(faster.is_alarmed());
+    assert!(gcra::Gcra::new().init(50, 150, 200).peek(200, 1) == 0);
+    /* Doubling it doubles the deficit. */
+    let mut slower: gcra::Gcra = throttle;
+    slower.reconfigure(200, 600, 200);
+    assert!(slower.get_reset(200) == 1600);
+    assert!(slower.get_reset(1800) == 0);
+    assert!(slower.peek(1800, 4) == 0);
+    assert!(slower.peek(1800, 5) == 200);
+    /* The same increment and limit change nothing. */
+    let mut same: gcra::Gcra = throttle;
+    same.reconfigure(100, 300, 200);
+    assert!(same.get_reset(250) == throttle.get_reset(250));
+    assert!(same.peek(250, 3) == throttle.peek(250, 3));
+    /* A partial event is rounded up. */
+    let mut rounded: gcra::Gcra = gcra::Gcra::new().init(3, 0, 0);
+    rounded.admit(0);
+    rounded.reconfigure(2, 0, 1);
+    assert!(rounded.get_reset(1) == 2);
+    /* An empty throttle stays empty and is not alarmed. */
+    let mut empty: gcra::Gcra = throttle;
+    empty.reconfigure(1000, 0, 10000);
+    assert!(empty.get_reset(10000) == 0);
+    assert!(empty.is_empty());
+    assert!(!empty.is_alarmed());
+    /* A zero rate saturates. */
+    let mut stopped: gcra::Gcra = throttle;
+    stopped.reconfigure(i64::max_value(), 0, 200);
+    assert!(stopped.get_reset(200) == i64::max_value());
+    /* A policer keeps its count of rejected events. */
+    let mut police: gcra::PoliceGcra = gcra::PoliceGcra::new().init(100, 0, 0);
+    assert!(police.admits(0, 1));
+    assert!(!police.admits(50, 1));
+    police.reconfigure(10, 0, 50);
+    assert!(police.get_rejected() == 1);
+    assert!(police.gcra().get_reset(50) == 5);
+    assert!(police.admits(55, 1));
+}
+
 #[test]
 fn test_gcra_300_one() {
     let mut throttle: gcra::Gcra = gcra::Gcra::new();
@@ -601,6 +780,97 @@ fn test_gcra_300_variable() {
     eprintln!("gcra={}", throttle.to_string());
 }
 
+#[test]
+fn test_gcra_310_police() {
+    let mut charged: gcra::Gcra = gcra::Gcra::new();
+    let mut police: gcra::PoliceGcra = gcra::PoliceGcra::new();
+    let increment: ticks::Ticks = 100;
+    let limit: ticks::Ticks = 10;
+    let mut size: throttle::Events = 0;
+    let mut now: ticks::Ticks = 0;
+    let maximum: usize = 32768;
+    /**/
+    charged.init(increment, limit, now);
+    police.init(increment, limit, now);
+    eprintln!("police={}", police.to_string());
+    /* SUSTAINED AND CONSUME LIMIT: THE SAME */
+    for ii in 0..20 {
+        now += (size * increment) - (if ii < 10 { 0 } else { 1 });
+        assert!(charged.request(now) == 0);
+        assert!(police.request(now) == 0);
+        size = harness::blocksize(maximum) as throttle::Events;
+        assert!(charged.commits(size));
+        assert!(police.commits(size));
+        assert!(charged.get_expected() == police.get_expected());
+    }
+    /* FILL: BOTH REJECT, BUT ONLY THE CHARGED GCRA IS CHARGED */
+    now += (size * increment) - 2;
+    assert!(charged.request(now) == 2);
+    assert!(police.request(now) == 2);
+    size = harness::blocksize(maximum) as throttle::Events;
+    assert!(!charged.commits(size));
+    assert!(!police.commits(size));
+    assert!(police.get_rejected() == size);
+    assert!(police.is_alarmed());
+    assert!(police.alarmed());
+    assert!(charged.get_reset(now) == (limit + 2 + (size * increment)));
+    assert!(police.gcra().get_reset(now) == (limit + 2));
+    /* A POLICER DOES NOT HOLD THE DROPPED EMISSION AGAINST THE SOURCE */
+    now += (size * increment) + 1;
+    assert!(charged.request(now) == 1);
+    assert!(police.request(now) == 0);
+    size = harness::blocksize(maximum) as throttle::Events;
+    assert!(!charged.commits(size));
+    assert!(police.commits(size));
+    now += (size * increment) + 1;
+    assert!(charged.request(now) == 0);
+    assert!(police.request(now) == 0);
+    size = harness::blocksize(maximum) as throttle::Events;
+    assert!(!charged.commits(size));
+    assert!(police.commits(size));
+    /* UPDATE */
+    now += size * increment + limit + 1;
+    assert!(police.update(now));
+    assert!(police.is_empty());
+    assert!(!police.is_alarmed());
+    police.reset(now);
+    assert!(police.get_rejected() == 0);
+    eprintln!("police={}", police.to_string());
+}
+
+#[test]
+fn test_gcra_320_police_overdriven() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(1000, 1, frequency);
+    let limit: ticks::Ticks = gcra::jittertolerance(increment, 100);
+    let mut charged: gcra::Gcra = gcra::Gcra::new().init(increment, limit, 0);
+    let mut police: gcra::PoliceGcra = gcra::PoliceGcra::new().init(increment, limit, 0);
+    let mut now: ticks::Ticks = 0;
+    let mut offered: throttle::Events = 0;
+    let mut passed: (throttle::Events, throttle::Events) = (0, 0);
+    let mut size: throttle::Events;
+    let maximum: usize = 64;
+    /* Offer twice the contracted rate for ten seconds. */
+    while now < (10 * frequency) {
+        size = harness::blocksize(maximum) as throttle::Events;
+        offered += size;
+        if charged.request(now) == 0 {
+            passed.0 += size;
+        }
+        charged.commits(size);
+        if police.admits(now, size) {
+            passed.1 += size;
+        }
+        now += size * increment / 2;
+    }
+    eprintln!("offered={} passed={:?} rejected={}", offered, passed, police.get_rejected());
+    assert!(police.get_rejected() == (offered - passed.1));
+    /* The policer passes the contracted rate plus its burst... */
+    assert!(harness::fabs((passed.1 as f64) - 10100.0) < 200.0);
+    /* ...but the charged Gcra, penalized for what it dropped, passes little more than the burst. */
+    assert!(passed.0 < (passed.1 / 10));
+}
+
 #[test]
 fn test_gcra_400_simulated() {
     let frequency: ticks::Ticks = ticks::frequency();
@@ -616,8 +886,6 @@ fn test_gcra_400_simulated() {
     assert!(harness::fabs(result.1 - 1024.0) < (1024.0 / 100.0));
 }
 
-use std::sync;
-
 #[test]
 fn test_gcra_500_exercised() {
     let frequency: ticks::Ticks = ticks::frequency();
@@ -627,12 +895,8 @@ fn test_gcra_500_exercised() {
     let total: u64 = 512 * 60;
     let now: ticks::Ticks = ticks::now();
     let shape: gcra::Gcra = gcra::Gcra::new().init(increment, 0, now);
-    let mshape: sync::Mutex<gcra::Gcra> = sync::Mutex::new(shape);
-    let amshape: sync::Arc<sync::Mutex<gcra::Gcra>> = sync::Arc::new(mshape);
     let police: gcra::Gcra = gcra::Gcra::new().init(increment, limit, now);
-    let mpolice: sync::Mutex<gcra::Gcra> = sync::Mutex::new(police);
-    let ampolice: sync::Arc<sync::Mutex<gcra::Gcra>> = sync::Arc::new(mpolice);
-    let result = harness::exercise_gcra(amshape, ampolice, burstsize, total);
+    let result = harness::exercise(shape, police, burstsize, total, harness::Transport::Udp);
     assert!(result.0 == 0);
     assert!(result.1 == 0);
 }
@@ -646,12 +910,23 @@ fn test_gcra_600_exercised() {
     let total: u64 = 1024 * 60;
     let now: ticks::Ticks = ticks::now();
     let shape: gcra::Gcra = gcra::Gcra::new().init(increment, 0, now);
-    let mshape: sync::Mutex<gcra::Gcra> = sync::Mutex::new(shape);
-    let amshape: sync::Arc<sync::Mutex<gcra::Gcra>> = sync::Arc::new(mshape);
     let police: gcra::Gcra = gcra::Gcra::new().init(increment, limit, now);
-    let mpolice: sync::Mutex<gcra::Gcra> = sync::Mutex::new(police);
-    let ampolice: sync::Arc<sync::Mutex<gcra::Gcra>> = sync::Arc::new(mpolice);
-    let result = harness::exercise_gcra(amshape, ampolice, burstsize, total);
+    let result = harness::exercise(shape, police, burstsize, total, harness::Transport::Udp);
+    assert!(result.0 == 0);
+    assert!(result.1 == 0);
+}
+
+#[test]
+fn test_gcra_700_exercised() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let increment: ticks::Ticks = gcra::increment(1024, 1, frequency);
+    let burstsize: usize = 64;
+    let limit: ticks::Ticks = gcra::jittertolerance(increment, burstsize as throttle::Events);
+    let total: u64 = 1024 * 10;
+    let now: ticks::Ticks = ticks::now();
+    let shape: gcra::Gcra = gcra::Gcra::new().init(increment, 0, now);
+    let police: gcra::Gcra = gcra::Gcra::new().init(increment, limit, now);
+    let result = harness::exercise(shape, police, burstsize, total, harness::Transport::Tcp);
     assert!(result.0 == 0);
     assert!(result.1 == 0);
 }
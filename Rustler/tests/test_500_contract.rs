@@ -7,6 +7,8 @@
 // https://github.com/coverclock/com-diag-rustler
 
 extern crate rustler;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 
 use rustler::ticks::ticks;
 use rustler::throttle::throttle;
@@ -52,6 +54,140 @@ fn test_contract_240_clone() {
     println!("D5={}", duplicate.to_string());
 }
 
+#[test]
+fn test_contract_260_peek() {
+    let peakincrement: ticks::Ticks = 100;
+    let jittertolerance: ticks::Ticks = 20;
+    let sustainedincrement: ticks::Ticks = 300;
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, jittertolerance, sustainedincrement, 4);
+    let mut throttle: contract::Contract = contract::Contract::new().init(peakincrement, jittertolerance, sustainedincrement, bursttolerance, 0);
+    let mut now: ticks::Ticks = 0;
+    let mut before: String;
+    let mut copy: contract::Contract;
+    let mut delay: ticks::Ticks;
+    let mut then: ticks::Ticks;
+    /**/
+    for ii in 0..1000 {
+        now += ((ii * 61) % (2 * sustainedincrement)) as ticks::Ticks;
+        for events in 0..8 {
+            before = throttle.to_string();
+            delay = throttle.peek(now, events);
+            assert!(throttle.to_string() == before);
+            assert!(delay >= 0);
+            copy = throttle;
+            if events <= 1 {
+                assert!(delay == copy.request(now));
+            }
+            then = now;
+            for _ in 0..events {
+                then += copy.request(then);
+                assert!(copy.request(then) == 0);
+                copy.commit();
+            }
+            if events > 0 {
+                assert!((then - now) == delay);
+            }
+        }
+        throttle.admits(now, ((ii % 4) + 1) as throttle::Events);
+    }
+}
+
+#[test]
+fn test_contract_280_checkpoint() {
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(100, 20, 300, 4);
+    let mut original: contract::Contract = contract::Contract::new().init(100, 20, 300, bursttolerance, 0);
+    let mut now: ticks::Ticks = harness::churn(& mut original, 0, 450, 1000);
+    now += 17;
+    let checkpoint: contract::Checkpoint = original.checkpoint(now);
+    println!("checkpoint={:?}", checkpoint);
+    let epoch: ticks::Ticks = 1_234_567_890;
+    let mut restored: contract::Contract = contract::Contract::new().restore(&checkpoint, epoch).unwrap();
+    println!("O={}", original.to_string());
+    println!("R={}", restored.to_string());
+    assert!(restored.checkpoint(epoch) == checkpoint);
+    harness::lockstep(& mut original, now, & mut restored, epoch, 600, 10000);
+}
+
+#[test]
+fn test_contract_285_overflow() {
+    let mut original: contract::Contract = contract::Contract::new().init(100, 20, 300, contract::bursttolerance(100, 20, 300, 4), 0);
+    let now: ticks::Ticks = harness::churn(& mut original, 0, 150, 1000);
+    let mut checkpoint: contract::Checkpoint = original.checkpoint(now);
+    checkpoint.sustained.then = i64::min_value();
+    let mut restored: contract::Contract = contract::Contract::new().init(100, 20, 300, 1000, 0);
+    let before: contract::Checkpoint = restored.checkpoint(0);
+    assert!(restored.restore(&checkpoint, 1).is_none());
+    assert!(restored.checkpoint(0) == before);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_contract_290_serde() {
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(100, 20, 300, 4);
+    let mut original: contract::Contract = contract::Contract::new().init(100, 20, 300, bursttolerance, 0);
+    let now: ticks::Ticks = harness::churn(& mut original, 0, 450, 1000);
+    let serialized: String = serde_json::to_string(&original.checkpoint(now)).unwrap();
+    println!("serialized={}", serialized);
+    let checkpoint: contract::Checkpoint = serde_json::from_str(&serialized).unwrap();
+    assert!(checkpoint == original.checkpoint(now));
+    let epoch: ticks::Ticks = ticks::now();
+    let mut restored: contract::Contract = contract::Contract::new().restore(&checkpoint, epoch).unwrap();
+    harness::lockstep(& mut original, now, & mut restored, epoch, 600, 10000);
+}
+
+#[test]
+fn test_contract_295_remaining() {
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(100, 0, 300, 4);
+    let mut throttle: contract::Contract = contract::Contract::new().init(100, 0, 300, bursttolerance, 0);
+    let mut now: ticks::Ticks = 0;
+    assert!(throttle.get_burstsize() == 4);
+    assert!(throttle.get_remaining(now) == 4);
+    assert!(throttle.get_retry(now) == 0);
+    assert!(throttle.get_reset(now) == 0);
+    assert!(throttle.admit(now));
+    assert!(throttle.get_remaining(now) == 3);
+    assert!(throttle.get_retry(now) == 100);
+    assert!(throttle.get_reset(now) == 300);
+    assert!(throttle.get_remaining(now + 100) == 3);
+    assert!(throttle.get_reset(now + 300) == 0);
+    /* Exactly the remaining events are admitted at the peak rate. */
+    for ii in 0..1000 {
+        now += 100 + ((ii * 53) % 500) as ticks::Ticks;
+        let remaining: throttle::Events = throttle.get_remaining(now);
+        let mut copy: contract::Contract = throttle;
+        for jj in 0..remaining {
+            assert!(copy.request(now + (jj * 100)) == 0);
+            copy.commit();
+        }
+        assert!(copy.request(now + (remaining * 100)) > 0);
+        throttle.admits(now, 1);
+    }
+    assert!(contract::Contract::new().init(300, 0, 100, 0, 0).get_burstsize() == i64::max_value());
+}
+
+#[test]
+fn test_contract_297_reconfigure() {
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(100, 0, 300, 4);
+    let mut throttle: contract::Contract = contract::Contract::new().init(100, 0, 300, bursttolerance, 0);
+    assert!(throttle.admits(0, 4));
+    assert!(throttle.get_reset(0) == 1200);
+    assert!(throttle.get_remaining(0) == 0);
+    /* Cutting both rates in half doubles the deficit instead of forgetting it. */
+    let mut slower: contract::Contract = throttle;
+    slower.reconfigure(200, 0, 600, contract::bursttolerance(200, 0, 600, 4), 0);
+    println!("slower={}", slower.to_string());
+    assert!(slower.get_burstsize() == 4);
+    assert!(slower.get_reset(0) == 2400);
+    assert!(slower.get_remaining(0) == 0);
+    assert!(slower.get_retry(0) == (2400 - slower.checkpoint(0).sustained.limit));
+    assert!(contract::Contract::new().init(200, 0, 600, contract::bursttolerance(200, 0, 600, 4), 0).get_remaining(0) == 4);
+    /* Raising them pays the debt off sooner. */
+    let mut faster: contract::Contract = throttle;
+    faster.reconfigure(50, 0, 150, contract::bursttolerance(50, 0, 150, 4), 0);
+    assert!(faster.get_reset(0) == 600);
+    assert!(faster.get_remaining(600) == 4);
+}
+
 #[test]
 fn test_contract_400_simulated() {
     let frequency: ticks::Ticks = ticks::frequency();
@@ -69,8 +205,6 @@ fn test_contract_400_simulated() {
     assert!(harness::fabs(pair.1 - 1024.0) < (1024.0 / 100.0));
 }
 
-use std::sync;
-
 #[test]
 fn test_contract_500_exercised() {
     let frequency: ticks::Ticks = ticks::frequency();
@@ -82,12 +216,25 @@ fn test_contract_500_exercised() {
     let total: u64 = 512 * 60;
     let now: ticks::Ticks = ticks::now();
     let shape: contract::Contract = contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, now);
-    let mshape: sync::Mutex<contract::Contract> = sync::Mutex::new(shape);
-    let amshape: sync::Arc<sync::Mutex<contract::Contract>> = sync::Arc::new(mshape);
     let police: contract::Contract = contract::Contract::new().init(peakincrement, jittertolerance, sustainedincrement, bursttolerance, now);
-    let mpolice: sync::Mutex<contract::Contract> = sync::Mutex::new(police);
-    let ampolice: sync::Arc<sync::Mutex<contract::Contract>> = sync::Arc::new(mpolice);
-    let result = harness::exercise_contract(amshape, ampolice, burstsize, total);
+    let result = harness::exercise(shape, police, burstsize, total, harness::Transport::Udp);
+    assert!(result.0 == 0);
+    assert!(result.1 == 0);
+}
+
+#[test]
+fn test_contract_600_exercised() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(1024, 1, frequency);
+    let burstsize: usize = 64;
+    let jittertolerance: ticks::Ticks = gcra::jittertolerance(peakincrement, burstsize as throttle::Events);
+    let sustainedincrement: ticks::Ticks = gcra::increment(512, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize as throttle::Events);
+    let total: u64 = 512 * 10;
+    let now: ticks::Ticks = ticks::now();
+    let shape: contract::Contract = contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, now);
+    let police: contract::Contract = contract::Contract::new().init(peakincrement, jittertolerance, sustainedincrement, bursttolerance, now);
+    let result = harness::exercise(shape, police, burstsize, total, harness::Transport::Unix);
     assert!(result.0 == 0);
     assert!(result.1 == 0);
 }
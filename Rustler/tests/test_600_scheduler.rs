@@ -0,0 +1,144 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::scheduler::scheduler;
+
+mod harness;
+
+#[test]
+fn test_scheduler_050_sanity() {
+    let mut this: scheduler::Scheduler = scheduler::Scheduler::new();
+    assert!(this.flows() == 0);
+    assert!(this.is_empty());
+    assert!(this.eligible().is_none());
+    assert!(this.request(0).is_none());
+    assert!(this.dispatch(0).is_none());
+    let flow: scheduler::Flow = this.add(Box::new(gcra::Gcra::new().init(100, 0, 0)));
+    assert!(flow == 0);
+    assert!(this.flows() == 1);
+    assert!(this.pending(flow) == 0);
+    println!("scheduler={}", this.to_string());
+}
+
+#[test]
+fn test_scheduler_100_earliest() {
+    let mut this: scheduler::Scheduler = scheduler::Scheduler::new();
+    let slow: scheduler::Flow = this.add(Box::new(gcra::Gcra::new().init(300, 0, 0)));
+    let fast: scheduler::Flow = this.add(Box::new(gcra::Gcra::new().init(100, 0, 0)));
+    let mut now: ticks::Ticks = 0;
+    /**/
+    for _ in 0..4 {
+        this.enqueue(slow, 1, now);
+        this.enqueue(fast, 1, now);
+    }
+    assert!(this.pending(slow) == 4);
+    assert!(this.pending(fast) == 4);
+    /* Both are eligible immediately; the first queued goes first. */
+    assert!(this.request(now) == Some(0));
+    assert!(this.dispatch(now) == Some((slow, 1)));
+    assert!(this.dispatch(now) == Some((fast, 1)));
+    assert!(this.dispatch(now).is_none());
+    /* The fast flow is eligible again before the slow flow. */
+    assert!(this.eligible() == Some(100));
+    assert!(this.request(now) == Some(100));
+    now += 100;
+    assert!(this.dispatch(now) == Some((fast, 1)));
+    assert!(this.dispatch(now).is_none());
+    now += 100;
+    assert!(this.dispatch(now) == Some((fast, 1)));
+    assert!(this.dispatch(now).is_none());
+    now += 100;
+    assert!(this.dispatch(now) == Some((slow, 1)));
+    assert!(this.dispatch(now) == Some((fast, 1)));
+    assert!(this.pending(fast) == 0);
+    assert!(this.request(now) == Some(300));
+    now += 300;
+    assert!(this.dispatch(now) == Some((slow, 1)));
+    now += 300;
+    assert!(this.dispatch(now) == Some((slow, 1)));
+    assert!(this.is_empty());
+    assert!(this.dispatch(now).is_none());
+}
+
+#[test]
+fn test_scheduler_400_simulated() {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let flows: usize = 24;
+    let maximum: usize = 512;
+    let iterations: usize = 100000;
+    let link: ticks::Ticks = gcra::increment(1024 * 1024, 1, frequency);
+    let mut this: scheduler::Scheduler = scheduler::Scheduler::new();
+    let mut policers: Vec<Box<throttle::Throttle>> = Vec::new();
+    let mut totals: Vec<u64> = Vec::new();
+    let mut now: ticks::Ticks = 0;
+    let mut delay: ticks::Ticks;
+    let mut eligible: ticks::Ticks;
+    let mut earliest: ticks::Ticks;
+    let mut events: throttle::Events;
+    let mut flow: scheduler::Flow;
+    let mut admitted: bool;
+    /**/
+    for ii in 0..flows {
+        let peakrate: throttle::Events = 4096 + (ii as throttle::Events) * 512;
+        let sustainedrate: throttle::Events = 1024 + (ii as throttle::Events) * 128;
+        let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
+        let jittertolerance: ticks::Ticks = gcra::jittertolerance(peakincrement, maximum as throttle::Events);
+        let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
+        let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, maximum as throttle::Events);
+        if (ii % 2) == 0 {
+            this.add(Box::new(contract::Contract::new().init(peakincrement, 0, sustainedincrement, bursttolerance, now)));
+            policers.push(Box::new(contract::Contract::new().init(peakincrement, jittertolerance, sustainedincrement, bursttolerance, now)));
+        } else {
+            this.add(Box::new(gcra::Gcra::new().init(sustainedincrement, 0, now)));
+            policers.push(Box::new(gcra::Gcra::new().init(sustainedincrement, jittertolerance, now)));
+        }
+        totals.push(0);
+    }
+    /**/
+    for ii in 0..iterations {
+        /* Keep every flow backlogged. */
+        flow = ii % flows;
+        while this.pending(flow) < 2 {
+            this.enqueue(flow, harness::blocksize(maximum) as throttle::Events, now);
+        }
+        /* Wait until something is eligible. */
+        delay = this.request(now).unwrap();
+        assert!(delay >= 0);
+        now += delay;
+        /* Determine the earliest eligibility among all backlogged flows. */
+        earliest = ticks::Ticks::max_value();
+        for jj in 0..this.flows() {
+            if this.pending(jj) > 0 {
+                eligible = this.throttle(jj).peek(now, 1);
+                if eligible < earliest { earliest = eligible; }
+            }
+        }
+        assert!(earliest == 0);
+        let pair = this.dispatch(now).unwrap();
+        flow = pair.0;
+        events = pair.1;
+        assert!(events > 0);
+        admitted = policers[flow].admits(now, events);
+        assert!(admitted);
+        totals[flow] += events as u64;
+        /* Serialize the emission onto the link. */
+        now += link * events;
+    }
+    /**/
+    for ii in 0..flows {
+        eprintln!("flow={} total={}B sustained={}B/s police={}", ii, totals[ii], (totals[ii] as f64) * (frequency as f64) / (now as f64), policers[ii].as_string());
+        assert!(totals[ii] > 0);
+        assert!(!policers[ii].is_alarmed());
+    }
+}
@@ -0,0 +1,160 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::scheduler::scheduler;
+use rustler::fairness::fairness;
+
+mod harness;
+
+fn aggregate(peakrate: throttle::Events, sustainedrate: throttle::Events, burstsize: throttle::Events, jitter: bool, now: ticks::Ticks) -> contract::Contract {
+    let frequency: ticks::Ticks = ticks::frequency();
+    let peakincrement: ticks::Ticks = gcra::increment(peakrate, 1, frequency);
+    let mut jittertolerance: ticks::Ticks = 0;
+    if jitter { jittertolerance = gcra::jittertolerance(peakincrement, burstsize); }
+    let sustainedincrement: ticks::Ticks = gcra::increment(sustainedrate, 1, frequency);
+    let bursttolerance: ticks::Ticks = contract::bursttolerance(peakincrement, 0, sustainedincrement, burstsize);
+    contract::Contract::new().init(peakincrement, jittertolerance, sustainedincrement, bursttolerance, now)
+}
+
+#[test]
+fn test_fairness_050_sanity() {
+    let mut wfq: fairness::Wfq = fairness::Wfq::new(Box::new(aggregate(2048, 1024, 512, false, 0)));
+    assert!(wfq.flows() == 0);
+    assert!(wfq.is_empty());
+    assert!(wfq.request(0).is_none());
+    assert!(wfq.dispatch(0).is_none());
+    assert!(wfq.add(1) == 0);
+    assert!(wfq.served(0) == 0);
+    println!("wfq={}", wfq.to_string());
+    let mut drr: fairness::Drr = fairness::Drr::new(Box::new(aggregate(2048, 1024, 512, false, 0)));
+    assert!(drr.flows() == 0);
+    assert!(drr.is_empty());
+    assert!(drr.request(0).is_none());
+    assert!(drr.dispatch(0).is_none());
+    assert!(drr.add(512) == 0);
+    assert!(drr.served(0) == 0);
+    println!("drr={}", drr.to_string());
+}
+
+#[test]
+fn test_fairness_070_invalid() {
+    /* An emission of zero or negative events is refused and does not starve its flow. */
+    let mut wfq: fairness::Wfq = fairness::Wfq::new(Box::new(aggregate(2048, 1024, 512, false, 0)));
+    let flow: scheduler::Flow = wfq.add(1);
+    assert!(!wfq.enqueue(flow, 0));
+    assert!(!wfq.enqueue(flow, -1));
+    assert!(!wfq.enqueue(flow, i64::min_value()));
+    assert!(wfq.is_empty());
+    assert!(wfq.enqueue(flow, 256));
+    assert!(wfq.dispatch(0) == Some((flow, 256)));
+    assert!(wfq.served(flow) == 256);
+    let mut drr: fairness::Drr = fairness::Drr::new(Box::new(aggregate(2048, 1024, 512, false, 0)));
+    let flow: scheduler::Flow = drr.add(512);
+    assert!(!drr.enqueue(flow, 0));
+    assert!(!drr.enqueue(flow, -1));
+    assert!(!drr.enqueue(flow, i64::min_value()));
+    assert!(drr.is_empty());
+    assert!(drr.enqueue(flow, 256));
+    assert!(drr.dispatch(0) == Some((flow, 256)));
+    assert!(drr.served(flow) == 256);
+}
+
+#[test]
+fn test_fairness_100_wfq() {
+    let weights: [throttle::Events; 4] = [ 1, 2, 4, 8 ];
+    let mut this: fairness::Wfq = fairness::Wfq::new(Box::new(aggregate(4096, 2048, 512, false, 0)));
+    let mut police: contract::Contract = aggregate(4096, 2048, 512, true, 0);
+    let mut now: ticks::Ticks = 0;
+    let mut total: u64 = 0;
+    /**/
+    for weight in weights.iter() { this.add(*weight); }
+    for _ in 0..100000 {
+        for flow in 0..this.flows() {
+            while this.pending(flow) < 2 {
+                this.enqueue(flow, harness::blocksize(512) as throttle::Events);
+            }
+        }
+        now += this.request(now).unwrap();
+        let pair = this.dispatch(now).unwrap();
+        assert!(police.admits(now, pair.1));
+        total += pair.1 as u64;
+    }
+    /**/
+    for flow in 0..this.flows() {
+        let share: f64 = (this.served(flow) as f64) / (total as f64);
+        let expected: f64 = (weights[flow] as f64) / 15.0;
+        eprintln!("wfq: flow={} weight={} served={}B share={} expected={}", flow, weights[flow], this.served(flow), share, expected);
+        assert!(harness::fabs(share - expected) < (expected / 20.0));
+    }
+    eprintln!("wfq: sustained={}B/s", (total as f64) * (ticks::frequency() as f64) / (now as f64));
+}
+
+#[test]
+fn test_fairness_200_drr() {
+    let quanta: [throttle::Events; 4] = [ 512, 1024, 2048, 4096 ];
+    let mut this: fairness::Drr = fairness::Drr::new(Box::new(aggregate(4096, 2048, 512, false, 0)));
+    let mut police: contract::Contract = aggregate(4096, 2048, 512, true, 0);
+    let mut now: ticks::Ticks = 0;
+    let mut total: u64 = 0;
+    /**/
+    for quantum in quanta.iter() { this.add(*quantum); }
+    for _ in 0..100000 {
+        for flow in 0..this.flows() {
+            while this.pending(flow) < 2 {
+                this.enqueue(flow, harness::blocksize(512) as throttle::Events);
+            }
+        }
+        now += this.request(now).unwrap();
+        let pair = this.dispatch(now).unwrap();
+        assert!(police.admits(now, pair.1));
+        total += pair.1 as u64;
+    }
+    /**/
+    for flow in 0..this.flows() {
+        let share: f64 = (this.served(flow) as f64) / (total as f64);
+        let expected: f64 = (quanta[flow] as f64) / 7680.0;
+        eprintln!("drr: flow={} quantum={}B served={}B share={} expected={}", flow, quanta[flow], this.served(flow), share, expected);
+        assert!(harness::fabs(share - expected) < (expected / 20.0));
+    }
+    eprintln!("drr: sustained={}B/s", (total as f64) * (ticks::frequency() as f64) / (now as f64));
+}
+
+#[test]
+fn test_fairness_300_idle() {
+    let mut this: fairness::Drr = fairness::Drr::new(Box::new(aggregate(4096, 2048, 512, false, 0)));
+    let mut now: ticks::Ticks = 0;
+    let busy = this.add(512);
+    let idle = this.add(512);
+    /* An idle flow does not accumulate credit. */
+    for _ in 0..100 {
+        this.enqueue(busy, 256);
+        now += this.request(now).unwrap();
+        assert!(this.dispatch(now) == Some((busy, 256)));
+    }
+    assert!(this.served(idle) == 0);
+    this.enqueue(idle, 256);
+    this.enqueue(idle, 256);
+    this.enqueue(idle, 256);
+    this.enqueue(busy, 256);
+    now += this.request(now).unwrap();
+    assert!(this.dispatch(now) == Some((idle, 256)));
+    now += this.request(now).unwrap();
+    assert!(this.dispatch(now) == Some((idle, 256)));
+    now += this.request(now).unwrap();
+    assert!(this.dispatch(now) == Some((busy, 256)));
+    now += this.request(now).unwrap();
+    assert!(this.dispatch(now) == Some((idle, 256)));
+    assert!(this.is_empty());
+}
@@ -0,0 +1,140 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use rustler::ticks::ticks;
+use rustler::throttle::throttle;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::shaper::shaper;
+
+mod harness;
+
+/// Offer emissions of the specified size at the specified interval to a shaper
+/// whose throttle is slower than the offered load, polling it as the throttle
+/// permits, and return the mean time in ticks spent in the queue by the
+/// emissions that were not dropped.
+fn congest(this: & mut shaper::Shaper<ticks::Ticks>, size: throttle::Events, interval: ticks::Ticks, iterations: usize) -> f64 {
+    let mut now: ticks::Ticks = 0;
+    let mut police: gcra::Gcra = gcra::Gcra::new().init(100, 0, 0);
+    let mut sojourn: ticks::Ticks = 0;
+    let mut count: u64 = 0;
+    let mut offered: u64 = 0;
+    let mut emitted: u64 = 0;
+    let mut arrival: ticks::Ticks;
+    /**/
+    for _ in 0..iterations {
+        this.offer(now, size, now);
+        offered += size as u64;
+        arrival = now + interval;
+        loop {
+            match this.request(now) {
+                Some(delay) if (now + delay) < arrival => { now += delay; },
+                _ => { break; },
+            }
+            if let Some((then, events)) = this.poll(now) {
+                assert!(police.admits(now, events));
+                sojourn += now - then;
+                count += 1;
+                emitted += events as u64;
+            }
+        }
+        now = arrival;
+    }
+    /**/
+    assert!(offered == (emitted + this.dropped() + (this.backlog() as u64)));
+    eprintln!("congest: shaper={} offered={} emitted={} dropped={} sojourn={}", this.to_string(), offered, emitted, this.dropped(), (sojourn as f64) / (count as f64));
+    (sojourn as f64) / (count as f64)
+}
+
+#[test]
+fn test_shaper_050_sanity() {
+    let mut this: shaper::Shaper<u8> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 1000, shaper::Policy::TailDrop);
+    assert!(this.is_empty());
+    assert!(this.backlog() == 0);
+    assert!(this.dropped() == 0);
+    assert!(this.drops() == 0);
+    assert!(this.request(0).is_none());
+    assert!(this.poll(0).is_none());
+    assert!(this.throttle().is_empty());
+    println!("shaper={}", this.to_string());
+}
+
+#[test]
+fn test_shaper_100_taildrop() {
+    let mut this: shaper::Shaper<usize> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 1000, shaper::Policy::TailDrop);
+    let mut now: ticks::Ticks = 0;
+    /**/
+    for ii in 0..20 {
+        assert!(this.offer(ii, 100, now) == (ii < 10));
+    }
+    assert!(this.len() == 10);
+    assert!(this.backlog() == 1000);
+    assert!(this.dropped() == 1000);
+    assert!(this.drops() == 10);
+    assert!(!this.offer(20, 1001, now));
+    assert!(this.dropped() == 2001);
+    /**/
+    for ii in 0..10 {
+        now += this.request(now).unwrap();
+        assert!(this.poll(now) == Some((ii, 100)));
+    }
+    assert!(this.is_empty());
+}
+
+#[test]
+fn test_shaper_200_headdrop() {
+    let mut this: shaper::Shaper<usize> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 1000, shaper::Policy::HeadDrop);
+    let mut now: ticks::Ticks = 0;
+    /**/
+    for ii in 0..20 {
+        assert!(this.offer(ii, 100, now));
+    }
+    assert!(this.len() == 10);
+    assert!(this.backlog() == 1000);
+    assert!(this.dropped() == 1000);
+    assert!(this.drops() == 10);
+    /**/
+    for ii in 10..20 {
+        now += this.request(now).unwrap();
+        assert!(this.poll(now) == Some((ii, 100)));
+    }
+    assert!(this.is_empty());
+}
+
+#[test]
+fn test_shaper_300_red() {
+    let policy: shaper::Policy = shaper::Policy::Red { minimum: 250, maximum: 750, probability: 0.1 };
+    /* Uncongested: no drops. */
+    let mut this: shaper::Shaper<ticks::Ticks> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 1000, policy);
+    congest(& mut this, 10, 1000, 10000);
+    assert!(this.dropped() == 0);
+    /* Congested: early drops keep the queue from filling. */
+    let mut this: shaper::Shaper<ticks::Ticks> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 1000, policy);
+    congest(& mut this, 10, 800, 100000);
+    assert!(this.dropped() > 0);
+    assert!(this.backlog() < 1000);
+}
+
+#[test]
+fn test_shaper_400_codel() {
+    let policy: shaper::Policy = shaper::Policy::Codel { target: 5000, interval: 100000 };
+    /* Uncongested: no drops. */
+    let mut this: shaper::Shaper<ticks::Ticks> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 1000, policy);
+    congest(& mut this, 10, 1000, 10000);
+    assert!(this.dropped() == 0);
+    /* Congested: CoDel holds the delay well below what tail drop allows. */
+    let mut that: shaper::Shaper<ticks::Ticks> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 10000, shaper::Policy::TailDrop);
+    let taildrop: f64 = congest(& mut that, 10, 800, 100000);
+    let mut this: shaper::Shaper<ticks::Ticks> = shaper::Shaper::new(Box::new(gcra::Gcra::new().init(100, 0, 0)), 10000, policy);
+    let codel: f64 = congest(& mut this, 10, 800, 100000);
+    assert!(that.dropped() > 0);
+    assert!(this.dropped() > 0);
+    assert!(codel < (taildrop / 2.0));
+}
@@ -0,0 +1,166 @@
+/* vi: set ts=4 expandtab shiftwidth=4: */
+
+// Copyright 2018 by the Digital Aggregates Corporation
+// Licensed under the terms in LICENSE.txt
+// Author: Chip Overclock
+// mailto:coverclock@diag.com
+// https://github.com/coverclock/com-diag-rustler
+
+extern crate rustler;
+
+use std::env;
+use std::fs;
+use std::panic;
+use std::path;
+use std::process;
+use std::thread;
+use rustler::ticks::ticks;
+use rustler::throttle::throttle::Throttle;
+use rustler::gcra::gcra;
+use rustler::contract::contract;
+use rustler::bucket::bucket;
+use rustler::shared::shared;
+
+/// Return a path in the temporary directory unique to this process and test.
+fn pathname(name: & str) -> path::PathBuf {
+    let mut path: path::PathBuf = env::temp_dir();
+    path.push(format!("rustler-{}-{}.dat", process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+/// Starting at the specified monotonic time in ticks, shape as fast as the
+/// shared throttle allows for the specified duration in ticks, and return the
+/// number of events emitted.
+fn shape(path: & path::Path, start: ticks::Ticks, duration: ticks::Ticks) -> u64 {
+    let increment: ticks::Ticks = ticks::frequency() / 1000;
+    let mut this: shared::Shared<gcra::Gcra> = shared::Shared::open(path, gcra::Gcra::new().init(increment, gcra::jittertolerance(increment, 10), start)).unwrap();
+    let mut count: u64 = 0;
+    let mut delay: ticks::Ticks;
+    let until: ticks::Ticks = start + duration;
+    ticks::sleep(start - ticks::monotonic());
+    while ticks::monotonic() < until {
+        delay = this.with(|throttle| {
+            let delay: ticks::Ticks = throttle.request(ticks::monotonic());
+            if delay == 0 { throttle.commit(); }
+            delay
+        });
+        if delay == 0 {
+            count += 1;
+        } else {
+            ticks::sleep(delay);
+        }
+    }
+    this.update(ticks::monotonic());
+    count
+}
+
+#[test]
+fn test_shared_050_sanity() {
+    let path: path::PathBuf = pathname("050");
+    let now: ticks::Ticks = ticks::monotonic();
+    let mut one: shared::Shared<gcra::Gcra> = shared::Shared::open(&path, gcra::Gcra::new().init(100, 0, now)).unwrap();
+    let two: shared::Shared<gcra::Gcra> = shared::Shared::open(&path, gcra::Gcra::new().init(200, 0, now)).unwrap();
+    println!("one={}", one.to_string());
+    println!("two={}", two.to_string());
+    assert!(one.size_of() == gcra::SIZE_OF_GCRA);
+    assert!(two.get_expected() == 0);
+    assert!(one.admits(now, 3));
+    /* The second open did not reinitialize, and sees the first update. */
+    assert!(two.get_expected() == 300);
+    assert!(two.peek(now, 1) == 300);
+    assert!(shared::Shared::open(&path, contract::Contract::new()).is_err());
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_shared_060_tag() {
+    let path: path::PathBuf = pathname("060");
+    let now: ticks::Ticks = ticks::monotonic();
+    let _one: shared::Shared<gcra::Gcra> = shared::Shared::open(&path, gcra::Gcra::new().init(100, 0, now)).unwrap();
+    /* A Bucket is the same size as a Gcra but is not a Gcra. */
+    assert!(bucket::SIZE_OF_BUCKET == gcra::SIZE_OF_GCRA);
+    match shared::Shared::open(&path, bucket::Bucket::new(100, 0, now)) {
+        Ok(_) => panic!("opened a Gcra as a Bucket"),
+        Err(error) => { println!("error={}", error); assert!(error.to_string().contains("type")); },
+    }
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_shared_070_panic() {
+    let path: path::PathBuf = pathname("070");
+    let now: ticks::Ticks = ticks::monotonic();
+    let mut one: shared::Shared<gcra::Gcra> = shared::Shared::open(&path, gcra::Gcra::new().init(100, 0, now)).unwrap();
+    /* A panic while holding the lock releases it as it unwinds. */
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        one.with(|throttle| { throttle.admits(now, 1); panic!("panicked while locked"); })
+    }));
+    assert!(result.is_err());
+    let two: shared::Shared<gcra::Gcra> = shared::Shared::open(&path, gcra::Gcra::new().init(200, 0, now)).unwrap();
+    assert!(two.get_expected() == 100);
+    one.admits(now, 1);
+    assert!(two.get_expected() == 200);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_shared_100_threads() {
+    let path: path::PathBuf = pathname("100");
+    let duration: ticks::Ticks = ticks::frequency();
+    let start: ticks::Ticks = ticks::monotonic() + (duration / 2);
+    let mut threads = Vec::new();
+    for _ in 0..4 {
+        let path: path::PathBuf = path.clone();
+        threads.push(thread::spawn(move || { shape(&path, start, duration) }));
+    }
+    let mut total: u64 = 0;
+    for handle in threads {
+        let count: u64 = handle.join().unwrap();
+        eprintln!("thread: count={}", count);
+        total += count;
+    }
+    eprintln!("threads: total={}", total);
+    assert!(total >= 900);
+    assert!(total <= 1050);
+    let _ = fs::remove_file(&path);
+}
+
+#[test]
+fn test_shared_400_processes() {
+    let duration: ticks::Ticks = ticks::frequency();
+    /* A child process shapes and reports its count. */
+    if let Ok(name) = env::var("RUSTLER_SHARED_PATH") {
+        let start: ticks::Ticks = env::var("RUSTLER_SHARED_START").unwrap().parse().unwrap();
+        println!("count={}", shape(path::Path::new(&name), start, duration));
+        return;
+    }
+    let path: path::PathBuf = pathname("400");
+    let start: ticks::Ticks = ticks::monotonic() + duration;
+    let mut children = Vec::new();
+    for _ in 0..4 {
+        children.push(process::Command::new(env::current_exe().unwrap())
+            .args(["test_shared_400_processes", "--exact", "--nocapture", "--test-threads=1"])
+            .env("RUSTLER_SHARED_PATH", &path)
+            .env("RUSTLER_SHARED_START", start.to_string())
+            .stdout(process::Stdio::piped())
+            .spawn().unwrap());
+    }
+    let mut total: u64 = 0;
+    for child in children {
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success());
+        let text: String = String::from_utf8_lossy(&output.stdout).into_owned();
+        for line in text.lines() {
+            if let Some(value) = line.split("count=").nth(1) {
+                let count: u64 = value.trim().parse().unwrap();
+                eprintln!("process: count={}", count);
+                total += count;
+            }
+        }
+    }
+    eprintln!("processes: total={}", total);
+    assert!(total >= 900);
+    assert!(total <= 1050);
+    let _ = fs::remove_file(&path);
+}